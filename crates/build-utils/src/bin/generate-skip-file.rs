@@ -0,0 +1,56 @@
+//! Updates the skip file from the JSON report of a test run, e.g.
+//! `generate-skip-file --report report.json`.
+use std::path::PathBuf;
+
+use build_utils::{filter::Filter, report::ResultsReport};
+use clap::Parser;
+
+const SKIPPED_TESTS: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../blockchain-tests-skip.yml"
+);
+
+#[derive(Debug, Parser)]
+#[command(about = "Updates the skip file from the JSON report of a test run")]
+struct Args {
+    /// Path to the JSON report written by `ef-runner --report-json`.
+    #[arg(long)]
+    report: PathBuf,
+    /// Path to the skip file to update.
+    #[arg(long, default_value = SKIPPED_TESTS)]
+    skip_file: String,
+    /// Remove the skipped tests which now pass.
+    #[arg(long)]
+    remove_passing: bool,
+}
+
+fn main() -> Result<(), eyre::Error> {
+    let args = Args::parse();
+
+    let report = ResultsReport::load_file(&args.report)?;
+    let mut filter = Filter::load_file(&args.skip_file)?;
+    let update = filter.apply_report(&report, args.remove_passing);
+    filter.save_file(&args.skip_file)?;
+
+    println!(
+        "{} failing tests added to the skip file",
+        update.added.len()
+    );
+    for (folder, test) in update.added.iter() {
+        println!("    {folder}::{test}");
+    }
+    let status = if args.remove_passing {
+        "removed from the skip file"
+    } else {
+        "still in the skip file"
+    };
+    println!(
+        "{} skipped tests now pass ({status})",
+        update.now_passing.len()
+    );
+    for (folder, test) in update.now_passing.iter() {
+        println!("    {folder}::{test}");
+    }
+
+    Ok(())
+}
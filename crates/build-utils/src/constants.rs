@@ -3,7 +3,6 @@ use lazy_static::lazy_static;
 use std::collections::HashMap;
 
 pub const ROOT: &str = "GeneralStateTests";
-pub const FORK: &str = "Cancun";
 
 lazy_static! {
     // A registry of the most common addresses and their associated secret keys.
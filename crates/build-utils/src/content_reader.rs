@@ -64,6 +64,57 @@ impl ContentReader {
             .clone())
     }
 
+    /// Returns the exceptions expected by the fixture for each block, as an
+    /// array holding `Value::Null` for the valid blocks.
+    pub fn expected_exceptions(test_case: &Value) -> Result<Value, eyre::Error> {
+        Ok(Value::Array(
+            Self::blocks(test_case)?
+                .iter()
+                .map(|block| block.get("expectException").cloned().unwrap_or(Value::Null))
+                .collect(),
+        ))
+    }
+
+    /// Returns the blocks of the test case, in order of execution.
+    pub fn blocks(test_case: &Value) -> Result<Vec<Value>, eyre::Error> {
+        let blocks = test_case
+            .get("blocks")
+            .ok_or_else(|| eyre!("key 'blocks' not found"))?
+            .as_array()
+            .ok_or_else(|| eyre!("'blocks' is not an array"))?;
+        if blocks.is_empty() {
+            return Err(eyre!("'blocks' array is empty"));
+        }
+        Ok(blocks.clone())
+    }
+
+    /// Returns the names of the variants of a state test for the given fork,
+    /// formatted as `{case_name}_d{data}g{gas}v{value}_{fork}`, matching the
+    /// names of the corresponding `BlockchainTests` cases.
+    pub fn state_variants(case_name: &str, test_case: &Value, fork: &str) -> Vec<String> {
+        test_case
+            .get("post")
+            .and_then(|post| post.get(fork))
+            .and_then(Value::as_array)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| {
+                        let indexes = entry.get("indexes")?;
+                        Some(format!(
+                            "{}_d{}g{}v{}_{}",
+                            case_name,
+                            indexes.get("data")?.as_u64()?,
+                            indexes.get("gas")?.as_u64()?,
+                            indexes.get("value")?.as_u64()?,
+                            fork
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     pub fn block(test_case: &Value) -> Result<Value, eyre::Error> {
         // Attempt to get the "blocks" value
         let blocks = test_case
@@ -161,3 +212,48 @@ fn get_secret_key_from_block(case_without_secret: &Value) -> Result<String, eyre
         .map(|addr| format!("\"{}\"", addr))
         .ok_or_else(|| eyre!("No secret key found for {}", sender_address))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_variants() {
+        // Given
+        let content = std::fs::read_to_string(
+            "../ef-testing/ethereum-tests/GeneralStateTests/VmTests/vmArithmeticTest/add.json",
+        )
+        .unwrap();
+        let cases: BTreeMap<String, Value> = serde_json::from_str(&content).unwrap();
+
+        // When
+        let variants = ContentReader::state_variants("add", &cases["add"], "Cancun");
+
+        // Then
+        let expected: Vec<String> = (0..5).map(|d| format!("add_d{d}g0v0_Cancun")).collect();
+        assert_eq!(variants, expected);
+    }
+
+    #[test]
+    fn test_expected_exceptions() {
+        // Given
+        let case = serde_json::json!({
+            "blocks": [
+                {"rlp": "0x00"},
+                {"rlp": "0x01", "expectException": "TransactionException.NONCE_MISMATCH_TOO_LOW"},
+            ]
+        });
+
+        // When
+        let blocks = ContentReader::blocks(&case).unwrap();
+        let expected_exceptions = ContentReader::expected_exceptions(&case).unwrap();
+
+        // Then
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            expected_exceptions,
+            serde_json::json!([null, "TransactionException.NONCE_MISMATCH_TOO_LOW"])
+        );
+        assert!(ContentReader::blocks(&serde_json::json!({ "blocks": [] })).is_err());
+    }
+}
@@ -4,8 +4,12 @@ use rayon::prelude::*;
 use serde_json::Value;
 
 use crate::{
-    constants::FORK, content_reader::ContentReader, dir_reader::DirReader, filter::Filter,
+    content_reader::ContentReader,
+    dir_reader::DirReader,
+    filter::{Expectation, Filter},
+    fork::Fork,
     path::PathWrapper,
+    shard::Shard,
 };
 
 /// The `TestConverter` is used to convert the directory structure
@@ -17,8 +21,10 @@ use crate::{
 /// List of tests: [randomStatetest0.json, randomStatetest1.json, ...]
 /// Inner tests: [`randomStatetest0_d0g0v0_Cancun`, `randomStatetest0_d1g0v0_Cancun`,
 /// ..., `randomStatetest1_d0g0v0_Cancun`, `randomStatetest1_d1g0v0_Cancun`, ...]
-/// Generated String:
+/// Generated String (for the Cancun fork):
 /// r#"
+/// mod cancun {
+/// use super::*;
 /// mod randomStatetest0 {
 ///   use super::*;
 ///   #[test]
@@ -39,15 +45,64 @@ use crate::{
 ///   }
 ///   ...
 /// }
+/// }
 /// "#
 pub struct EfTests<'a> {
     directory: DirReader<'a>,
     filter: Arc<Filter>,
+    format: TestFormat,
+    forks: &'a [Fork],
+    shard: Option<Shard>,
+}
+
+/// Format of the test files to convert.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TestFormat {
+    /// Tests from the `BlockchainTests/GeneralStateTests` folder. Each case
+    /// in a file is converted into one rust test.
+    #[default]
+    Blockchain,
+    /// Tests from the `GeneralStateTests` folder. Each case in a file is
+    /// converted into one rust test, which expands all the indexed variants
+    /// of the case.
+    State,
 }
 
 impl<'a> EfTests<'a> {
     pub const fn new(directory: DirReader<'a>, filter: Arc<Filter>) -> Self {
-        Self { directory, filter }
+        Self {
+            directory,
+            filter,
+            format: TestFormat::Blockchain,
+            forks: &[Fork::DEFAULT],
+            shard: None,
+        }
+    }
+
+    /// Sets the forks to generate the tests for. Tests of each fork are
+    /// generated in a dedicated module.
+    pub const fn with_forks(mut self, forks: &'a [Fork]) -> Self {
+        self.forks = forks;
+        self
+    }
+
+    /// Sets the shard of the tests to generate. Tests of the other shards are
+    /// not generated.
+    pub const fn with_shard(mut self, shard: Option<Shard>) -> Self {
+        self.shard = shard;
+        self
+    }
+
+    /// Returns true if the test case belongs to the shard, if any.
+    fn is_in_shard(&self, fork: Fork, parent_dir: &str, case_name: &str) -> bool {
+        self.shard
+            .is_none_or(|shard| shard.contains(&Shard::key(fork, parent_dir, case_name)))
+    }
+
+    /// Sets the format of the test files to convert.
+    pub const fn with_format(mut self, format: TestFormat) -> Self {
+        self.format = format;
+        self
     }
 
     /// Converts the given directory into a String containing all
@@ -59,23 +114,82 @@ impl<'a> EfTests<'a> {
             .map(|(folder_name, node)| {
                 let mut acc = String::new();
                 acc += &Self::format_to_folder();
-                acc += &Self::format_to_module(folder_name);
-                acc += &self.convert_folders(node, folder_name)?;
-                acc += "}";
+                for fork in self.forks {
+                    acc += &Self::format_to_module(&fork.module_name());
+                    acc += &Self::format_to_module(folder_name);
+                    acc += &self.convert_folders(node, folder_name, *fork)?;
+                    acc += "}}";
+                }
                 Ok((folder_name.clone(), acc))
             })
             .collect()
     }
 
     /// Converts the given directory into a String.
-    fn convert_folders(&self, node: &DirReader, parent_dir: &str) -> Result<String, eyre::Error> {
+    fn convert_folders(
+        &self,
+        node: &DirReader,
+        parent_dir: &str,
+        fork: Fork,
+    ) -> Result<String, eyre::Error> {
         let mut acc = String::new();
         for (dir_name, sub_node) in &node.sub_dirs {
             acc += &Self::format_to_module(dir_name);
-            acc += &self.convert_folders(sub_node, dir_name)?;
+            acc += &self.convert_folders(sub_node, dir_name, fork)?;
             acc += "}";
         }
-        acc += self.convert_files(&node.files, parent_dir)?.as_str();
+        acc += match self.format {
+            TestFormat::Blockchain => self.convert_files(&node.files, parent_dir, fork)?,
+            TestFormat::State => self.convert_state_files(&node.files, parent_dir, fork)?,
+        }
+        .as_str();
+        Ok(acc)
+    }
+
+    /// Converts the given state test files into a String.
+    fn convert_state_files(
+        &self,
+        files: &[PathWrapper],
+        parent_dir: &str,
+        fork: Fork,
+    ) -> Result<String, eyre::Error> {
+        let mut acc = String::new();
+        for file_path in files {
+            let content = file_path.read_file_to_string()?;
+            let cases: BTreeMap<String, serde_json::Value> = serde_json::from_str(&content)?;
+            for (case_name, content) in cases.iter() {
+                let variants = ContentReader::state_variants(case_name, content, &fork.to_string());
+                if variants.is_empty() || !self.is_in_shard(fork, parent_dir, case_name) {
+                    continue;
+                }
+                let expectation = self.filter.expectation(file_path, None, fork);
+                // The variants of a case are run by a single test, so a variant
+                // can be skipped but not expected to fail on its own.
+                let mut skipped_variants = Vec::new();
+                for variant in variants {
+                    match self
+                        .filter
+                        .expectation(file_path, Some(variant.clone()), fork)
+                    {
+                        Expectation::Pass => {}
+                        Expectation::Skip(_) => skipped_variants.push(variant),
+                        Expectation::Fail(_) => {
+                            return Err(eyre::eyre!(
+                                "{variant} is marked as xfail, expected failures of state tests are only supported for whole files"
+                            ))
+                        }
+                    }
+                }
+                acc += &Self::format_to_state_test(
+                    case_name,
+                    parent_dir,
+                    content,
+                    &skipped_variants,
+                    fork,
+                    expectation,
+                )?;
+            }
+        }
         Ok(acc)
     }
 
@@ -85,6 +199,7 @@ impl<'a> EfTests<'a> {
         &self,
         files: &[PathWrapper],
         parent_dir: &str,
+        fork: Fork,
     ) -> Result<String, eyre::Error> {
         let mut acc = String::new();
         for file_path in files {
@@ -93,17 +208,27 @@ impl<'a> EfTests<'a> {
             let file_contents = cases
                 .par_iter()
                 .map(|(case_name, content)| {
-                    if !(case_name.ends_with(FORK) || case_name.contains(&format!("fork_{}", FORK)))
+                    if !fork.is_case_of(case_name, content)
+                        || !self.is_in_shard(fork, parent_dir, case_name)
                     {
                         return Ok(String::new());
                     }
-                    let is_skipped = self.filter.is_skipped(file_path, Some(case_name.clone()));
-                    let secret_key = if is_skipped {
+                    let expectation =
+                        self.filter
+                            .expectation(file_path, Some(case_name.clone()), fork);
+                    let secret_key = if matches!(expectation, Expectation::Skip(_)) {
                         String::default() // secret key is not needed if the test is skipped
                     } else {
                         ContentReader::secret_key(file_path.clone(), content)?
                     };
-                    Self::format_to_test(case_name, parent_dir, &secret_key, content, is_skipped)
+                    Self::format_to_test(
+                        case_name,
+                        parent_dir,
+                        &secret_key,
+                        content,
+                        fork,
+                        expectation,
+                    )
                 })
                 .collect::<Result<Vec<String>, eyre::Error>>()?;
             acc += &file_contents.into_iter().fold(String::new(), |mut acc, s| {
@@ -121,7 +246,9 @@ impl<'a> EfTests<'a> {
         use std::{str::FromStr};
 
         use ef_testing::models::case::BlockchainTestCase;
-        use ef_testing::test_utils::{setup, TestMonitor};
+        use ef_testing::models::state_case::StateTestCase;
+        use ef_testing::models::{Fork, StateTest};
+        use ef_testing::test_utils::{expect_failure, setup, TestMonitor};
         use ef_testing::monitor_test;
         use ef_testing::traits::Case;
         use ef_tests::models::{Block, Account, State};
@@ -147,13 +274,24 @@ impl<'a> EfTests<'a> {
         parent_dir: &str,
         secret_key: &String,
         content: &Value,
-        is_skipped: bool,
+        fork: Fork,
+        expectation: Expectation,
     ) -> Result<String, eyre::Error> {
-        let test_content =
-            Self::format_test_content(case_name, parent_dir, secret_key, content, is_skipped);
+        let test_content = Self::format_test_content(
+            case_name,
+            parent_dir,
+            secret_key,
+            content,
+            fork,
+            &expectation,
+        );
         let test_content_err = test_content.as_ref().map_err(|err| err.to_string());
 
-        let test_header = Self::format_test_header(is_skipped, test_content_err.err());
+        let skip_reason = match expectation {
+            Expectation::Skip(reason) => Some(reason),
+            _ => None,
+        };
+        let test_header = Self::format_test_header(skip_reason, test_content_err.err());
         let test_content = test_content.unwrap_or_default();
         let test_name = Self::format_into_identifier(case_name);
 
@@ -169,39 +307,105 @@ impl<'a> EfTests<'a> {
         ))
     }
 
-    /// Formats the given test content into a rust test.
+    /// Formats the given test content into a rust test. Expected failures are run
+    /// through `expect_failure`.
     fn format_test_content(
         case_name: &str,
         parent_dir: &str,
         secret_key: &String,
         content: &Value,
-        is_skipped: bool,
+        fork: Fork,
+        expectation: &Expectation,
     ) -> Result<String, eyre::Error> {
-        if is_skipped {
-            return Ok(String::default());
-        }
-        let block = ContentReader::block(content)?;
+        let run_case = match expectation {
+            Expectation::Skip(_) => return Ok(String::default()),
+            Expectation::Fail(reason) => {
+                format!("expect_failure(\"{case_name}\", {reason:?}, || case.run());")
+            }
+            Expectation::Pass => {
+                String::from("case.run().expect(\"Error while running the test\");")
+            }
+        };
+        let blocks = Value::Array(ContentReader::blocks(content)?);
         let pre = ContentReader::pre_state(content)?;
         let post = ContentReader::post_state(content)?;
+        let expected_exceptions = ContentReader::expected_exceptions(content)?;
         Ok(format!(
             r##"
             setup();
-            let block: Block = serde_json::from_str(r#"{block}"#).expect("Error while reading the block");
+            let blocks: Vec<Block> = serde_json::from_str(r#"{blocks}"#).expect("Error while reading the blocks");
             let pre: State = serde_json::from_str(r#"{pre}"#).expect("Error while reading the pre state");
             let post: Option<BTreeMap<Address, Account>> = serde_json::from_str(r#"{post}"#).expect("Error while reading the post state");
-            let case = BlockchainTestCase::new("{case_name}".to_string(), "{parent_dir}".to_string(), block, pre, post, B256::from_str({secret_key}).expect("Error while reading  secret key"));
-            case.run().expect("Error while running the test");
+            let expected_exceptions: Vec<Option<String>> = serde_json::from_str(r#"{expected_exceptions}"#).expect("Error while reading the expected exceptions");
+            let case = BlockchainTestCase::new("{case_name}".to_string(), "{parent_dir}".to_string(), blocks, pre, post, B256::from_str({secret_key}).expect("Error while reading  secret key"))
+                .with_expected_exceptions(expected_exceptions)
+                .with_fork(Fork::{fork});
+            {run_case}
+        "##
+        ))
+    }
+
+    /// Formats the given state test case into a rust test. Expected failures are
+    /// run through `expect_failure`.
+    fn format_to_state_test(
+        case_name: &str,
+        parent_dir: &str,
+        content: &Value,
+        skipped_variants: &[String],
+        fork: Fork,
+        expectation: Expectation,
+    ) -> Result<String, eyre::Error> {
+        let (skip_reason, run_case) = match expectation {
+            Expectation::Skip(reason) => (Some(reason), None),
+            Expectation::Fail(reason) => (
+                None,
+                Some(format!(
+                    "expect_failure(\"{case_name}\", {reason:?}, || case.run());"
+                )),
+            ),
+            Expectation::Pass => (
+                None,
+                Some(String::from(
+                    "case.run().expect(\"Error while running the test\");",
+                )),
+            ),
+        };
+        let test_header = Self::format_test_header(skip_reason, None);
+        let test_name = Self::format_into_identifier(case_name);
+        let test_content = if let Some(run_case) = run_case {
+            let skipped_variants = serde_json::to_string(skipped_variants)?;
+            format!(
+                r##"
+            setup();
+            let test: StateTest = serde_json::from_str(r#"{content}"#).expect("Error while reading the state test");
+            let skipped_variants: Vec<String> = serde_json::from_str(r#"{skipped_variants}"#).expect("Error while reading the skipped variants");
+            let case = StateTestCase::new("{case_name}".to_string(), "{parent_dir}".to_string(), Fork::{fork}, test, skipped_variants);
+            {run_case}
         "##
+            )
+        } else {
+            String::default()
+        };
+
+        Ok(format!(
+            r#"
+            #[test]
+            {test_header}
+            fn test_{test_name}() {{
+                monitor_test!("{test_name}", &[120, 300, 600, 900, 1200, 2000, 3000, 4000, 5000], || {{
+                {test_content}
+                }});
+            }}"#,
         ))
     }
 
-    fn format_test_header(is_skipped: bool, content_err: Option<String>) -> String {
-        if is_skipped {
-            return String::from("#[ignore = \"skipped in config file\"]");
-        } else if content_err.is_some() {
-            return format!("#[ignore = \"{}\"]", content_err.unwrap());
+    /// Formats the ignore attribute of a test, escaping the reason into a
+    /// valid string literal.
+    fn format_test_header(skip_reason: Option<String>, content_err: Option<String>) -> String {
+        match skip_reason.or(content_err) {
+            Some(reason) => format!("#[ignore = {reason:?}]"),
+            None => String::default(),
         }
-        String::default()
     }
 
     /// Formats the given string into a valid rust identifier.
@@ -40,6 +40,11 @@ impl<'a> DirReader<'a> {
         &self.files
     }
 
+    /// Returns the sub directories, indexed by name
+    pub const fn sub_dirs(&self) -> &BTreeMap<String, DirReader<'a>> {
+        &self.sub_dirs
+    }
+
     /// Walks the given directory
     pub fn walk_dir(directory_path: PathWrapper) -> Vec<DirEntry> {
         let path_ref: &Path = &directory_path;
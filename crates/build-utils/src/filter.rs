@@ -1,11 +1,286 @@
+use globset::{GlobBuilder, GlobMatcher};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, fs, path::Path};
+use std::{collections::BTreeMap, fmt, fs, path::Path};
 
-use crate::{converter::EfTests, path::PathWrapper};
+use crate::{
+    converter::EfTests,
+    fork::Fork,
+    path::PathWrapper,
+    report::{CaseStatus, ResultsReport},
+    version::KakarotVersion,
+};
 
 type Folder = String;
-type FilterMap = BTreeMap<Folder, Vec<String>>;
+type FilterMap<T = SkipEntry> = BTreeMap<Folder, Vec<T>>;
+
+/// Reason used for the skipped tests without any reason in the filter.
+pub const DEFAULT_SKIP_REASON: &str = "skipped in config file";
+
+/// Category of the reason why a test is skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipCategory {
+    /// The test runs out of Cairo steps or times out.
+    OutOfResources,
+    /// The test fails because of a known bug in Kakarot.
+    KakarotBug,
+    /// The test covers an EIP which is not supported by Kakarot.
+    UnsupportedEip,
+    Other,
+}
+
+impl fmt::Display for SkipCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let category = match self {
+            Self::OutOfResources => "out of resources",
+            Self::KakarotBug => "kakarot bug",
+            Self::UnsupportedEip => "unsupported eip",
+            Self::Other => "other",
+        };
+        write!(f, "{category}")
+    }
+}
+
+/// Entry of the filter. Entries can either be written as a bare name, or as a
+/// map with the name and the reason why the test is skipped:
+///
+/// ```yaml
+/// testname:
+///   vmArithmeticTest:
+///     - addmod_d8g0v0_Cancun
+///     - name: addmod_d9g0v0_Cancun
+///       category: kakarot_bug
+///       reason: wrong result for modulus 0
+///       issue: https://github.com/kkrt-labs/kakarot/issues/1
+///     - name: addmod_d10g0v0_Cancun
+///       xfail: true
+///       versions: [v0]
+///       forks: [Prague]
+/// ```
+///
+/// Entries marked as `xfail` are expected failures: the test still runs, a
+/// failure is expected and a pass is reported. Entries with `versions` or
+/// `forks` only apply to the given Kakarot versions or forks.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(from = "RawSkipEntry", into = "RawSkipEntry")]
+pub struct SkipEntry {
+    /// Name of the file, regex pattern or test identifier, depending on the
+    /// section of the filter.
+    pub name: String,
+    pub reason: Option<String>,
+    pub category: Option<SkipCategory>,
+    /// Reference to the issue tracking the skipped test.
+    pub issue: Option<String>,
+    /// Run the test and expect it to fail instead of skipping it.
+    pub xfail: bool,
+    /// Kakarot versions the entry applies to, all versions if empty.
+    pub versions: Vec<KakarotVersion>,
+    /// Forks the entry applies to, all forks if empty.
+    pub forks: Vec<Fork>,
+}
+
+impl SkipEntry {
+    pub const fn new(name: String) -> Self {
+        Self {
+            name,
+            reason: None,
+            category: None,
+            issue: None,
+            xfail: false,
+            versions: Vec::new(),
+            forks: Vec::new(),
+        }
+    }
+
+    /// Returns true if the entry applies to the given version and fork.
+    pub fn applies_to(&self, version: Option<KakarotVersion>, fork: Fork) -> bool {
+        let version_applies = self.versions.is_empty()
+            || version.is_some_and(|version| {
+                self.versions
+                    .iter()
+                    .any(|scope| version.is_in_scope(*scope))
+            });
+        let fork_applies = self.forks.is_empty() || self.forks.contains(&fork);
+        version_applies && fork_applies
+    }
+
+    /// Returns the expectation for the tests matching the entry.
+    pub fn expectation(&self) -> Expectation {
+        if self.xfail {
+            Expectation::Fail(self.skip_reason())
+        } else {
+            Expectation::Skip(self.skip_reason())
+        }
+    }
+
+    /// Returns the reason why the test is skipped, e.g.
+    /// `kakarot bug: wrong result for modulus 0 (https://...)`.
+    pub fn skip_reason(&self) -> String {
+        let mut skip_reason = match (&self.category, &self.reason) {
+            (Some(category), Some(reason)) => format!("{category}: {reason}"),
+            (Some(category), None) => category.to_string(),
+            (None, Some(reason)) => reason.clone(),
+            (None, None) => DEFAULT_SKIP_REASON.to_string(),
+        };
+        if let Some(issue) = &self.issue {
+            skip_reason += &format!(" ({issue})");
+        }
+        skip_reason
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum RawSkipEntry {
+    Name(String),
+    Detailed {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        category: Option<SkipCategory>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        issue: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        xfail: bool,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        versions: Vec<KakarotVersion>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        forks: Vec<Fork>,
+    },
+}
+
+impl From<RawSkipEntry> for SkipEntry {
+    fn from(raw: RawSkipEntry) -> Self {
+        match raw {
+            RawSkipEntry::Name(name) => Self::new(name),
+            RawSkipEntry::Detailed {
+                name,
+                category,
+                reason,
+                issue,
+                xfail,
+                versions,
+                forks,
+            } => Self {
+                name,
+                reason,
+                category,
+                issue,
+                xfail,
+                versions,
+                forks,
+            },
+        }
+    }
+}
+
+impl From<SkipEntry> for RawSkipEntry {
+    fn from(entry: SkipEntry) -> Self {
+        match entry {
+            SkipEntry {
+                name,
+                reason: None,
+                category: None,
+                issue: None,
+                xfail: false,
+                versions,
+                forks,
+            } if versions.is_empty() && forks.is_empty() => Self::Name(name),
+            SkipEntry {
+                name,
+                reason,
+                category,
+                issue,
+                xfail,
+                versions,
+                forks,
+            } => Self::Detailed {
+                name,
+                category,
+                reason,
+                issue,
+                xfail,
+                versions,
+                forks,
+            },
+        }
+    }
+}
+
+/// Entry of the `regex` section, with its pattern compiled at load.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "SkipEntry", into = "SkipEntry")]
+pub struct RegexEntry {
+    pub entry: SkipEntry,
+    regex: Regex,
+}
+
+impl TryFrom<SkipEntry> for RegexEntry {
+    type Error = regex::Error;
+
+    fn try_from(entry: SkipEntry) -> Result<Self, Self::Error> {
+        let regex = Regex::new(&entry.name)?;
+        Ok(Self { entry, regex })
+    }
+}
+
+impl From<RegexEntry> for SkipEntry {
+    fn from(regex_entry: RegexEntry) -> Self {
+        regex_entry.entry
+    }
+}
+
+impl PartialEq for RegexEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.entry == other.entry
+    }
+}
+
+/// Entry of the `glob` section, with its pattern compiled at load. The pattern
+/// matches the path of the test file relative to the `GeneralStateTests` folder,
+/// e.g. `stBadOpcode/opc4*.json` or `**/*Create2*.json`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "SkipEntry", into = "SkipEntry")]
+pub struct GlobEntry {
+    pub entry: SkipEntry,
+    matcher: GlobMatcher,
+}
+
+impl TryFrom<SkipEntry> for GlobEntry {
+    type Error = globset::Error;
+
+    fn try_from(entry: SkipEntry) -> Result<Self, Self::Error> {
+        let matcher = GlobBuilder::new(&entry.name)
+            .literal_separator(true)
+            .build()?
+            .compile_matcher();
+        Ok(Self { entry, matcher })
+    }
+}
+
+impl From<GlobEntry> for SkipEntry {
+    fn from(glob_entry: GlobEntry) -> Self {
+        glob_entry.entry
+    }
+}
+
+impl PartialEq for GlobEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.entry == other.entry
+    }
+}
+
+/// Expectation for a test, according to the filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expectation {
+    Pass,
+    /// The test is skipped, for the given reason.
+    Skip(String),
+    /// The test runs and is expected to fail, for the given reason.
+    Fail(String),
+}
 
 /// Filter to be applied on the tests files
 #[derive(Deserialize, Default, Serialize)]
@@ -15,10 +290,27 @@ pub struct Filter {
     /// Mapping containing the directories and the files that should be skipped
     filename: FilterMap,
     /// Mapping containing the directories and the regex patterns that should be skipped
-    regex: FilterMap,
+    regex: FilterMap<RegexEntry>,
+    /// Glob patterns over the path relative to `GeneralStateTests` of the files
+    /// that should be skipped
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    glob: Vec<GlobEntry>,
     /// Mapping containing the directories and the specific tests that should be skipped
     #[serde(rename = "testname")]
     test_name: FilterMap,
+    /// Version of Kakarot the tests run against, used for the scoped entries
+    #[serde(skip)]
+    version: Option<KakarotVersion>,
+}
+
+/// Changes applied to a filter from a results report. Tests are identified by
+/// their folder and their test identifier.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FilterUpdate {
+    /// Failing tests added to the skipped tests.
+    pub added: Vec<(Folder, String)>,
+    /// Skipped tests which now pass.
+    pub now_passing: Vec<(Folder, String)>,
 }
 
 impl Filter {
@@ -27,8 +319,110 @@ impl Filter {
         Ok(serde_yaml::from_str(&filter)?)
     }
 
+    /// Sets the version of Kakarot the tests run against. Entries scoped to
+    /// versions don't apply if the version is not set.
+    pub const fn with_version(mut self, version: Option<KakarotVersion>) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Writes the filter to the file, keeping the comments at the top of the
+    /// existing file.
+    pub fn save_file(&self, path: &str) -> Result<(), eyre::Error> {
+        let header: String = fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .take_while(|line| line.is_empty() || line.starts_with('#'))
+            .map(|line| format!("{line}\n"))
+            .collect();
+        fs::write(path, header + &serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Updates the skipped tests names from the results report: failing tests
+    /// are added and, if `remove_passing` is set, skipped tests which now pass
+    /// are removed. Other entries are preserved and all the entries are sorted.
+    pub fn apply_report(&mut self, report: &ResultsReport, remove_passing: bool) -> FilterUpdate {
+        let mut update = FilterUpdate::default();
+
+        for case in report.cases.iter() {
+            let test_identifier = EfTests::format_into_identifier(&case.case_name);
+            let skipped = self.test_name.entry(case.category.clone()).or_default();
+            let is_skipped = skipped.iter().any(|entry| entry.name == test_identifier);
+
+            match case.status {
+                CaseStatus::Failed if !is_skipped => {
+                    skipped.push(SkipEntry {
+                        category: case.skip_category(),
+                        ..SkipEntry::new(test_identifier.clone())
+                    });
+                    update.added.push((case.category.clone(), test_identifier));
+                }
+                CaseStatus::Passed | CaseStatus::UnexpectedPass if is_skipped => {
+                    if remove_passing {
+                        skipped.retain(|entry| entry.name != test_identifier);
+                    }
+                    update
+                        .now_passing
+                        .push((case.category.clone(), test_identifier));
+                }
+                _ => {}
+            }
+        }
+
+        self.test_name.retain(|_, tests| !tests.is_empty());
+        for tests in self
+            .filename
+            .values_mut()
+            .chain(self.test_name.values_mut())
+        {
+            tests.sort();
+            tests.dedup_by(|lhs, rhs| lhs.name == rhs.name);
+        }
+        for regexes in self.regex.values_mut() {
+            regexes.sort_by(|lhs, rhs| lhs.entry.cmp(&rhs.entry));
+            regexes.dedup_by(|lhs, rhs| lhs.entry.name == rhs.entry.name);
+        }
+        self.glob.sort_by(|lhs, rhs| lhs.entry.cmp(&rhs.entry));
+        self.glob
+            .dedup_by(|lhs, rhs| lhs.entry.name == rhs.entry.name);
+        self.directories.sort();
+        self.directories.dedup();
+        update.added.sort();
+        update.now_passing.sort();
+
+        update
+    }
+
     /// Checks if the given path is inside the filter object
-    pub fn is_skipped(&self, path: &PathWrapper, case_name: Option<String>) -> bool {
+    pub fn is_skipped(&self, path: &PathWrapper, case_name: Option<String>, fork: Fork) -> bool {
+        self.skip_reason(path, case_name, fork).is_some()
+    }
+
+    /// Returns the reason why the given path is skipped, if it is inside the
+    /// filter object and is not an expected failure.
+    pub fn skip_reason(
+        &self,
+        path: &PathWrapper,
+        case_name: Option<String>,
+        fork: Fork,
+    ) -> Option<String> {
+        match self.expectation(path, case_name, fork) {
+            Expectation::Skip(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// Returns the expectation for the given path and fork, from the entries of
+    /// the filter object which apply to the version and the fork. The test is
+    /// skipped if any of the matching entries skips it or if its directory is
+    /// skipped, and is expected to fail if all the matching entries are `xfail`.
+    pub fn expectation(
+        &self,
+        path: &PathWrapper,
+        case_name: Option<String>,
+        fork: Fork,
+    ) -> Expectation {
         let pathb = (*path).clone();
         let path_str = pathb.to_string_lossy();
 
@@ -37,52 +431,67 @@ impl Filter {
             .map(|(_, path)| path)
             .unwrap_or(&path_str);
 
-        let relative_path = Path::new(relative_path)
+        let top_dir = Path::new(relative_path)
             .components()
             .next()
             .and_then(|c| c.as_os_str().to_str())
-            .map(ToString::to_string)
             .unwrap_or_default();
 
         let dir_name = path.parent().file_stem_to_string();
         let file_name = path.file_stem_to_string();
+        let applies = |entry: &SkipEntry| entry.applies_to(self.version, fork);
 
-        if self
-            .directories
-            .iter()
-            .any(|dir| relative_path.contains(dir))
-        {
-            return true;
-        }
-
-        let mut should_skip = self
+        let test_identifier =
+            case_name.map(|case_name| EfTests::format_into_identifier(&case_name));
+        let test_names = self
+            .test_name
+            .get(&dir_name)
+            .into_iter()
+            .flatten()
+            .filter(|entry| test_identifier.as_ref() == Some(&entry.name));
+        let file_names = self
             .filename
             .get(&dir_name)
-            .map(|filtered_files| filtered_files.iter().any(|filename| filename == &file_name))
-            .unwrap_or_default();
-
-        should_skip |= self
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.name == file_name);
+        let regexes = self
             .regex
             .get(&dir_name)
-            .map(|regexes| {
-                regexes.iter().any(|regex| {
-                    Regex::new(regex.as_str())
-                        .expect("Error with regex pattern")
-                        .is_match(&file_name)
-                })
-            })
-            .unwrap_or_default();
+            .into_iter()
+            .flatten()
+            .filter(|regex| regex.regex.is_match(&file_name))
+            .map(|regex| &regex.entry);
+        let globs = self
+            .glob
+            .iter()
+            .filter(|glob| glob.matcher.is_match(relative_path))
+            .map(|glob| &glob.entry);
 
-        if let Some(case_name) = case_name {
-            let test_identifier = &EfTests::format_into_identifier(&case_name);
-            should_skip |= self
-                .test_name
-                .get(&dir_name)
-                .map(|tests| tests.iter().any(|test| test == test_identifier))
-                .unwrap_or_default();
+        let mut expected_failure = None;
+        for entry in test_names
+            .chain(file_names)
+            .chain(regexes)
+            .chain(globs)
+            .filter(|entry| applies(entry))
+        {
+            match entry.expectation() {
+                Expectation::Fail(reason) => {
+                    expected_failure.get_or_insert(reason);
+                }
+                expectation => return expectation,
+            }
+        }
+        if self.directories.iter().any(|dir| dir == top_dir) {
+            return Expectation::Skip(DEFAULT_SKIP_REASON.to_string());
         }
+        expected_failure.map_or(Expectation::Pass, Expectation::Fail)
+    }
 
-        should_skip
+    /// Returns true if the entries which are not scoped to a folder differ
+    /// between the two filters, in which case all the tests are affected.
+    pub fn has_global_diff(&self, rhs: &Self) -> bool {
+        self.directories != rhs.directories || self.glob != rhs.glob
     }
 
     /// Returns the difference in keys (folders) between the two filters
@@ -100,9 +509,9 @@ impl Filter {
     }
 }
 
-fn diff_map(lhs: &FilterMap, rhs: &FilterMap) -> Vec<Folder> {
+fn diff_map<T: PartialEq>(lhs: &FilterMap<T>, rhs: &FilterMap<T>) -> Vec<Folder> {
     let mut top = Vec::with_capacity(std::cmp::max(lhs.len(), rhs.len()));
-    let diff = |top: &mut Vec<String>, lhs: &FilterMap, rhs: &FilterMap| {
+    let diff = |top: &mut Vec<String>, lhs: &FilterMap<T>, rhs: &FilterMap<T>| {
         for (key, _) in lhs.iter() {
             if !rhs.contains_key(key) && !top.contains(key) {
                 top.push(key.clone());
@@ -137,13 +546,267 @@ mod tests {
         let path = PathWrapper::from(Path::new(
             "../../ef-testing/ethereum-tests/BlockchainTests/GeneralStateTests/stBadOpcode/opc4DDiffPlaces.json",
         ).to_path_buf());
-        assert!(filter.is_skipped(&path, None));
+        assert!(filter.is_skipped(&path, None, Fork::Cancun));
+    }
+
+    #[test]
+    fn test_apply_report() {
+        // Given
+        let mut filter = Filter {
+            test_name: vec![(
+                "vmArithmeticTest".to_string(),
+                vec![
+                    SkipEntry::new("mulmod_d1g0v0_Cancun".to_string()),
+                    SkipEntry::new("add_d0g0v0_Cancun".to_string()),
+                ],
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let report: ResultsReport = serde_json::from_value(serde_json::json!({
+            "passed": 1,
+            "cases": [
+                {"category": "vmArithmeticTest", "case_name": "add_d0g0v0_Cancun", "status": "passed", "gas_used": 21000},
+                {"category": "vmArithmeticTest", "case_name": "addmod_d1g0v0_Cancun", "status": "failed"},
+                {"category": "stExample", "case_name": "add11-d0g0v0_Cancun", "status": "failed"},
+                {"category": "stExample", "case_name": "add12_d0g0v0_Cancun", "status": "ignored"},
+            ]
+        }))
+        .unwrap();
+
+        // When
+        let update = filter.apply_report(&report, false);
+
+        // Then
+        assert_eq!(
+            update.added,
+            vec![
+                (
+                    "stExample".to_string(),
+                    "add11_minus_d0g0v0_Cancun".to_string()
+                ),
+                (
+                    "vmArithmeticTest".to_string(),
+                    "addmod_d1g0v0_Cancun".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            update.now_passing,
+            vec![(
+                "vmArithmeticTest".to_string(),
+                "add_d0g0v0_Cancun".to_string()
+            )]
+        );
+        assert_eq!(
+            names(&filter.test_name["vmArithmeticTest"]),
+            vec![
+                "add_d0g0v0_Cancun",
+                "addmod_d1g0v0_Cancun",
+                "mulmod_d1g0v0_Cancun"
+            ]
+        );
+        assert_eq!(
+            names(&filter.test_name["stExample"]),
+            vec!["add11_minus_d0g0v0_Cancun"]
+        );
+
+        // When
+        filter.apply_report(&report, true);
+
+        // Then
+        assert_eq!(
+            names(&filter.test_name["vmArithmeticTest"]),
+            vec!["addmod_d1g0v0_Cancun", "mulmod_d1g0v0_Cancun"]
+        );
+    }
+
+    #[test]
+    fn test_apply_report_out_of_resources() {
+        // Given
+        let mut filter = Filter::default();
+        let report: ResultsReport = serde_json::from_value(serde_json::json!({
+            "cases": [
+                {
+                    "category": "vmPerformance",
+                    "case_name": "loopExp_d0g0v0_Cancun",
+                    "status": "failed",
+                    "messages": ["Could not reach the end of the program. RunResources has no remaining steps."]
+                },
+                {
+                    "category": "vmPerformance",
+                    "case_name": "loopMul_d0g0v0_Cancun",
+                    "status": "failed",
+                    "messages": ["storage mismatch"]
+                },
+            ]
+        }))
+        .unwrap();
+
+        // When
+        filter.apply_report(&report, false);
+
+        // Then
+        let categories: Vec<_> = filter.test_name["vmPerformance"]
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.category))
+            .collect();
+        assert_eq!(
+            categories,
+            vec![
+                ("loopExp_d0g0v0_Cancun", Some(SkipCategory::OutOfResources)),
+                ("loopMul_d0g0v0_Cancun", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skip_entries() {
+        // Given
+        let filter: Filter = serde_yaml::from_str(
+            r##"
+directories: []
+filename:
+  stExample:
+    - name: example
+      category: out_of_resources
+      issue: "#42"
+regex: {}
+testname:
+  vmArithmeticTest:
+    - addmod_d8g0v0_Cancun
+    - name: addmod_d9g0v0_Cancun
+      category: kakarot_bug
+      reason: wrong "result"
+    - name: addmod_d10g0v0_Cancun
+      xfail: true
+    - name: addmod_d12g0v0_Cancun
+      xfail: true
+    - addmod_d12g0v0_Cancun
+"##,
+        )
+        .unwrap();
+        let arithmetic_path = PathWrapper::from(
+            Path::new("GeneralStateTests/vmArithmeticTest/addmod.json").to_path_buf(),
+        );
+        let example_path =
+            PathWrapper::from(Path::new("GeneralStateTests/stExample/example.json").to_path_buf());
+
+        // When
+        let bare = filter.skip_reason(
+            &arithmetic_path,
+            Some("addmod_d8g0v0_Cancun".to_string()),
+            Fork::Cancun,
+        );
+        let detailed = filter.skip_reason(
+            &arithmetic_path,
+            Some("addmod_d9g0v0_Cancun".to_string()),
+            Fork::Cancun,
+        );
+        let not_skipped = filter.expectation(
+            &arithmetic_path,
+            Some("addmod_d11g0v0_Cancun".to_string()),
+            Fork::Cancun,
+        );
+        let file = filter.skip_reason(&example_path, None, Fork::Cancun);
+        let xfail = filter.expectation(
+            &arithmetic_path,
+            Some("addmod_d10g0v0_Cancun".to_string()),
+            Fork::Cancun,
+        );
+        let skip_over_xfail = filter.skip_reason(
+            &arithmetic_path,
+            Some("addmod_d12g0v0_Cancun".to_string()),
+            Fork::Cancun,
+        );
+
+        // Then
+        assert_eq!(bare.as_deref(), Some(DEFAULT_SKIP_REASON));
+        assert_eq!(detailed.as_deref(), Some("kakarot bug: wrong \"result\""));
+        assert_eq!(not_skipped, Expectation::Pass);
+        assert_eq!(xfail, Expectation::Fail(DEFAULT_SKIP_REASON.to_string()));
+        assert_eq!(skip_over_xfail.as_deref(), Some(DEFAULT_SKIP_REASON));
+        assert_eq!(file.as_deref(), Some("out of resources (#42)"));
+
+        // When
+        let serialized = serde_yaml::to_string(&filter).unwrap();
+        let deserialized: Filter = serde_yaml::from_str(&serialized).unwrap();
+
+        // Then
+        assert!(serialized.contains("- addmod_d8g0v0_Cancun\n"));
+        assert!(!serialized.contains("glob"));
+        assert_eq!(deserialized.diff(&filter), Vec::<Folder>::new());
+    }
+
+    #[test]
+    fn test_glob_and_scoped_entries() {
+        // Given
+        let content = r#"
+directories:
+  - Pyspecs
+filename: {}
+regex:
+  stExample:
+    - name: ^call.*
+      versions: [v1]
+glob:
+  - stExample/create*.json
+  - name: "**/*Prague*.json"
+    forks: [Prague]
+testname: {}
+"#;
+        let filter: Filter = serde_yaml::from_str(content).unwrap();
+        let path = |relative: &str| {
+            PathWrapper::from(
+                Path::new("ethereum-tests/BlockchainTests/GeneralStateTests")
+                    .join(relative)
+                    .to_path_buf(),
+            )
+        };
+
+        // When / Then
+        assert!(filter.is_skipped(&path("stExample/create2.json"), None, Fork::Cancun));
+        assert!(!filter.is_skipped(&path("stOther/stExample/create2.json"), None, Fork::Cancun));
+        assert!(filter.is_skipped(&path("stOther/deep/callPrague.json"), None, Fork::Prague));
+        assert!(!filter.is_skipped(&path("stOther/deep/callPrague.json"), None, Fork::Cancun));
+        assert!(filter.is_skipped(&path("Pyspecs/cancun/test.json"), None, Fork::Cancun));
+        assert!(!filter.is_skipped(&path("stPyspecsLike/test.json"), None, Fork::Cancun));
+
+        // Regex entries scoped to v1 apply to v1 and native only
+        let call = path("stExample/callcode.json");
+        assert!(!filter.is_skipped(&call, None, Fork::Cancun));
+        let v0 = serde_yaml::from_str::<Filter>(content)
+            .unwrap()
+            .with_version(Some(KakarotVersion::V0));
+        assert!(!v0.is_skipped(&call, None, Fork::Cancun));
+        let native = serde_yaml::from_str::<Filter>(content)
+            .unwrap()
+            .with_version(Some(KakarotVersion::Native));
+        assert!(native.is_skipped(&call, None, Fork::Cancun));
+    }
+
+    #[test]
+    fn test_invalid_pattern_fails_at_load() {
+        // Given
+        let content =
+            "directories: []\nfilename: {}\nregex:\n  stExample:\n    - (unclosed\ntestname: {}\n";
+
+        // When
+        let filter = serde_yaml::from_str::<Filter>(content);
+
+        // Then
+        assert!(filter.is_err());
+    }
+
+    fn names(entries: &[SkipEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
     }
 
     #[test]
     fn test_map_diff() {
         // Given
-        let lhs: FilterMap = vec![
+        let lhs: FilterMap<String> = vec![
             ("a".to_string(), vec!["a".to_string()]),
             ("b".to_string(), vec!["b".to_string(), "b".to_string()]),
             (
@@ -157,7 +820,7 @@ mod tests {
         ]
         .into_iter()
         .collect();
-        let rhs: FilterMap = vec![
+        let rhs: FilterMap<String> = vec![
             ("a".to_string(), vec!["a".to_string()]),
             ("b".to_string(), vec!["b".to_string(), "d".to_string()]),
             (
@@ -0,0 +1,141 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Environment variable used to select the forks to run the tests for, as a
+/// comma separated list (e.g. `EF_TESTS_FORKS=Shanghai,Cancun`).
+pub const FORKS_ENV_VAR: &str = "EF_TESTS_FORKS";
+
+/// Forks supported by the test runner, ordered chronologically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Fork {
+    Shanghai,
+    Cancun,
+    Prague,
+}
+
+impl Fork {
+    /// Fork used when none is configured.
+    pub const DEFAULT: Self = Self::Cancun;
+
+    /// Returns the forks configured in [`FORKS_ENV_VAR`], or the default fork
+    /// if the variable is not set.
+    pub fn from_env() -> Result<Vec<Self>, eyre::Error> {
+        match std::env::var(FORKS_ENV_VAR) {
+            Ok(forks) => Self::parse_list(&forks),
+            Err(_) => Ok(vec![Self::DEFAULT]),
+        }
+    }
+
+    /// Parses a comma separated list of forks, deduplicated and sorted.
+    pub fn parse_list(forks: &str) -> Result<Vec<Self>, eyre::Error> {
+        let mut forks = forks
+            .split(',')
+            .map(str::trim)
+            .filter(|fork| !fork.is_empty())
+            .map(Self::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        forks.sort();
+        forks.dedup();
+        if forks.is_empty() {
+            return Err(eyre!("no fork provided in {FORKS_ENV_VAR}"));
+        }
+        Ok(forks)
+    }
+
+    /// Returns the name of the module the tests for this fork are generated in.
+    pub fn module_name(&self) -> String {
+        self.to_string().to_lowercase()
+    }
+
+    /// Returns true if the blockchain test case targets this fork. The `network`
+    /// field of the case is used if present, otherwise the fork is read from the
+    /// case name (`..._Cancun` or `...fork_Cancun...` for pyspec tests).
+    pub fn is_case_of(&self, case_name: &str, content: &Value) -> bool {
+        let fork = self.to_string();
+        match content.get("network").and_then(Value::as_str) {
+            Some(network) => network == fork,
+            None => case_name.ends_with(&fork) || case_name.contains(&format!("fork_{fork}")),
+        }
+    }
+
+    /// Returns true if the beacon roots contract (EIP-4788) is part of the fork.
+    pub fn has_beacon_root(&self) -> bool {
+        *self >= Self::Cancun
+    }
+}
+
+impl Display for Fork {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Shanghai => write!(f, "Shanghai"),
+            Self::Cancun => write!(f, "Cancun"),
+            Self::Prague => write!(f, "Prague"),
+        }
+    }
+}
+
+impl FromStr for Fork {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Shanghai" => Ok(Self::Shanghai),
+            "Cancun" => Ok(Self::Cancun),
+            "Prague" => Ok(Self::Prague),
+            _ => Err(eyre!("unsupported fork: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_list() {
+        // Given
+        let forks = "Prague, Cancun,Cancun";
+
+        // When
+        let forks = Fork::parse_list(forks).unwrap();
+
+        // Then
+        assert_eq!(forks, vec![Fork::Cancun, Fork::Prague]);
+    }
+
+    #[test]
+    fn test_parse_list_unsupported() {
+        // Given
+        let forks = "Cancun,Berlin";
+
+        // When
+        let forks = Fork::parse_list(forks);
+
+        // Then
+        assert!(forks.is_err());
+    }
+
+    #[test]
+    fn test_is_case_of() {
+        // Given
+        let network = json!({ "network": "Shanghai" });
+        let no_network = json!({});
+
+        // When / Then
+        assert!(Fork::Shanghai.is_case_of("add_d0g0v0_Cancun", &network));
+        assert!(!Fork::Cancun.is_case_of("add_d0g0v0_Cancun", &network));
+        assert!(Fork::Cancun.is_case_of("add_d0g0v0_Cancun", &no_network));
+        assert!(Fork::Prague.is_case_of(
+            "test_acl.py::test_access_list[fork_Prague-blockchain_test]",
+            &no_network
+        ));
+        assert!(!Fork::Shanghai.is_case_of("add_d0g0v0_Cancun", &no_network));
+    }
+}
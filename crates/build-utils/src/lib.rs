@@ -1,7 +1,11 @@
-mod constants;
-mod content_reader;
+pub mod constants;
+pub mod content_reader;
 pub mod converter;
 pub mod dir_reader;
 pub mod filter;
-mod path;
+pub mod fork;
+pub mod path;
+pub mod report;
+pub mod shard;
 mod utils;
+pub mod version;
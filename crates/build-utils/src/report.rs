@@ -0,0 +1,65 @@
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::filter::SkipCategory;
+
+/// Patterns of the failure messages of the cases which ran out of Cairo steps.
+const OUT_OF_RESOURCES_PATTERNS: &[&str] = &["RunResources", "out of resources"];
+
+/// Status of a case in a results report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseStatus {
+    Passed,
+    Failed,
+    Ignored,
+    /// The test is expected to fail and failed.
+    #[serde(rename = "xfail")]
+    ExpectedFailure,
+    /// The test is expected to fail but passed.
+    #[serde(rename = "xpass")]
+    UnexpectedPass,
+}
+
+/// Result of a single case, as written in the JSON report of the `ef-runner`.
+/// Only the fields needed to update the skip file are read.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CaseResult {
+    /// Name of the directory containing the fixture file.
+    pub category: String,
+    /// Name of the case in the fixture file.
+    pub case_name: String,
+    pub status: CaseStatus,
+    /// Failure messages of a failed case.
+    #[serde(default)]
+    pub messages: Vec<String>,
+}
+
+impl CaseResult {
+    /// Returns the skip category of the case inferred from its failure messages,
+    /// if any.
+    pub fn skip_category(&self) -> Option<SkipCategory> {
+        self.messages
+            .iter()
+            .any(|message| {
+                OUT_OF_RESOURCES_PATTERNS
+                    .iter()
+                    .any(|pattern| message.contains(pattern))
+            })
+            .then_some(SkipCategory::OutOfResources)
+    }
+}
+
+/// Results report of a test run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ResultsReport {
+    pub cases: Vec<CaseResult>,
+}
+
+impl ResultsReport {
+    pub fn load_file(path: &Path) -> Result<Self, eyre::Error> {
+        let report = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&report)?)
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use eyre::eyre;
+
+use crate::fork::Fork;
+
+/// Environment variable used to select the shard of the tests to generate or run,
+/// as `i/n` with `1 <= i <= n` (e.g. `EF_TESTS_SHARD=2/4`).
+pub const SHARD_ENV_VAR: &str = "EF_TESTS_SHARD";
+
+/// Shard `index` of `count` of the tests. Tests are assigned to a shard from a
+/// stable hash of their key, so that the assignment is the same across processes,
+/// machines and toolchains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    /// Index of the shard, starting at 1.
+    index: usize,
+    count: usize,
+}
+
+impl Shard {
+    pub fn new(index: usize, count: usize) -> Result<Self, eyre::Error> {
+        if index == 0 || index > count {
+            return Err(eyre!("invalid shard {index}/{count}, expected 1 <= i <= n"));
+        }
+        Ok(Self { index, count })
+    }
+
+    /// Returns the shard configured in [`SHARD_ENV_VAR`], if any.
+    pub fn from_env() -> Result<Option<Self>, eyre::Error> {
+        match std::env::var(SHARD_ENV_VAR) {
+            Ok(shard) if !shard.trim().is_empty() => Ok(Some(shard.parse()?)),
+            _ => Ok(None),
+        }
+    }
+
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the key of a test used to assign it to a shard. The key only depends
+    /// on the test case, so that the generated tests and the runner agree.
+    pub fn key(fork: Fork, category: &str, case_name: &str) -> String {
+        format!("{fork}/{category}/{case_name}")
+    }
+
+    /// Returns true if the test with the given key belongs to the shard.
+    pub fn contains(&self, key: &str) -> bool {
+        (stable_hash(key) % self.count as u64) as usize == self.index - 1
+    }
+
+    /// Returns the names of the tests which belong to the shard when balancing
+    /// the shards by expected duration. Tests are assigned from the longest to the
+    /// shortest to the shard with the lowest total duration. Tests without a known
+    /// duration are given the mean of the known durations.
+    pub fn balanced_selection<'a>(&self, tests: &[(&'a str, Option<f64>)]) -> BTreeSet<&'a str> {
+        let known: Vec<f64> = tests.iter().filter_map(|(_, duration)| *duration).collect();
+        let default_duration = if known.is_empty() {
+            1.
+        } else {
+            known.iter().sum::<f64>() / known.len() as f64
+        };
+
+        let mut tests: Vec<(&str, f64)> = tests
+            .iter()
+            .map(|(name, duration)| (*name, duration.unwrap_or(default_duration)))
+            .collect();
+        tests.sort_by(|lhs, rhs| rhs.1.total_cmp(&lhs.1).then_with(|| lhs.0.cmp(rhs.0)));
+
+        let mut loads = vec![0.; self.count];
+        let mut selection = BTreeSet::new();
+        for (name, duration) in tests {
+            let (shard, _) = loads
+                .iter()
+                .enumerate()
+                .min_by(|lhs, rhs| lhs.1.total_cmp(rhs.1))
+                .expect("at least one shard");
+            loads[shard] += duration;
+            if shard == self.index - 1 {
+                selection.insert(name);
+            }
+        }
+        selection
+    }
+}
+
+impl FromStr for Shard {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| eyre!("invalid shard {s}, expected i/n"))?;
+        Self::new(index.trim().parse()?, count.trim().parse()?)
+    }
+}
+
+impl Display for Shard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// 64-bit FNV-1a hash, which unlike the std hashers is stable across releases.
+fn stable_hash(key: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    key.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_shard() {
+        assert_eq!("2/4".parse::<Shard>().unwrap(), Shard::new(2, 4).unwrap());
+        assert!("0/4".parse::<Shard>().is_err());
+        assert!("5/4".parse::<Shard>().is_err());
+        assert!("4".parse::<Shard>().is_err());
+    }
+
+    #[test]
+    fn test_shards_partition_the_tests() {
+        // Given
+        let keys: Vec<String> = (0..100)
+            .map(|i| Shard::key(Fork::Cancun, "stExample", &format!("test{i}_d0g0v0_Cancun")))
+            .collect();
+        let shards: Vec<Shard> = (1..=3).map(|i| Shard::new(i, 3).unwrap()).collect();
+
+        // When
+        let counts: Vec<usize> = keys
+            .iter()
+            .map(|key| shards.iter().filter(|shard| shard.contains(key)).count())
+            .collect();
+
+        // Then
+        assert!(counts.iter().all(|count| *count == 1));
+        assert_eq!(stable_hash("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_balanced_selection() {
+        // Given
+        let tests = [
+            ("a", Some(10.)),
+            ("b", Some(6.)),
+            ("c", Some(4.)),
+            ("d", None),
+        ];
+
+        // When
+        let first = Shard::new(1, 2).unwrap().balanced_selection(&tests);
+        let second = Shard::new(2, 2).unwrap().balanced_selection(&tests);
+
+        // Then
+        // `d` is expected to last 20/3s: a -> 1, d -> 2, b -> 2, c -> 1
+        assert_eq!(first, BTreeSet::from(["a", "c"]));
+        assert_eq!(second, BTreeSet::from(["b", "d"]));
+    }
+}
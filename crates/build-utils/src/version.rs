@@ -0,0 +1,65 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+
+/// Version of Kakarot the tests are run against, selected through the features
+/// of the `ef-testing` crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KakarotVersion {
+    V0,
+    V1,
+    /// Kakarot v1 run with Cairo native.
+    Native,
+}
+
+impl KakarotVersion {
+    /// Returns the version selected by the features of the crate being built,
+    /// from the `CARGO_FEATURE_*` environment variables set for build scripts.
+    pub fn from_cargo_features() -> Option<Self> {
+        let is_enabled = |feature: &str| std::env::var(format!("CARGO_FEATURE_{feature}")).is_ok();
+        if is_enabled("NATIVE") {
+            Some(Self::Native)
+        } else if is_enabled("V1") {
+            Some(Self::V1)
+        } else if is_enabled("V0") {
+            Some(Self::V0)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if an entry scoped to the given version applies to this
+    /// version. Native runs Kakarot v1, so entries scoped to v1 also apply to it.
+    pub fn is_in_scope(&self, scope: Self) -> bool {
+        *self == scope || (*self == Self::Native && scope == Self::V1)
+    }
+}
+
+impl Display for KakarotVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let version = match self {
+            Self::V0 => "v0",
+            Self::V1 => "v1",
+            Self::Native => "native",
+        };
+        write!(f, "{version}")
+    }
+}
+
+impl FromStr for KakarotVersion {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v0" => Ok(Self::V0),
+            "v1" => Ok(Self::V1),
+            "native" => Ok(Self::Native),
+            _ => Err(eyre!("unsupported Kakarot version: {s}")),
+        }
+    }
+}
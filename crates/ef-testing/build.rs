@@ -4,15 +4,25 @@ use std::{
     sync::Arc,
 };
 
-use build_utils::{converter::EfTests, dir_reader::DirReader, filter::Filter};
+use build_utils::{
+    converter::{EfTests, TestFormat},
+    dir_reader::DirReader,
+    filter::Filter,
+    fork::{Fork, FORKS_ENV_VAR},
+    shard::{Shard, SHARD_ENV_VAR},
+    version::KakarotVersion,
+};
 use lazy_static::lazy_static;
 
 const SKIPPED_TESTS: &str = "../../blockchain-tests-skip.yml";
 const CACHED_SKIPPED_TESTS: &str = "../../tmp/blockchain-tests-skip.yml";
+const CACHED_CONFIGURATION: &str = "../../tmp/configuration";
 
 lazy_static! {
     static ref SUITE_PATH: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("./ethereum-tests/BlockchainTests/GeneralStateTests");
+    static ref STATE_SUITE_PATH: PathBuf =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("./ethereum-tests/GeneralStateTests");
     static ref INTEGRATION_TESTS_PATH: PathBuf =
         PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("./tests/");
 }
@@ -20,11 +30,33 @@ lazy_static! {
 fn main() {
     // Tell Cargo that if the given file changes -> to rerun this build script.
     cargo_emit::rerun_if_changed!(SKIPPED_TESTS);
-    let filter = Filter::load_file(SKIPPED_TESTS).expect("Unable to load test filter file");
+    cargo_emit::rerun_if_env_changed!(FORKS_ENV_VAR);
+    cargo_emit::rerun_if_env_changed!(SHARD_ENV_VAR);
+    let forks = Fork::from_env().expect("Unable to read the forks to test");
+    let version = KakarotVersion::from_cargo_features();
+    let shard = Shard::from_env().expect("Unable to read the shard to test");
+    let configuration = format!(
+        "forks={};version={};shard={}",
+        forks
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+        version
+            .map(|version| version.to_string())
+            .unwrap_or_default(),
+        shard.map(|shard| shard.to_string()).unwrap_or_default()
+    );
+    let filter = Filter::load_file(SKIPPED_TESTS)
+        .expect("Unable to load test filter file")
+        .with_version(version);
     let maybe_cached_filter = Filter::load_file(CACHED_SKIPPED_TESTS).ok();
 
-    // Get the diff between the cached filter and the current one
-    let mut target = maybe_cached_filter.map(|cached_filter| filter.diff(&cached_filter));
+    // Get the diff between the cached filter and the current one, regenerating
+    // all tests if entries which are not scoped to a folder changed
+    let mut target = maybe_cached_filter.and_then(|cached_filter| {
+        (!filter.has_global_diff(&cached_filter)).then(|| filter.diff(&cached_filter))
+    });
 
     // Check that we already have tests in the folder
     let current_tests = DirReader::walk_dir((INTEGRATION_TESTS_PATH.clone()).into());
@@ -33,6 +65,12 @@ fn main() {
         target = None;
     }
 
+    // Regenerate all tests if the selected forks, version or shard changed
+    let cached_configuration = fs::read_to_string(CACHED_CONFIGURATION).unwrap_or_default();
+    if cached_configuration != configuration {
+        target = None;
+    }
+
     // Walk the directory and store all files based on diff (or all files if no diff)
     let mut root_node = DirReader::new(&target);
     root_node
@@ -44,11 +82,35 @@ fn main() {
 
     // Convert all test files
     let filter = Arc::new(filter);
-    let converter = EfTests::new(root_node, filter.clone());
+    let converter = EfTests::new(root_node, filter.clone())
+        .with_forks(&forks)
+        .with_shard(shard);
     let tests = converter
         .convert()
         .expect("Error while converting the tests");
 
+    // Convert the GeneralStateTests if the `state-tests` feature is enabled.
+    // Generated modules are prefixed with `state_` to avoid conflicting with
+    // the BlockchainTests modules.
+    let mut tests: Vec<(String, String)> = tests;
+    if std::env::var("CARGO_FEATURE_STATE_TESTS").is_ok() {
+        let mut state_node = DirReader::new(&None);
+        state_node
+            .walk_dir_and_store_files(STATE_SUITE_PATH.clone().into())
+            .expect("Error while walking state tests directory");
+        let state_tests = EfTests::new(state_node, filter.clone())
+            .with_format(TestFormat::State)
+            .with_forks(&forks)
+            .with_shard(shard)
+            .convert()
+            .expect("Error while converting the state tests");
+        tests.extend(
+            state_tests
+                .into_iter()
+                .map(|(folder_name, content)| (format!("state_{}", folder_name), content)),
+        );
+    }
+
     fs::create_dir_all(INTEGRATION_TESTS_PATH.clone())
         .expect("Unable to create dir integration tests folder");
     // Write all tests to the integration tests folder
@@ -81,4 +143,7 @@ fn main() {
         .expect("Unable to get parent dir");
     fs::create_dir_all(cached_skipped_tests_path).expect("Unable to create tmp folder");
     fs::write(CACHED_SKIPPED_TESTS, filter_content).expect("Unable to write file");
+
+    // Cache the forks, version and shard
+    fs::write(CACHED_CONFIGURATION, configuration).expect("Unable to write file");
 }
@@ -0,0 +1,245 @@
+//! Runs the EF blockchain tests by discovering the fixtures at runtime, instead
+//! of compiling a rust test per case. Arguments and output mirror libtest, e.g.
+//! `ef-runner add --skip addmod --test-threads 8`.
+use std::path::PathBuf;
+use std::time::Instant;
+
+use build_utils::{dir_reader::DirReader, filter::Filter, fork::Fork, shard::Shard};
+use clap::Parser;
+use ef_testing::coverage::report::CoverageReport;
+use ef_testing::differential::Reference;
+use ef_testing::models::fees::FeeTolerance;
+use ef_testing::runner::{
+    self,
+    budget::{self, ResourceBudget},
+    libtest,
+    report::Report,
+    IgnoredMode, NameFilter, RunOptions,
+};
+use ef_testing::test_utils::{is_xfail_strict, setup};
+
+const SUITE_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/ethereum-tests/BlockchainTests/GeneralStateTests"
+);
+const SKIPPED_TESTS: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../blockchain-tests-skip.yml"
+);
+
+#[derive(Debug, Parser)]
+#[command(about = "Runs the EF blockchain tests against Kakarot")]
+struct Args {
+    /// Run only the tests whose name contains one of the filters.
+    filters: Vec<String>,
+    /// Match the filters exactly.
+    #[arg(long)]
+    exact: bool,
+    /// Skip the tests whose name contains the pattern.
+    #[arg(long, value_name = "PATTERN")]
+    skip: Vec<String>,
+    /// Number of threads used to run the tests. Defaults to the number of CPUs.
+    #[arg(long, default_value_t = 0, hide_default_value = true)]
+    test_threads: usize,
+    /// Run only the ignored tests.
+    #[arg(long, conflicts_with = "include_ignored")]
+    ignored: bool,
+    /// Run the ignored tests along with the other tests.
+    #[arg(long)]
+    include_ignored: bool,
+    /// List the tests instead of running them.
+    #[arg(long)]
+    list: bool,
+    /// Accepted for libtest compatibility, output is never captured.
+    #[allow(dead_code)]
+    #[arg(long)]
+    nocapture: bool,
+    /// Accepted for libtest compatibility, the output is always formatted as pretty.
+    #[allow(dead_code)]
+    #[arg(long, value_name = "FORMAT")]
+    format: Option<String>,
+    /// Comma separated list of forks to run. Defaults to the `EF_TESTS_FORKS`
+    /// environment variable, or Cancun.
+    #[arg(long)]
+    forks: Option<String>,
+    /// Path to the blockchain tests fixtures.
+    #[arg(long, default_value = SUITE_PATH)]
+    suite_path: PathBuf,
+    /// Path to the skip file.
+    #[arg(long, default_value = SKIPPED_TESTS)]
+    skip_file: String,
+    /// Fail the expected failures which pass. Defaults to the `EF_TESTS_XFAIL_STRICT`
+    /// environment variable.
+    #[arg(long)]
+    xfail_strict: bool,
+    /// Run only the given shard of the tests, as `i/n`. Defaults to the
+    /// `EF_TESTS_SHARD` environment variable.
+    #[arg(long, value_name = "i/n")]
+    shard: Option<Shard>,
+    /// JSON report of a previous run, used to balance the shards by the duration
+    /// of the tests.
+    #[arg(long, value_name = "PATH")]
+    shard_timings: Option<PathBuf>,
+    /// Compare the execution of each test to the given reference: `revm` or another
+    /// version of Kakarot (`kakarot-v0`, `kakarot-v1` or `kakarot-native`), reporting
+    /// the divergences of the accounts, storage, logs and gas as failures.
+    #[arg(long, value_name = "REFERENCE")]
+    differential: Option<Reference>,
+    /// Write the EIP-3155 trace of each transaction to the given directory, as
+    /// `<test name>-<transaction index>.jsonl`. Kakarot doesn't emit opcode steps
+    /// yet, so the traces only hold the summary of each transaction.
+    #[arg(long, value_name = "DIR")]
+    trace: Option<PathBuf>,
+    /// Write the Cairo steps executed by Kakarot for each test to the given directory,
+    /// as folded stacks in `<test name>.folded`, which can be rendered with
+    /// `inferno-flamegraph` or `flamegraph.pl`. Only supported by Kakarot v0.
+    #[arg(long, value_name = "DIR")]
+    profile: Option<PathBuf>,
+    /// Print a warning for each test after which the resident memory of the runner
+    /// exceeds the given size, in MiB, or whose run raised the peak resident memory
+    /// beyond it. Memory is measured on Linux and macOS only, and the tests are run
+    /// on a single thread to attribute it to each test.
+    #[arg(long, value_name = "MIB")]
+    memory_threshold: Option<u64>,
+    /// Tolerance, in wei, allowed on each fee component of every transaction when
+    /// comparing balances, as `base=<wei>,priority=<wei>,blob=<wei>`. Missing
+    /// components have no tolerance.
+    #[arg(
+        long,
+        value_name = "TOLERANCE",
+        default_value = "",
+        hide_default_value = true
+    )]
+    fee_tolerance: FeeTolerance,
+    /// Write a JSON report of the run to the given path.
+    #[arg(long, value_name = "PATH")]
+    report_json: Option<PathBuf>,
+    /// Write a JUnit XML report of the run to the given path.
+    #[arg(long, value_name = "PATH")]
+    report_junit: Option<PathBuf>,
+    /// Write the gas and Cairo resources used by each test as CSV to the given path.
+    #[arg(long, value_name = "PATH")]
+    report_resources_csv: Option<PathBuf>,
+    /// Write the coverage of each opcode and precompile as CSV to the given path:
+    /// the number of tests covering it, and how many of them passed, failed or
+    /// were ignored. Coverage is collected from the code of the tests, or from
+    /// their traces when these contain opcode steps.
+    #[arg(long, value_name = "PATH")]
+    report_coverage_csv: Option<PathBuf>,
+    /// JSON report of a baseline run. The run fails if the Cairo steps or a builtin
+    /// used by a test grew beyond `--resource-budget` compared to the baseline.
+    #[arg(long, value_name = "PATH")]
+    resource_baseline: Option<PathBuf>,
+    /// Maximum growth, in percent, of the Cairo resources of each test compared
+    /// to the baseline.
+    #[arg(long, value_name = "PERCENT", default_value_t = 5.)]
+    resource_budget: f64,
+    /// Only warn about the resources which grew beyond the budget.
+    #[arg(long)]
+    resource_budget_warn: bool,
+    /// Number of regressions printed per category.
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    resource_regressions_top: usize,
+}
+
+fn main() -> Result<(), eyre::Error> {
+    let args = Args::parse();
+    setup();
+
+    let forks = match args.forks.as_deref() {
+        Some(forks) => Fork::parse_list(forks)?,
+        None => Fork::from_env()?,
+    };
+    let filter = Filter::load_file(&args.skip_file)?.with_version(runner::KAKAROT_VERSION);
+    let mut root = DirReader::new(&None);
+    root.walk_dir_and_store_files(args.suite_path.into())?;
+
+    let mut entries = runner::discover(&root, &filter, &forks)?;
+    let discovered = entries.len();
+    let shard = match args.shard {
+        Some(shard) => Some(shard),
+        None => Shard::from_env()?,
+    };
+    if let Some(shard) = shard {
+        let durations = match args.shard_timings.as_deref() {
+            Some(path) => Some(
+                Report::load_json(path)?
+                    .cases
+                    .into_iter()
+                    .map(|case| (case.name, case.duration_secs))
+                    .collect(),
+            ),
+            None => None,
+        };
+        entries = runner::shard(entries, shard, durations.as_ref());
+    }
+    let sharded_out = discovered - entries.len();
+
+    let name_filter = NameFilter {
+        filters: args.filters,
+        skip: args.skip,
+        exact: args.exact,
+    };
+    let ignored_mode = if args.ignored {
+        IgnoredMode::Only
+    } else if args.include_ignored {
+        IgnoredMode::Include
+    } else {
+        IgnoredMode::Skip
+    };
+    let (entries, filtered_out) = runner::select(entries, &name_filter, ignored_mode);
+    let filtered_out = filtered_out + sharded_out;
+
+    if args.list {
+        libtest::print_list(&entries);
+        return Ok(());
+    }
+
+    if args.memory_threshold.is_some() && args.test_threads != 1 {
+        eprintln!("note: --memory-threshold runs the tests on a single thread");
+    }
+
+    let start = Instant::now();
+    libtest::print_header(entries.len());
+    let options = RunOptions {
+        threads: args.test_threads,
+        xfail_strict: args.xfail_strict || is_xfail_strict(),
+        reference: args.differential,
+        trace_dir: args.trace,
+        coverage: args.report_coverage_csv.is_some(),
+        profile_dir: args.profile,
+        memory_threshold: args.memory_threshold.map(|mib| mib * 1024 * 1024),
+        fee_tolerance: args.fee_tolerance,
+    };
+    let results = runner::run(entries, &options, libtest::print_result)?;
+    let elapsed = start.elapsed();
+
+    let report = Report::new(&results, elapsed);
+    if let Some(path) = args.report_json.as_deref() {
+        report.write_json(path)?;
+    }
+    if let Some(path) = args.report_junit.as_deref() {
+        report.write_junit(path)?;
+    }
+    if let Some(path) = args.report_resources_csv.as_deref() {
+        report.write_resources_csv(path)?;
+    }
+    if let Some(path) = args.report_coverage_csv.as_deref() {
+        CoverageReport::new(&report).write_csv(path)?;
+    }
+
+    let mut within_budget = true;
+    if let Some(path) = args.resource_baseline.as_deref() {
+        let baseline = Report::load_json(path)?;
+        let regressions = ResourceBudget::new(args.resource_budget).check(&baseline, &report);
+        budget::print_regressions(&regressions, args.resource_regressions_top);
+        within_budget = regressions.is_empty() || args.resource_budget_warn;
+    }
+
+    let passed = libtest::print_summary(&results, filtered_out, elapsed);
+    if !passed || !within_budget {
+        std::process::exit(101);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! Finds the first divergence between the EIP-3155 traces of Kakarot and of a
+//! reference (e.g. revm or geth), and prints the steps around it, e.g.
+//! `trace-diff kakarot.jsonl revm.jsonl --context 20`.
+use std::path::PathBuf;
+
+use clap::Parser;
+use ef_testing::trace::{divergence::Divergence, Trace};
+
+#[derive(Debug, Parser)]
+#[command(about = "Finds the first divergence between two EIP-3155 traces")]
+struct Args {
+    /// JSON lines trace of Kakarot.
+    actual: PathBuf,
+    /// JSON lines trace of the reference.
+    expected: PathBuf,
+    /// Number of matching steps printed before the divergence.
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    context: usize,
+}
+
+fn main() -> Result<(), eyre::Error> {
+    let args = Args::parse();
+    let actual = Trace::read_json_lines(&args.actual)?;
+    let expected = Trace::read_json_lines(&args.expected)?;
+    if actual.len() != expected.len() {
+        println!(
+            "transaction count mismatch: {} != {}",
+            actual.len(),
+            expected.len()
+        );
+    }
+
+    let mut diverged = false;
+    for (index, (actual, expected)) in actual.iter().zip(expected.iter()).enumerate() {
+        let Some(divergence) = Divergence::find(actual, expected) else {
+            continue;
+        };
+        diverged = true;
+        println!("transaction {index}:");
+        print!(
+            "{}",
+            divergence.report(actual, expected, args.context, "actual", "expected")
+        );
+    }
+
+    if !diverged {
+        println!("no divergence found");
+    } else {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,236 @@
+//! Coverage of the opcodes and precompiles by the cases. The coverage of a case
+//! is collected statically, from the code of its pre-state and of the contracts
+//! created by its transactions, and dynamically, from the traces of its
+//! transactions when they contain opcode steps.
+pub mod report;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use ::revm::precompile::{PrecompileSpecId, Precompiles};
+use alloy_primitives::{Address, B256, U256};
+use ef_tests::models::Account;
+use reth_primitives::TransactionSigned;
+use revm_interpreter::{
+    opcode::{CALL, CALLCODE, DELEGATECALL, PUSH1, PUSH32, STATICCALL},
+    OpCode,
+};
+use revm_primitives::TxKind;
+use serde::{Deserialize, Serialize};
+
+use crate::differential::revm::{spec_id, tx_env};
+use crate::models::Fork;
+use crate::trace::Trace;
+
+/// Opcodes and precompiles covered by a case.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Coverage {
+    pub opcodes: BTreeSet<u8>,
+    pub precompiles: BTreeSet<Address>,
+}
+
+impl Coverage {
+    /// Adds the opcodes and precompiles covered by the other coverage.
+    pub fn merge(&mut self, other: &Self) {
+        self.opcodes.extend(other.opcodes.iter());
+        self.precompiles.extend(other.precompiles.iter());
+    }
+}
+
+/// Coverage of a case.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaseCoverage {
+    /// Opcodes found in the code of the pre-state and in the init code of the
+    /// contract creations, and precompiles pushed by that code or called by the
+    /// transactions.
+    pub code: Coverage,
+    /// Opcodes and precompiles executed by the transactions, if their traces
+    /// contain opcode steps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executed: Option<Coverage>,
+}
+
+impl CaseCoverage {
+    /// Collects the coverage of the transactions run from the pre-state, for the
+    /// given fork. The executed coverage is collected from the traces of the
+    /// transactions, if any contains opcode steps.
+    pub fn new(
+        pre: &BTreeMap<Address, Account>,
+        transactions: &[TransactionSigned],
+        traces: &[Trace],
+        fork: Fork,
+    ) -> Self {
+        let precompiles = precompiles(fork);
+        let mut code = Coverage::default();
+        let mut called = Coverage::default();
+
+        for account in pre.values() {
+            code.merge(&analyse_code(&account.code, &precompiles));
+        }
+        for transaction in transactions {
+            let env = tx_env(&transaction.transaction, Address::ZERO);
+            match env.transact_to {
+                TxKind::Create => code.merge(&analyse_code(&env.data, &precompiles)),
+                TxKind::Call(to) if precompiles.contains(&to) => {
+                    called.precompiles.insert(to);
+                }
+                TxKind::Call(_) => {}
+            }
+        }
+        code.merge(&called);
+
+        let executed = analyse_traces(traces, &precompiles).map(|mut executed| {
+            executed.merge(&called);
+            executed
+        });
+
+        Self { code, executed }
+    }
+
+    /// Returns the coverage the case is counted for: the executed opcodes and
+    /// precompiles if known, else the ones found in the code.
+    pub fn covered(&self) -> &Coverage {
+        self.executed.as_ref().unwrap_or(&self.code)
+    }
+}
+
+/// Returns the addresses of the precompiles of the fork.
+pub fn precompiles(fork: Fork) -> BTreeSet<Address> {
+    Precompiles::new(PrecompileSpecId::from_spec_id(spec_id(fork)))
+        .addresses()
+        .copied()
+        .collect()
+}
+
+/// Returns the name of the precompile at the address, or the address if the
+/// precompile is unknown.
+pub fn precompile_name(address: &Address) -> String {
+    let name = match U256::from_be_slice(address.as_slice()).saturating_to::<u64>() {
+        0x01 => "ecrecover",
+        0x02 => "sha256",
+        0x03 => "ripemd160",
+        0x04 => "identity",
+        0x05 => "modexp",
+        0x06 => "ecadd",
+        0x07 => "ecmul",
+        0x08 => "ecpairing",
+        0x09 => "blake2f",
+        0x0a => "point_evaluation",
+        0x100 => "p256verify",
+        _ => return address.to_string(),
+    };
+    name.to_string()
+}
+
+/// Analyses the code, returning the defined opcodes it contains, push data
+/// excluded, and the precompiles whose address is pushed by the code.
+pub fn analyse_code(code: &[u8], precompiles: &BTreeSet<Address>) -> Coverage {
+    let mut coverage = Coverage::default();
+    let mut pc = 0;
+    while pc < code.len() {
+        let op = code[pc];
+        if OpCode::new(op).is_some() {
+            coverage.opcodes.insert(op);
+        }
+
+        let immediate_size = if (PUSH1..=PUSH32).contains(&op) {
+            usize::from(op - PUSH1 + 1)
+        } else {
+            0
+        };
+        let immediate = &code[(pc + 1).min(code.len())..(pc + 1 + immediate_size).min(code.len())];
+        if !immediate.is_empty() && immediate.len() <= Address::len_bytes() {
+            let address = Address::left_padding_from(immediate);
+            if precompiles.contains(&address) {
+                coverage.precompiles.insert(address);
+            }
+        }
+
+        pc += 1 + immediate_size;
+    }
+    coverage
+}
+
+/// Analyses the traces, returning the executed opcodes and the precompiles
+/// called by the calls of the traces. Returns `None` if the traces contain no
+/// opcode step.
+pub fn analyse_traces(traces: &[Trace], precompiles: &BTreeSet<Address>) -> Option<Coverage> {
+    if traces.iter().all(|trace| trace.steps.is_empty()) {
+        return None;
+    }
+
+    let mut coverage = Coverage::default();
+    for step in traces.iter().flat_map(|trace| trace.steps.iter()) {
+        coverage.opcodes.insert(step.op);
+
+        // The address is the second item of the stack of a call.
+        if matches!(step.op, CALL | CALLCODE | DELEGATECALL | STATICCALL) {
+            let Some(address) = step.stack.iter().rev().nth(1) else {
+                continue;
+            };
+            let address = Address::from_word(B256::from(*address));
+            if precompiles.contains(&address) {
+                coverage.precompiles.insert(address);
+            }
+        }
+    }
+    Some(coverage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::TraceStep;
+    use alloy_primitives::address;
+
+    #[test]
+    fn test_analyse_code() {
+        // Given
+        // PUSH1 0x5b PUSH1 0x02 GAS STATICCALL, followed by the undefined opcode 0x0c
+        let code = [0x60, 0x5b, 0x60, 0x02, 0x5a, 0xfa, 0x0c];
+        let precompiles = precompiles(Fork::Cancun);
+
+        // When
+        let coverage = analyse_code(&code, &precompiles);
+
+        // Then
+        assert_eq!(coverage.opcodes, BTreeSet::from([0x5a, 0x60, 0xfa]));
+        assert_eq!(
+            coverage.precompiles,
+            BTreeSet::from([address!("0000000000000000000000000000000000000002")])
+        );
+    }
+
+    #[test]
+    fn test_analyse_traces() {
+        // Given
+        let sha256 = address!("0000000000000000000000000000000000000002");
+        let step = |op: u8, stack: Vec<U256>| TraceStep {
+            op,
+            stack,
+            op_name: TraceStep::op_name(op),
+            ..Default::default()
+        };
+        let traces = vec![
+            Trace::default(),
+            Trace {
+                steps: vec![
+                    step(PUSH1, vec![]),
+                    step(
+                        STATICCALL,
+                        vec![U256::ZERO, U256::from(2), U256::from(100_000)],
+                    ),
+                ],
+                ..Default::default()
+            },
+        ];
+
+        // When
+        let coverage = analyse_traces(&traces, &precompiles(Fork::Cancun)).unwrap();
+
+        // Then
+        assert_eq!(coverage.opcodes, BTreeSet::from([PUSH1, STATICCALL]));
+        assert_eq!(coverage.precompiles, BTreeSet::from([sha256]));
+        assert_eq!(analyse_traces(&[Trace::default()], &BTreeSet::new()), None);
+    }
+}
@@ -0,0 +1,207 @@
+//! Aggregation of the coverage of the cases of a run, per opcode and precompile.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use alloy_primitives::Address;
+use revm_interpreter::OpCode;
+use serde::Serialize;
+
+use super::{precompile_name, precompiles};
+use crate::runner::report::{Report, Status};
+use crate::trace::TraceStep;
+
+/// Coverage of an opcode or a precompile by the cases of a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CoverageEntry {
+    /// Name of the opcode or precompile.
+    pub name: String,
+    /// Number of cases covering the opcode or precompile.
+    pub tests: usize,
+    /// Number of cases covering the opcode or precompile according to their
+    /// traces, the others covering it according to their code.
+    pub traced: usize,
+    /// Number of cases which passed, including the unexpected passes.
+    pub passed: usize,
+    /// Number of cases which failed, including the expected failures.
+    pub failed: usize,
+    /// Number of ignored cases.
+    pub skipped: usize,
+}
+
+impl CoverageEntry {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    fn record(&mut self, status: Status, traced: bool) {
+        self.tests += 1;
+        self.traced += usize::from(traced);
+        match status {
+            Status::Passed | Status::UnexpectedPass => self.passed += 1,
+            Status::Failed | Status::ExpectedFailure => self.failed += 1,
+            Status::Ignored => self.skipped += 1,
+        }
+    }
+}
+
+/// Coverage of the opcodes and precompiles by the cases of a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CoverageReport {
+    pub opcodes: BTreeMap<u8, CoverageEntry>,
+    pub precompiles: BTreeMap<Address, CoverageEntry>,
+}
+
+impl CoverageReport {
+    /// Aggregates the coverage of the cases of the report. All the opcodes
+    /// defined by revm and the precompiles of the forks of the run are listed,
+    /// even if no case covers them. Cases without coverage are not counted.
+    pub fn new(report: &Report) -> Self {
+        let opcodes = (0..=u8::MAX)
+            .filter(|op| OpCode::new(*op).is_some())
+            .map(|op| (op, CoverageEntry::new(TraceStep::op_name(op))))
+            .collect();
+        let forks: BTreeSet<_> = report.cases.iter().map(|case| case.fork).collect();
+        let precompiles = forks
+            .into_iter()
+            .flat_map(precompiles)
+            .map(|address| (address, CoverageEntry::new(precompile_name(&address))))
+            .collect();
+        let mut coverage = Self {
+            opcodes,
+            precompiles,
+        };
+
+        for case in report.cases.iter() {
+            let Some(case_coverage) = case.metrics.coverage.as_ref() else {
+                continue;
+            };
+            let traced = case_coverage.executed.is_some();
+            let covered = case_coverage.covered();
+            for op in covered.opcodes.iter() {
+                coverage
+                    .opcodes
+                    .entry(*op)
+                    .or_insert_with(|| CoverageEntry::new(TraceStep::op_name(*op)))
+                    .record(case.status, traced);
+            }
+            for address in covered.precompiles.iter() {
+                coverage
+                    .precompiles
+                    .entry(*address)
+                    .or_insert_with(|| CoverageEntry::new(precompile_name(address)))
+                    .record(case.status, traced);
+            }
+        }
+        coverage
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), eyre::Error> {
+        fs::write(path, self.to_csv())?;
+        Ok(())
+    }
+
+    /// Formats the coverage as CSV, with one row per opcode, then one row per
+    /// precompile.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("kind,code,name,tests,traced,passed,failed,skipped\n");
+        let rows = self
+            .opcodes
+            .iter()
+            .map(|(op, entry)| ("opcode", format!("{op:#04x}"), entry))
+            .chain(
+                self.precompiles
+                    .iter()
+                    .map(|(address, entry)| ("precompile", address.to_string(), entry)),
+            );
+        for (kind, code, entry) in rows {
+            csv += &format!(
+                "{kind},{code},{},{},{},{},{},{}\n",
+                entry.name, entry.tests, entry.traced, entry.passed, entry.failed, entry.skipped
+            );
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::{CaseCoverage, Coverage};
+    use crate::models::metrics::CaseMetrics;
+    use crate::runner::report::CaseReport;
+    use alloy_primitives::address;
+    use build_utils::fork::Fork;
+    use std::path::PathBuf;
+
+    fn case(name: &str, status: Status, coverage: Option<CaseCoverage>) -> CaseReport {
+        CaseReport {
+            name: name.to_string(),
+            case_name: name.to_string(),
+            category: "stExample".to_string(),
+            fork: Fork::Cancun,
+            path: PathBuf::from("stExample/example.json"),
+            status,
+            messages: Vec::new(),
+            metrics: CaseMetrics {
+                coverage,
+                ..Default::default()
+            },
+            duration_secs: 1.,
+        }
+    }
+
+    #[test]
+    fn test_coverage_report() {
+        // Given
+        let sha256 = address!("0000000000000000000000000000000000000002");
+        let code = Coverage {
+            opcodes: BTreeSet::from([0x01, 0xfa]),
+            precompiles: BTreeSet::from([sha256]),
+        };
+        let traced = CaseCoverage {
+            code: code.clone(),
+            executed: Some(Coverage {
+                opcodes: BTreeSet::from([0x01]),
+                ..Default::default()
+            }),
+        };
+        let untraced = CaseCoverage {
+            code,
+            executed: None,
+        };
+        let report = Report {
+            cases: vec![
+                case("test_a", Status::Passed, Some(traced)),
+                case("test_b", Status::ExpectedFailure, Some(untraced.clone())),
+                case("test_c", Status::Ignored, Some(untraced)),
+                case("test_d", Status::Failed, None),
+            ],
+            ..Default::default()
+        };
+
+        // When
+        let coverage = CoverageReport::new(&report);
+
+        // Then
+        assert_eq!(
+            coverage.opcodes[&0x01],
+            CoverageEntry {
+                name: "ADD".to_string(),
+                tests: 3,
+                traced: 1,
+                passed: 1,
+                failed: 1,
+                skipped: 1,
+            }
+        );
+        assert_eq!(coverage.opcodes[&0xfa].tests, 2);
+        assert_eq!(coverage.opcodes[&0x00].tests, 0);
+        assert_eq!(coverage.precompiles[&sha256].name, "sha256");
+        assert_eq!(coverage.precompiles[&sha256].tests, 2);
+        assert!(coverage.to_csv().contains("\nopcode,0x01,ADD,3,1,1,1,1\n"));
+    }
+}
@@ -0,0 +1,230 @@
+//! Differential execution of the EF cases. The block of a case is executed by a
+//! reference EVM, or by another version of Kakarot, from the same pre-state, and
+//! the resulting accounts, storage, logs and gas are compared to the ones of
+//! Kakarot, in order to tell whether a failure comes from the fixture or from
+//! Kakarot.
+pub mod revm;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use alloy_primitives::{Address, Bytes, Log, U256};
+use build_utils::version::KakarotVersion;
+use ef_tests::models::Account;
+
+/// Reference the Kakarot execution is compared to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reference {
+    Revm,
+    /// Another version of Kakarot, executed in the same run.
+    Kakarot(KakarotVersion),
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Revm => write!(f, "revm"),
+            Self::Kakarot(version) => write!(f, "kakarot-{version}"),
+        }
+    }
+}
+
+impl FromStr for Reference {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "revm" => Ok(Self::Revm),
+            _ => s
+                .strip_prefix("kakarot-")
+                .and_then(|version| KakarotVersion::from_str(version).ok())
+                .map(Self::Kakarot)
+                .ok_or_else(|| {
+                    eyre::eyre!(
+                        "unknown reference {s}, expected revm, kakarot-v0, kakarot-v1 or kakarot-native"
+                    )
+                }),
+        }
+    }
+}
+
+/// State of an account after the execution of a block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountSnapshot {
+    pub balance: U256,
+    pub nonce: U256,
+    pub code: Bytes,
+    pub storage: BTreeMap<U256, U256>,
+}
+
+impl From<&Account> for AccountSnapshot {
+    fn from(account: &Account) -> Self {
+        Self {
+            balance: account.balance,
+            nonce: account.nonce,
+            code: account.code.clone(),
+            storage: account.storage.clone(),
+        }
+    }
+}
+
+/// Accounts, logs and gas resulting from the execution of a block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionSnapshot {
+    pub accounts: BTreeMap<Address, AccountSnapshot>,
+    /// Logs emitted by the transactions, if known. The fixtures only hold the
+    /// hash of the logs.
+    pub logs: Option<Vec<Log>>,
+    pub gas_used: u64,
+}
+
+impl ExecutionSnapshot {
+    /// Returns the snapshot of the post state of a fixture.
+    pub fn from_post_state(post_state: &BTreeMap<Address, Account>, gas_used: u64) -> Self {
+        Self {
+            accounts: post_state
+                .iter()
+                .map(|(address, account)| (*address, account.into()))
+                .collect(),
+            logs: None,
+            gas_used,
+        }
+    }
+
+    /// Returns the differences between the snapshot and the other one. Accounts
+    /// and storage slots missing from a snapshot are considered empty, so that
+    /// accounts deleted by one execution match the ones never created by the other.
+    pub fn diff(&self, other: &Self) -> Vec<String> {
+        let mut diffs = Vec::new();
+
+        if self.gas_used != other.gas_used {
+            diffs.push(format!(
+                "gas used mismatch: {} != {}",
+                self.gas_used, other.gas_used
+            ));
+        }
+
+        let addresses: BTreeSet<&Address> =
+            self.accounts.keys().chain(other.accounts.keys()).collect();
+        let empty = AccountSnapshot::default();
+        for address in addresses {
+            let lhs = self.accounts.get(address).unwrap_or(&empty);
+            let rhs = other.accounts.get(address).unwrap_or(&empty);
+            if lhs.balance != rhs.balance {
+                diffs.push(format!(
+                    "balance mismatch for {:#20x}: {:#x} != {:#x}",
+                    address, lhs.balance, rhs.balance
+                ));
+            }
+            if lhs.nonce != rhs.nonce {
+                diffs.push(format!(
+                    "nonce mismatch for {:#20x}: {:#x} != {:#x}",
+                    address, lhs.nonce, rhs.nonce
+                ));
+            }
+            if lhs.code != rhs.code {
+                diffs.push(format!(
+                    "code mismatch for {:#20x}: {:#x} != {:#x}",
+                    address, lhs.code, rhs.code
+                ));
+            }
+            let keys: BTreeSet<&U256> = lhs.storage.keys().chain(rhs.storage.keys()).collect();
+            for key in keys {
+                let lhs_value = lhs.storage.get(key).copied().unwrap_or_default();
+                let rhs_value = rhs.storage.get(key).copied().unwrap_or_default();
+                if lhs_value != rhs_value {
+                    diffs.push(format!(
+                        "storage mismatch for {:#20x} at {:#x}: {:#x} != {:#x}",
+                        address, key, lhs_value, rhs_value
+                    ));
+                }
+            }
+        }
+
+        if let (Some(lhs), Some(rhs)) = (&self.logs, &other.logs) {
+            if lhs.len() != rhs.len() {
+                diffs.push(format!(
+                    "logs count mismatch: {} != {}",
+                    lhs.len(),
+                    rhs.len()
+                ));
+            }
+            for (index, (lhs, rhs)) in lhs.iter().zip(rhs.iter()).enumerate() {
+                if lhs != rhs {
+                    diffs.push(format!("log {index} mismatch: {lhs:?} != {rhs:?}"));
+                }
+            }
+        }
+
+        diffs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, bytes, LogData};
+
+    #[test]
+    fn test_parse_reference() {
+        // Given
+        let references = ["revm", "kakarot-v0", "kakarot-native"];
+
+        // When
+        let parsed: Vec<Reference> = references
+            .iter()
+            .map(|reference| reference.parse().unwrap())
+            .collect();
+
+        // Then
+        assert_eq!(
+            parsed,
+            vec![
+                Reference::Revm,
+                Reference::Kakarot(KakarotVersion::V0),
+                Reference::Kakarot(KakarotVersion::Native)
+            ]
+        );
+        assert_eq!(parsed[1].to_string(), "kakarot-v0");
+        assert!("kakarot-v2".parse::<Reference>().is_err());
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        // Given
+        let account = address!("a94f5374fce5edbc8e2a8697c15331677e6ebf0b");
+        let lhs = ExecutionSnapshot {
+            accounts: BTreeMap::from([(
+                account,
+                AccountSnapshot {
+                    balance: U256::from(10),
+                    storage: BTreeMap::from([(U256::from(1), U256::from(2))]),
+                    ..Default::default()
+                },
+            )]),
+            logs: Some(vec![Log {
+                address: account,
+                data: LogData::new_unchecked(vec![], bytes!("01")),
+            }]),
+            gas_used: 21_000,
+        };
+        let mut rhs = lhs.clone();
+        rhs.accounts.get_mut(&account).unwrap().storage.clear();
+        rhs.logs = None;
+
+        // When
+        let same = lhs.diff(&lhs);
+        let diffs = lhs.diff(&rhs);
+
+        // Then
+        assert!(same.is_empty());
+        assert_eq!(
+            diffs,
+            vec![format!(
+                "storage mismatch for {:#20x} at 0x1: 0x2 != 0x0",
+                account
+            )]
+        );
+    }
+}
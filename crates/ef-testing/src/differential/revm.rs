@@ -0,0 +1,222 @@
+//! Execution of the block of a case by revm.
+use std::collections::BTreeMap;
+
+use ::revm::{
+    db::{AccountState, CacheDB, EmptyDB},
+    primitives::{
+        AccountInfo, AuthorizationList, BlockEnv, Bytecode, SpecId, TxEnv, TxKind, KECCAK_EMPTY,
+    },
+    Evm,
+};
+use alloy_primitives::{Address, U256};
+use ef_tests::models::Account;
+use reth_primitives::{SealedBlock, Transaction, TransactionSigned};
+
+use super::{AccountSnapshot, ExecutionSnapshot};
+use crate::models::{error::RunnerError, Fork};
+
+/// Executes the transactions of the block in revm from the pre-state, for the
+/// given chain id. System calls (e.g. the beacon roots update of EIP-4788) are not
+/// executed. Returns the resulting snapshot, or an error if revm rejects a
+/// transaction of the block.
+pub fn execute_block(
+    pre: &BTreeMap<Address, Account>,
+    block: &SealedBlock,
+    fork: Fork,
+    chain_id: u64,
+) -> Result<ExecutionSnapshot, RunnerError> {
+    execute_transactions(
+        pre,
+        block_env(block),
+        &block.body.transactions,
+        fork,
+        chain_id,
+    )
+}
+
+/// Executes the transactions in revm from the pre-state, in the given block
+/// environment. Returns the resulting snapshot, or an error if revm rejects one
+/// of the transactions.
+pub fn execute_transactions(
+    pre: &BTreeMap<Address, Account>,
+    block_env: BlockEnv,
+    transactions: &[TransactionSigned],
+    fork: Fork,
+    chain_id: u64,
+) -> Result<ExecutionSnapshot, RunnerError> {
+    let mut db = pre_state_db(pre)?;
+
+    let mut gas_used = 0;
+    let mut logs = Vec::new();
+    {
+        let mut evm = Evm::builder()
+            .with_db(&mut db)
+            .modify_cfg_env(|cfg| cfg.chain_id = chain_id)
+            .modify_block_env(|env| *env = block_env)
+            .with_spec_id(spec_id(fork))
+            .build();
+
+        for tx in transactions.iter() {
+            let sender = tx.recover_signer().ok_or_else(|| {
+                RunnerError::Other(vec!["revm: unable to recover the sender".to_string()].into())
+            })?;
+            *evm.tx_mut() = tx_env(&tx.transaction, sender);
+            let result = evm.transact_commit().map_err(|err| {
+                RunnerError::Other(vec![format!("revm rejected the transaction: {err}")].into())
+            })?;
+            gas_used += result.gas_used();
+            logs.extend(result.logs().iter().cloned());
+        }
+    }
+
+    let accounts = db
+        .accounts
+        .iter()
+        .filter(|(_, account)| account.account_state != AccountState::NotExisting)
+        .map(|(address, account)| {
+            let code = account
+                .info
+                .code
+                .clone()
+                .or_else(|| db.contracts.get(&account.info.code_hash).cloned())
+                .map(|code| code.original_bytes())
+                .unwrap_or_default();
+            let snapshot = AccountSnapshot {
+                balance: account.info.balance,
+                nonce: U256::from(account.info.nonce),
+                code,
+                storage: account
+                    .storage
+                    .iter()
+                    .map(|(key, value)| (*key, *value))
+                    .collect::<BTreeMap<_, _>>(),
+            };
+            (*address, snapshot)
+        })
+        .collect();
+
+    Ok(ExecutionSnapshot {
+        accounts,
+        logs: Some(logs),
+        gas_used,
+    })
+}
+
+/// Returns the revm database holding the pre-state.
+pub(crate) fn pre_state_db(
+    pre: &BTreeMap<Address, Account>,
+) -> Result<CacheDB<EmptyDB>, RunnerError> {
+    let mut db = CacheDB::new(EmptyDB::default());
+    for (address, account) in pre.iter() {
+        let code = Bytecode::new_raw_checked(account.code.clone()).map_err(|err| {
+            RunnerError::Other(vec![format!("invalid code for {address:#20x}: {err:?}")].into())
+        })?;
+        db.insert_account_info(
+            *address,
+            AccountInfo {
+                balance: account.balance,
+                nonce: account.nonce.saturating_to(),
+                code_hash: KECCAK_EMPTY,
+                code: Some(code),
+            },
+        );
+        for (key, value) in account.storage.iter() {
+            // The empty database is infallible
+            let _ = db.insert_account_storage(*address, *key, *value);
+        }
+    }
+    Ok(db)
+}
+
+/// Returns the revm environment of the block.
+pub(crate) fn block_env(block: &SealedBlock) -> BlockEnv {
+    let header = &block.header;
+    let mut block_env = BlockEnv {
+        number: U256::from(header.number),
+        coinbase: header.beneficiary,
+        timestamp: U256::from(header.timestamp),
+        gas_limit: U256::from(header.gas_limit),
+        basefee: U256::from(header.base_fee_per_gas.unwrap_or_default()),
+        difficulty: header.difficulty,
+        prevrandao: Some(header.mix_hash),
+        blob_excess_gas_and_price: None,
+    };
+    if let Some(excess_blob_gas) = header.excess_blob_gas {
+        block_env.set_blob_excess_gas_and_price(excess_blob_gas);
+    }
+    block_env
+}
+
+pub(crate) const fn spec_id(fork: Fork) -> SpecId {
+    match fork {
+        Fork::Shanghai => SpecId::SHANGHAI,
+        Fork::Cancun => SpecId::CANCUN,
+        Fork::Prague => SpecId::PRAGUE,
+    }
+}
+
+/// Returns the revm environment of the transaction.
+pub(crate) fn tx_env(transaction: &Transaction, caller: Address) -> TxEnv {
+    let mut env = TxEnv {
+        caller,
+        ..Default::default()
+    };
+    match transaction {
+        Transaction::Legacy(tx) => {
+            env.chain_id = tx.chain_id;
+            env.nonce = Some(tx.nonce);
+            env.gas_limit = tx.gas_limit;
+            env.gas_price = U256::from(tx.gas_price);
+            env.transact_to = tx.to;
+            env.value = tx.value;
+            env.data = tx.input.clone();
+        }
+        Transaction::Eip2930(tx) => {
+            env.chain_id = Some(tx.chain_id);
+            env.nonce = Some(tx.nonce);
+            env.gas_limit = tx.gas_limit;
+            env.gas_price = U256::from(tx.gas_price);
+            env.transact_to = tx.to;
+            env.value = tx.value;
+            env.data = tx.input.clone();
+            env.access_list = tx.access_list.to_vec();
+        }
+        Transaction::Eip1559(tx) => {
+            env.chain_id = Some(tx.chain_id);
+            env.nonce = Some(tx.nonce);
+            env.gas_limit = tx.gas_limit;
+            env.gas_price = U256::from(tx.max_fee_per_gas);
+            env.gas_priority_fee = Some(U256::from(tx.max_priority_fee_per_gas));
+            env.transact_to = tx.to;
+            env.value = tx.value;
+            env.data = tx.input.clone();
+            env.access_list = tx.access_list.to_vec();
+        }
+        Transaction::Eip4844(tx) => {
+            env.chain_id = Some(tx.chain_id);
+            env.nonce = Some(tx.nonce);
+            env.gas_limit = tx.gas_limit;
+            env.gas_price = U256::from(tx.max_fee_per_gas);
+            env.gas_priority_fee = Some(U256::from(tx.max_priority_fee_per_gas));
+            env.transact_to = TxKind::Call(tx.to);
+            env.value = tx.value;
+            env.data = tx.input.clone();
+            env.access_list = tx.access_list.to_vec();
+            env.blob_hashes = tx.blob_versioned_hashes.clone();
+            env.max_fee_per_blob_gas = Some(U256::from(tx.max_fee_per_blob_gas));
+        }
+        Transaction::Eip7702(tx) => {
+            env.chain_id = Some(tx.chain_id);
+            env.nonce = Some(tx.nonce);
+            env.gas_limit = tx.gas_limit;
+            env.gas_price = U256::from(tx.max_fee_per_gas);
+            env.gas_priority_fee = Some(U256::from(tx.max_priority_fee_per_gas));
+            env.transact_to = TxKind::Call(tx.to);
+            env.value = tx.value;
+            env.data = tx.input.clone();
+            env.access_list = tx.access_list.to_vec();
+            env.authorization_list = Some(AuthorizationList::Signed(tx.authorization_list.clone()));
+        }
+    }
+    env
+}
@@ -0,0 +1,325 @@
+//! Encoding of EVM accounts into the Starknet storage of Kakarot account
+//! contracts, and decoding of EVM accounts from that storage.
+//!
+//! An account is stored as:
+//! - its EVM address, initialization flag and nonce;
+//! - its code, packed in 31-byte big-endian chunks, and its length;
+//! - the keccak hash of its code, split in two 128-bit limbs;
+//! - the valid jump destinations of its code;
+//! - its EVM storage, each key and value split in two 128-bit limbs.
+//!
+//! The balance of an account is held by the fee token contract and is not part
+//! of the account storage.
+//!
+//! The Cairo 0 and Cairo 1 account contracts share this layout, so that a
+//! single codec is used for all the versions of Kakarot.
+use std::collections::BTreeMap;
+
+use alloy_consensus::constants::KECCAK_EMPTY;
+use alloy_primitives::{keccak256, Address, Bytes, U256};
+use blockifier::state::state_api::StateResult;
+use ef_tests::models::Account;
+use revm_interpreter::analysis::to_analysed;
+use revm_primitives::Bytecode;
+use starknet_api::abi::abi_utils::get_storage_var_address;
+use starknet_api::state::StorageKey;
+use starknet_api::StarknetApiError;
+use starknet_crypto::Felt;
+
+use super::{delegated_address, pack_byte_array_to_starkfelt_array};
+use crate::evm_sequencer::constants::storage_variables::{
+    ACCOUNT_BYTECODE_LEN, ACCOUNT_CODE_HASH, ACCOUNT_EVM_ADDRESS, ACCOUNT_IMPLEMENTATION,
+    ACCOUNT_IS_INITIALIZED, ACCOUNT_KAKAROT_ADDRESS, ACCOUNT_NONCE, ACCOUNT_PUBLIC_KEY,
+    ACCOUNT_STORAGE, ACCOUNT_VALID_JUMPDESTS, OWNABLE_OWNER,
+};
+use crate::evm_sequencer::utils::{felt_to_bytes, split_u256};
+use crate::starknet_storage;
+
+/// Number of bytes packed in a chunk of code.
+const BYTES_PER_CHUNK: usize = 31;
+
+/// Storage variables of the account contract which are not part of the EVM
+/// account: its implementation class and owner, written when the account is set
+/// up, and its Kakarot address and public key, written by the contract itself.
+const CONTRACT_VARIABLES: [&str; 4] = [
+    ACCOUNT_IMPLEMENTATION,
+    OWNABLE_OWNER,
+    ACCOUNT_KAKAROT_ADDRESS,
+    ACCOUNT_PUBLIC_KEY,
+];
+
+/// Codec of an EVM account to and from the Starknet storage of its Kakarot
+/// account contract.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccountCodec;
+
+impl AccountCodec {
+    /// Encodes the account at the given address into the storage entries of its
+    /// account contract.
+    pub fn encode(
+        &self,
+        evm_address: &Address,
+        account: &Account,
+    ) -> Result<Vec<(StorageKey, Felt)>, StarknetApiError> {
+        let nonce = Felt::from(TryInto::<u128>::try_into(account.nonce).map_err(|err| {
+            StarknetApiError::OutOfRange {
+                string: err.to_string(),
+            }
+        })?);
+        let evm_address = Felt::from_bytes_be_slice(&evm_address.0[..]);
+
+        let mut storage = vec![
+            starknet_storage!(ACCOUNT_EVM_ADDRESS, evm_address),
+            starknet_storage!(ACCOUNT_IS_INITIALIZED, 1u8),
+            starknet_storage!(ACCOUNT_NONCE, nonce),
+        ];
+
+        // Initialize the bytecode storage vars.
+        storage.push((
+            Self::bytecode_len_key(),
+            Felt::from(account.code.len() as u64),
+        ));
+        for (index, chunk) in pack_byte_array_to_starkfelt_array(&account.code).enumerate() {
+            storage.push((Self::chunk_key(index), chunk));
+        }
+
+        // Initialize the code hash var
+        let account_is_empty =
+            account.code.is_empty() && nonce == Felt::ZERO && account.balance == U256::ZERO;
+        let code_hash = if account_is_empty {
+            U256::ZERO
+        } else if account.code.is_empty() {
+            U256::from_be_slice(KECCAK_EMPTY.as_slice())
+        } else {
+            U256::from_be_slice(keccak256(&account.code).as_slice())
+        };
+        let code_hash_values = split_u256(code_hash);
+        let code_hash_low_key = get_storage_var_address(ACCOUNT_CODE_HASH, &[]);
+        let code_hash_high_key = code_hash_low_key.next_storage_key()?;
+        storage.extend([
+            (code_hash_low_key, Felt::from(code_hash_values[0])),
+            (code_hash_high_key, Felt::from(code_hash_values[1])),
+        ]);
+
+        // Initialize the bytecode jumpdests. The code of a delegated account is the
+        // delegation designator, which is never executed.
+        let valid_jumpdests: Vec<usize> = if delegated_address(&account.code).is_some() {
+            Vec::new()
+        } else {
+            match to_analysed(Bytecode::new_raw(account.code.clone())) {
+                Bytecode::LegacyAnalyzed(legacy_analyzed_bytecode) => legacy_analyzed_bytecode
+                    .jump_table()
+                    .0
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, bit)| bit.as_ref().then(|| index))
+                    .collect(),
+                _ => unreachable!("Bytecode should be analysed"),
+            }
+        };
+        let jumpdests_storage_address =
+            Felt::from(get_storage_var_address(ACCOUNT_VALID_JUMPDESTS, &[]));
+        for index in valid_jumpdests {
+            storage.push((
+                (jumpdests_storage_address + Felt::from(index)).try_into()?,
+                Felt::ONE,
+            ));
+        }
+
+        // Initialize the storage vars.
+        for (key, value) in &account.storage {
+            let [low_key, high_key] = Self::storage_keys(*key)?;
+            let [low, high] = split_u256(*value);
+            storage.extend([(low_key, Felt::from(low)), (high_key, Felt::from(high))]);
+        }
+
+        Ok(storage)
+    }
+
+    /// Encodes the implementation class and the owner of the account contract.
+    pub fn encode_contract(&self, implementation: Felt, owner: Felt) -> Vec<(StorageKey, Felt)> {
+        vec![
+            starknet_storage!(ACCOUNT_IMPLEMENTATION, implementation),
+            starknet_storage!(OWNABLE_OWNER, owner),
+        ]
+    }
+
+    /// Returns the storage keys of the variables of the account contract which
+    /// are not part of the EVM account.
+    pub fn contract_keys(&self) -> impl Iterator<Item = StorageKey> {
+        CONTRACT_VARIABLES
+            .into_iter()
+            .map(|var| get_storage_var_address(var, &[]))
+    }
+
+    /// Decodes the account from the storage of its account contract, read
+    /// through `read`. Only the given EVM storage keys are decoded, and the
+    /// balance is left to zero.
+    pub fn decode(
+        &self,
+        storage_keys: impl IntoIterator<Item = U256>,
+        mut read: impl FnMut(StorageKey) -> StateResult<Felt>,
+    ) -> StateResult<Account> {
+        let nonce = self.decode_nonce(&mut read)?;
+        let code = self.decode_code(&mut read)?;
+        let storage = storage_keys
+            .into_iter()
+            .map(|key| Ok((key, self.decode_storage(key, &mut read)?)))
+            .collect::<StateResult<BTreeMap<_, _>>>()?;
+
+        Ok(Account {
+            nonce,
+            code,
+            storage,
+            ..Default::default()
+        })
+    }
+
+    /// Decodes the nonce of the account.
+    pub fn decode_nonce(
+        &self,
+        mut read: impl FnMut(StorageKey) -> StateResult<Felt>,
+    ) -> StateResult<U256> {
+        let nonce = read(get_storage_var_address(ACCOUNT_NONCE, &[]))?;
+        Ok(U256::from_be_bytes(nonce.to_bytes_be()))
+    }
+
+    /// Decodes the code of the account. The code of an EOA has a zero length,
+    /// and the code of an account delegating its code (EIP-7702) is the
+    /// delegation designator.
+    pub fn decode_code(
+        &self,
+        mut read: impl FnMut(StorageKey) -> StateResult<Felt>,
+    ) -> StateResult<Bytes> {
+        let bytecode_len = read(Self::bytecode_len_key())?;
+        let bytecode_len: u64 = bytecode_len.to_biguint().try_into()?;
+        let bytecode_len = bytecode_len as usize;
+
+        let mut bytecode = Vec::with_capacity(bytecode_len);
+        for index in 0..bytecode_len.div_ceil(BYTES_PER_CHUNK) {
+            let chunk = read(Self::chunk_key(index))?;
+            // The last chunk holds the remaining bytes, right-aligned in the felt.
+            let chunk_len = BYTES_PER_CHUNK.min(bytecode_len - bytecode.len());
+            bytecode.extend_from_slice(&felt_to_bytes(&chunk, 32 - chunk_len));
+        }
+
+        Ok(Bytes::from(bytecode))
+    }
+
+    /// Decodes the value of the EVM storage at the given key.
+    pub fn decode_storage(
+        &self,
+        key: U256,
+        mut read: impl FnMut(StorageKey) -> StateResult<Felt>,
+    ) -> StateResult<U256> {
+        let [low_key, high_key] = Self::storage_keys(key)?;
+        let low = U256::from_be_bytes(read(low_key)?.to_bytes_be());
+        let high = U256::from_be_bytes(read(high_key)?.to_bytes_be());
+
+        Ok(high << 128 | low)
+    }
+
+    /// Returns the storage keys of the low and high limbs of the value of the
+    /// EVM storage at the given key.
+    fn storage_keys(key: U256) -> Result<[StorageKey; 2], StarknetApiError> {
+        let keys = split_u256(key).map(Into::into);
+        let low_key = get_storage_var_address(ACCOUNT_STORAGE, &keys);
+        let high_key = low_key.next_storage_key()?;
+        Ok([low_key, high_key])
+    }
+
+    /// Returns the storage key of the length of the code.
+    fn bytecode_len_key() -> StorageKey {
+        get_storage_var_address(ACCOUNT_BYTECODE_LEN, &[])
+    }
+
+    /// Returns the storage key of the code chunk at the given index.
+    fn chunk_key(index: usize) -> StorageKey {
+        StorageKey::from(index as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::{btree_map, vec};
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+
+    fn round_trip(codec: AccountCodec, address: Address, account: &Account) -> Account {
+        let storage: HashMap<StorageKey, Felt> = codec
+            .encode(&address, account)
+            .unwrap()
+            .into_iter()
+            .collect();
+        codec
+            .decode(account.storage.keys().copied(), |key| {
+                Ok(storage.get(&key).copied().unwrap_or_default())
+            })
+            .unwrap()
+    }
+
+    fn u256() -> impl Strategy<Value = U256> {
+        any::<[u8; 32]>().prop_map(U256::from_be_bytes)
+    }
+
+    fn account(max_code_size: usize) -> impl Strategy<Value = Account> {
+        (
+            vec(any::<u8>(), 0..max_code_size),
+            btree_map(u256(), u256(), 0..16),
+            any::<u128>(),
+        )
+            .prop_map(|(code, storage, nonce)| Account {
+                code: code.into(),
+                storage,
+                nonce: U256::from(nonce),
+                ..Default::default()
+            })
+    }
+
+    #[test]
+    fn test_encode_chunks() {
+        // Given
+        let codec = AccountCodec;
+        let account = Account {
+            code: Bytes::from(vec![0x5b; 32]),
+            ..Default::default()
+        };
+
+        // When
+        let storage = codec.encode(&Address::ZERO, &account).unwrap();
+
+        // Then
+        assert!(storage.contains(&(
+            get_storage_var_address(ACCOUNT_BYTECODE_LEN, &[]),
+            Felt::from(32)
+        )));
+        assert!(storage.contains(&(
+            StorageKey::from(0u32),
+            Felt::from_bytes_be_slice(&[0x5b; 31])
+        )));
+        assert!(storage.contains(&(StorageKey::from(1u32), Felt::from(0x5b))));
+    }
+
+    #[test]
+    fn test_decode_code_out_of_range_length() {
+        // Given
+        let codec = AccountCodec;
+
+        // When
+        let result = codec.decode_code(|_| Ok(Felt::MAX));
+
+        // Then
+        assert!(result.is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(
+            address in any::<[u8; 20]>().prop_map(Address::from),
+            account in account(1_000),
+        ) {
+            prop_assert_eq!(&round_trip(AccountCodec, address, &account), &account);
+        }
+    }
+}
@@ -1,17 +1,11 @@
-use crate::evm_sequencer::constants::storage_variables::{
-    ACCOUNT_BYTECODE_LEN, ACCOUNT_CODE_HASH, ACCOUNT_EVM_ADDRESS, ACCOUNT_IS_INITIALIZED,
-    ACCOUNT_NONCE, ACCOUNT_STORAGE, ACCOUNT_VALID_JUMPDESTS,
-};
-use crate::evm_sequencer::utils::split_u256;
-use crate::starknet_storage;
-use alloy_consensus::constants::KECCAK_EMPTY;
-use alloy_primitives::keccak256;
-use alloy_primitives::{Address, U256};
+pub mod codec;
+
+pub use codec::AccountCodec;
+
+use alloy_eips::eip7702::constants::EIP7702_DELEGATION_DESIGNATOR;
+use alloy_primitives::{Address, Bytes};
 use ef_tests::models::Account;
-use revm_interpreter::analysis::to_analysed;
-use revm_primitives::Bytecode;
 use starknet::core::utils::cairo_short_string_to_felt;
-use starknet_api::abi::abi_utils::get_storage_var_address;
 use starknet_api::StarknetApiError;
 use starknet_api::{core::Nonce, state::StorageKey};
 use starknet_crypto::{poseidon_permute_comp, Felt};
@@ -44,6 +38,7 @@ pub struct KakarotAccount {
     pub(crate) evm_address: Felt,
     pub(crate) nonce: Nonce,
     pub(crate) storage: Vec<(StorageKey, Felt)>,
+    pub(crate) delegation: Option<Address>,
 }
 
 impl KakarotAccount {
@@ -58,6 +53,11 @@ impl KakarotAccount {
     pub fn storage(&self) -> &[(StorageKey, Felt)] {
         self.storage.as_slice()
     }
+
+    /// Returns the address the account delegates its code to (EIP-7702).
+    pub const fn delegation(&self) -> Option<&Address> {
+        self.delegation.as_ref()
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -69,94 +69,46 @@ pub enum AccountType {
 }
 
 impl KakarotAccount {
+    /// Creates the account at the given address, encoded with the layout shared
+    /// by all versions of Kakarot.
     pub fn new(evm_address: &Address, account: Account) -> Result<Self, StarknetApiError> {
-        let nonce = Felt::from(TryInto::<u128>::try_into(account.nonce).map_err(|err| {
-            StarknetApiError::OutOfRange {
-                string: err.to_string(),
-            }
-        })?);
-
-        let evm_address = Felt::from_bytes_be_slice(&evm_address.0[..]);
-
-        let mut storage = vec![
-            starknet_storage!(ACCOUNT_EVM_ADDRESS, evm_address),
-            starknet_storage!(ACCOUNT_IS_INITIALIZED, 1u8),
-            starknet_storage!(ACCOUNT_BYTECODE_LEN, account.code.len() as u32),
-            starknet_storage!(ACCOUNT_NONCE, nonce),
-        ];
-
-        // Initialize the bytecode storage var.
-        let mut bytecode_storage = pack_byte_array_to_starkfelt_array(&account.code)
-            .enumerate()
-            .map(|(i, bytes)| (StorageKey::from(i as u32), bytes))
-            .collect();
-        storage.append(&mut bytecode_storage);
-
-        // Initialize the code hash var
-        let account_is_empty =
-            account.code.is_empty() && nonce == Felt::ZERO && account.balance == U256::ZERO;
-        let code_hash = if account_is_empty {
-            U256::ZERO
-        } else if account.code.is_empty() {
-            U256::from_be_slice(KECCAK_EMPTY.as_slice())
-        } else {
-            U256::from_be_slice(keccak256(account.code.clone()).as_slice())
-        };
-
-        let code_hash_values = split_u256(code_hash);
-        let code_hash_low_key = get_storage_var_address(ACCOUNT_CODE_HASH, &[]);
-        let code_hash_high_key = code_hash_low_key.next_storage_key()?;
-        storage.extend([
-            (code_hash_low_key, Felt::from(code_hash_values[0])),
-            (code_hash_high_key, Felt::from(code_hash_values[1])),
-        ]);
-
-        // Initialize the bytecode jumpdests.
-        let bytecode = to_analysed(Bytecode::new_raw(account.code));
-        let valid_jumpdests: Vec<usize> = match bytecode {
-            Bytecode::LegacyAnalyzed(legacy_analyzed_bytecode) => legacy_analyzed_bytecode
-                .jump_table()
-                .0
-                .iter()
-                .enumerate()
-                .filter_map(|(index, bit)| bit.as_ref().then(|| index))
-                .collect(),
-            _ => unreachable!("Bytecode should be analysed"),
-        };
+        Self::with_codec(evm_address, account, &AccountCodec)
+    }
 
-        let jumdpests_storage_address = get_storage_var_address(ACCOUNT_VALID_JUMPDESTS, &[]);
-        let jumdpests_storage_address = Felt::from(jumdpests_storage_address);
-        valid_jumpdests.into_iter().for_each(|index| {
-            storage.push((
-                (jumdpests_storage_address + Felt::from(index))
-                    .try_into()
-                    .unwrap(),
-                Felt::ONE,
-            ))
-        });
-
-        // Initialize the storage vars.
-        let mut evm_storage_storage: Vec<(StorageKey, Felt)> = account
-            .storage
-            .iter()
-            .flat_map(|(k, v)| {
-                let keys = split_u256(*k).map(Into::into);
-                let values = split_u256(*v).map(Into::<Felt>::into);
-                let low_key = get_storage_var_address(ACCOUNT_STORAGE, &keys);
-                let high_key = low_key.next_storage_key().unwrap(); // can fail only if low is the max key
-                vec![(low_key, values[0]), (high_key, values[1])]
-            })
-            .collect();
-        storage.append(&mut evm_storage_storage);
+    /// Creates the account at the given address, encoded with the given codec.
+    pub fn with_codec(
+        evm_address: &Address,
+        account: Account,
+        codec: &AccountCodec,
+    ) -> Result<Self, StarknetApiError> {
+        let storage = codec.encode(evm_address, &account)?;
+        // The nonce fits in a u128, as checked by the encoding.
+        let nonce = Felt::from_bytes_be(&account.nonce.to_be_bytes());
 
         Ok(Self {
             storage,
-            evm_address,
+            evm_address: Felt::from_bytes_be_slice(&evm_address.0[..]),
             nonce: Nonce(nonce),
+            delegation: delegated_address(&account.code),
         })
     }
 }
 
+/// Returns the delegation designator (EIP-7702) of an account delegating its
+/// code to the given address: `0xef0100 || address`.
+pub fn delegation_designator(address: &Address) -> Bytes {
+    [&EIP7702_DELEGATION_DESIGNATOR[..], address.as_slice()]
+        .concat()
+        .into()
+}
+
+/// Returns the address the code delegates to, if the code is a delegation
+/// designator (EIP-7702).
+pub fn delegated_address(code: &[u8]) -> Option<Address> {
+    let address = code.strip_prefix(&EIP7702_DELEGATION_DESIGNATOR[..])?;
+    (address.len() == Address::len_bytes()).then(|| Address::from_slice(address))
+}
+
 /// Splits a byte array into 31-byte chunks and converts each chunk to a Felt.
 pub fn pack_byte_array_to_starkfelt_array(bytes: &[u8]) -> impl Iterator<Item = Felt> + '_ {
     bytes.chunks(31).map(Felt::from_bytes_be_slice)
@@ -184,10 +136,12 @@ pub fn inner_byte_array_pointer(base_address: Felt, storage_segment: Felt) -> Fe
 
 #[cfg(test)]
 mod tests {
-    use crate::evm_sequencer::constants::storage_variables::ACCOUNT_BYTECODE;
+    use crate::evm_sequencer::constants::storage_variables::{
+        ACCOUNT_BYTECODE, ACCOUNT_VALID_JUMPDESTS,
+    };
 
     use super::*;
-    use alloy_primitives::Bytes;
+    use alloy_primitives::address;
     use starknet_api::abi::abi_utils::get_storage_var_address;
 
     #[test]
@@ -202,6 +156,45 @@ mod tests {
         assert_eq!(result, vec![Felt::from(0x0102030405u64)]);
     }
 
+    #[test]
+    fn test_delegation_designator() {
+        // Given
+        let delegate = address!("1000000000000000000000000000000000000001");
+
+        // When
+        let designator = delegation_designator(&delegate);
+
+        // Then
+        assert_eq!(designator.len(), 23);
+        assert_eq!(delegated_address(&designator), Some(delegate));
+        assert_eq!(delegated_address(&designator[..22]), None);
+        assert_eq!(delegated_address(&[0x60, 0x01]), None);
+    }
+
+    #[test]
+    fn test_new_delegated_account() {
+        // Given
+        // Address made of JUMPDEST opcodes, which should not be analysed as such.
+        let delegate = address!("5b5b5b5b5b5b5b5b5b5b5b5b5b5b5b5b5b5b5b5b");
+        let account = Account {
+            code: delegation_designator(&delegate),
+            ..Default::default()
+        };
+
+        // When
+        let account = KakarotAccount::new(&Address::ZERO, account).unwrap();
+
+        // Then
+        assert_eq!(account.delegation(), Some(&delegate));
+        let jumpdests = Felt::from(get_storage_var_address(ACCOUNT_VALID_JUMPDESTS, &[]));
+        let keys: Vec<Felt> = account
+            .storage()
+            .iter()
+            .map(|(key, _)| Felt::from(*key))
+            .collect();
+        assert!((0..23u8).all(|index| !keys.contains(&(jumpdests + Felt::from(index)))));
+    }
+
     #[test]
     fn test_inner_byte_array_pointer() {
         // Given
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::fs;
 
 use alloy_primitives::{address, Address};
+use build_utils::version::KakarotVersion;
 use lazy_static::lazy_static;
 use serde::de::DeserializeOwned;
 use starknet::core::types::contract::CompiledClass;
@@ -24,6 +25,17 @@ pub fn get_raw_contract_class(contract_path: &str) -> String {
     fs::read_to_string(contract_path).unwrap()
 }
 
+/// Version of Kakarot selected by the features of the crate.
+pub const KAKAROT_VERSION: Option<KakarotVersion> = if cfg!(feature = "native") {
+    Some(KakarotVersion::Native)
+} else if cfg!(feature = "v1") {
+    Some(KakarotVersion::V1)
+} else if cfg!(feature = "v0") {
+    Some(KakarotVersion::V0)
+} else {
+    None
+};
+
 // Chain params
 pub const CHAIN_ID: u64 = 0x1;
 
@@ -72,45 +84,79 @@ lazy_static! {
     pub static ref RELAYER_VERIFYING_KEY: VerifyingKey = RELAYER_SIGNING_KEY.verifying_key();
 }
 
-#[cfg(feature = "v0")]
-lazy_static! {
-    // Main contract classes v0
-    pub static ref KAKAROT_CLASS: LegacyContractClass = load_contract_class("../../build/v0/kakarot.json").expect("Failed to load Kakarot contract class");
-    pub static ref ACCOUNT_CONTRACT_CLASS: LegacyContractClass = load_contract_class("../../build/v0/account_contract.json").expect("Failed to load ContractAccount contract class");
-    pub static ref UNINITIALIZED_ACCOUNT_CLASS: LegacyContractClass = load_contract_class("../../build/v0/uninitialized_account.json").expect("Failed to load uninitialized account c contract class");
-
-    // Main class hashes
-    pub static ref KAKAROT_CLASS_HASH: ClassHash = ClassHash(KAKAROT_CLASS.class_hash().unwrap());
-    pub static ref ACCOUNT_CONTRACT_CLASS_HASH: ClassHash = ClassHash(ACCOUNT_CONTRACT_CLASS.class_hash().unwrap());
-    pub static ref UNINITIALIZED_ACCOUNT_CLASS_HASH: ClassHash = ClassHash(UNINITIALIZED_ACCOUNT_CLASS.class_hash().unwrap());
+/// Classes of Kakarot v0, loaded independently of the features so that both
+/// versions can be run in the same process.
+pub mod v0 {
+    use lazy_static::lazy_static;
+    use starknet::core::types::contract::legacy::LegacyContractClass;
+    use starknet_api::core::ClassHash;
+
+    use super::load_contract_class;
+
+    lazy_static! {
+        // Main contract classes v0
+        pub static ref KAKAROT_CLASS: LegacyContractClass = load_contract_class("../../build/v0/kakarot.json").expect("Failed to load Kakarot contract class");
+        pub static ref ACCOUNT_CONTRACT_CLASS: LegacyContractClass = load_contract_class("../../build/v0/account_contract.json").expect("Failed to load ContractAccount contract class");
+        pub static ref UNINITIALIZED_ACCOUNT_CLASS: LegacyContractClass = load_contract_class("../../build/v0/uninitialized_account.json").expect("Failed to load uninitialized account c contract class");
+
+        // Main class hashes
+        pub static ref KAKAROT_CLASS_HASH: ClassHash = ClassHash(KAKAROT_CLASS.class_hash().unwrap());
+        pub static ref ACCOUNT_CONTRACT_CLASS_HASH: ClassHash = ClassHash(ACCOUNT_CONTRACT_CLASS.class_hash().unwrap());
+        pub static ref UNINITIALIZED_ACCOUNT_CLASS_HASH: ClassHash = ClassHash(UNINITIALIZED_ACCOUNT_CLASS.class_hash().unwrap());
+    }
 }
 
-#[cfg(feature = "v1")]
-lazy_static! {
-    // Main contract classes v1
-    pub static ref KAKAROT_CLASS: CompiledClass = load_contract_class("../../build/v1/contracts_KakarotCore.compiled_contract_class.json").expect("Failed to load Kakarot contract class");
-    pub static ref ACCOUNT_CONTRACT_CLASS: CompiledClass = load_contract_class("../../build/v1/contracts_AccountContract.compiled_contract_class.json").expect("Failed to load ContractAccount contract class");
-    pub static ref UNINITIALIZED_ACCOUNT_CLASS: CompiledClass = load_contract_class("../../build/v1/contracts_UninitializedAccount.compiled_contract_class.json").expect("Failed to load uninitialized account contract class");
-
-    pub static ref KAKAROT_JSON_CLASS: String = get_raw_contract_class("../../build/v1/contracts_KakarotCore.contract_class.json");
-    pub static ref ACCOUNT_CONTRACT_JSON_CLASS: String  = get_raw_contract_class("../../build/v1/contracts_AccountContract.contract_class.json");
-    pub static ref UNINITIALIZED_ACCOUNT_JSON_CLASS: String = get_raw_contract_class("../../build/v1/contracts_UninitializedAccount.contract_class.json");
-
-    // Main class hashes
-    pub static ref KAKAROT_CLASS_HASH: ClassHash = ClassHash(KAKAROT_CLASS.class_hash().unwrap());
-    pub static ref ACCOUNT_CONTRACT_CLASS_HASH: ClassHash = ClassHash(ACCOUNT_CONTRACT_CLASS.class_hash().unwrap());
-    pub static ref UNINITIALIZED_ACCOUNT_CLASS_HASH: ClassHash = ClassHash(UNINITIALIZED_ACCOUNT_CLASS.class_hash().unwrap());
-    pub static ref PROXY_CLASS_HASH: ClassHash = *UNINITIALIZED_ACCOUNT_CLASS_HASH;
-
-    pub static ref CLASS_HASH_TO_JSON_CLASS: HashMap<ClassHash, String> = {
-        let mut map = HashMap::new();
-        map.insert(*KAKAROT_CLASS_HASH, KAKAROT_JSON_CLASS.clone());
-        map.insert(*ACCOUNT_CONTRACT_CLASS_HASH, ACCOUNT_CONTRACT_JSON_CLASS.clone());
-        map.insert(*UNINITIALIZED_ACCOUNT_CLASS_HASH, UNINITIALIZED_ACCOUNT_JSON_CLASS.clone());
-        map
-    };
+/// Classes of Kakarot v1, loaded independently of the features so that both
+/// versions can be run in the same process.
+pub mod v1 {
+    use std::collections::HashMap;
+
+    use lazy_static::lazy_static;
+    use starknet::core::types::contract::CompiledClass;
+    use starknet_api::core::ClassHash;
+
+    use super::{get_raw_contract_class, load_contract_class};
+
+    lazy_static! {
+        // Main contract classes v1
+        pub static ref KAKAROT_CLASS: CompiledClass = load_contract_class("../../build/v1/contracts_KakarotCore.compiled_contract_class.json").expect("Failed to load Kakarot contract class");
+        pub static ref ACCOUNT_CONTRACT_CLASS: CompiledClass = load_contract_class("../../build/v1/contracts_AccountContract.compiled_contract_class.json").expect("Failed to load ContractAccount contract class");
+        pub static ref UNINITIALIZED_ACCOUNT_CLASS: CompiledClass = load_contract_class("../../build/v1/contracts_UninitializedAccount.compiled_contract_class.json").expect("Failed to load uninitialized account contract class");
+
+        pub static ref KAKAROT_JSON_CLASS: String = get_raw_contract_class("../../build/v1/contracts_KakarotCore.contract_class.json");
+        pub static ref ACCOUNT_CONTRACT_JSON_CLASS: String  = get_raw_contract_class("../../build/v1/contracts_AccountContract.contract_class.json");
+        pub static ref UNINITIALIZED_ACCOUNT_JSON_CLASS: String = get_raw_contract_class("../../build/v1/contracts_UninitializedAccount.contract_class.json");
+
+        // Main class hashes
+        pub static ref KAKAROT_CLASS_HASH: ClassHash = ClassHash(KAKAROT_CLASS.class_hash().unwrap());
+        pub static ref ACCOUNT_CONTRACT_CLASS_HASH: ClassHash = ClassHash(ACCOUNT_CONTRACT_CLASS.class_hash().unwrap());
+        pub static ref UNINITIALIZED_ACCOUNT_CLASS_HASH: ClassHash = ClassHash(UNINITIALIZED_ACCOUNT_CLASS.class_hash().unwrap());
+        pub static ref PROXY_CLASS_HASH: ClassHash = *UNINITIALIZED_ACCOUNT_CLASS_HASH;
+
+        pub static ref CLASS_HASH_TO_JSON_CLASS: HashMap<ClassHash, String> = {
+            let mut map = HashMap::new();
+            map.insert(*KAKAROT_CLASS_HASH, KAKAROT_JSON_CLASS.clone());
+            map.insert(*ACCOUNT_CONTRACT_CLASS_HASH, ACCOUNT_CONTRACT_JSON_CLASS.clone());
+            map.insert(*UNINITIALIZED_ACCOUNT_CLASS_HASH, UNINITIALIZED_ACCOUNT_JSON_CLASS.clone());
+            map
+        };
+    }
 }
 
+// Main contract classes and class hashes of the version selected by the features
+#[cfg(feature = "v0")]
+pub use v0::{
+    ACCOUNT_CONTRACT_CLASS, ACCOUNT_CONTRACT_CLASS_HASH, KAKAROT_CLASS, KAKAROT_CLASS_HASH,
+    UNINITIALIZED_ACCOUNT_CLASS, UNINITIALIZED_ACCOUNT_CLASS_HASH,
+};
+#[cfg(feature = "v1")]
+pub use v1::{
+    ACCOUNT_CONTRACT_CLASS, ACCOUNT_CONTRACT_CLASS_HASH, ACCOUNT_CONTRACT_JSON_CLASS,
+    CLASS_HASH_TO_JSON_CLASS, KAKAROT_CLASS, KAKAROT_CLASS_HASH, KAKAROT_JSON_CLASS,
+    PROXY_CLASS_HASH, UNINITIALIZED_ACCOUNT_CLASS, UNINITIALIZED_ACCOUNT_CLASS_HASH,
+    UNINITIALIZED_ACCOUNT_JSON_CLASS,
+};
+
 #[cfg(not(any(feature = "v0", feature = "v1")))]
 lazy_static! {
     pub static ref ACCOUNT_CONTRACT_CLASS_HASH: ClassHash =
@@ -153,6 +199,7 @@ pub mod storage_variables {
 
     pub const KAKAROT_COINBASE: &str = "Kakarot_coinbase";
     pub const KAKAROT_BASE_FEE: &str = "Kakarot_base_fee";
+    pub const KAKAROT_BLOB_BASE_FEE: &str = "Kakarot_blob_base_fee";
     pub const KAKAROT_BLOCK_GAS_LIMIT: &str = "Kakarot_block_gas_limit";
     pub const KAKAROT_CHAIN_ID: &str = "Kakarot_chain_id";
     pub const KAKAROT_EVM_TO_STARKNET_ADDRESS: &str = "Kakarot_evm_to_starknet_address";
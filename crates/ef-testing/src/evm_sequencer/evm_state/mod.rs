@@ -1,21 +1,14 @@
-use crate::evm_sequencer::constants::storage_variables::ACCOUNT_BYTECODE_LEN;
-use crate::evm_sequencer::constants::RELAYER_ADDRESS;
-use crate::evm_sequencer::utils::felt_to_bytes;
-use crate::{
-    evm_sequencer::{
-        account::KakarotAccount,
-        constants::{
-            storage_variables::{
-                ACCOUNT_IMPLEMENTATION, ACCOUNT_NONCE, ACCOUNT_STORAGE, KAKAROT_BASE_FEE,
-                KAKAROT_BLOCK_GAS_LIMIT, KAKAROT_COINBASE, KAKAROT_EVM_TO_STARKNET_ADDRESS,
-                KAKAROT_PREV_RANDAO, OWNABLE_OWNER,
-            },
-            ETH_FEE_TOKEN_ADDRESS, KAKAROT_ADDRESS,
+use crate::evm_sequencer::{
+    account::{delegated_address, AccountCodec, KakarotAccount},
+    constants::{
+        storage_variables::{
+            ACCOUNT_EVM_ADDRESS, KAKAROT_BASE_FEE, KAKAROT_BLOB_BASE_FEE, KAKAROT_BLOCK_GAS_LIMIT,
+            KAKAROT_COINBASE, KAKAROT_EVM_TO_STARKNET_ADDRESS, KAKAROT_PREV_RANDAO,
         },
-        sequencer::KakarotSequencer,
-        utils::{split_u256, to_broadcasted_starknet_transaction},
+        ETH_FEE_TOKEN_ADDRESS, KAKAROT_ADDRESS, RELAYER_ADDRESS,
     },
-    starknet_storage,
+    sequencer::KakarotSequencer,
+    utils::{split_u256, to_broadcasted_starknet_transaction},
 };
 use alloy_primitives::Address;
 use alloy_primitives::Bytes;
@@ -28,12 +21,15 @@ use blockifier::{
         objects::{TransactionExecutionInfo, TransactionExecutionResult},
     },
 };
+use ef_tests::models::Account;
 use reth_primitives::TransactionSigned;
 use sequencer::{execution::Execution as _, transaction::BroadcastedTransactionWrapper};
 use starknet::core::types::BroadcastedTransaction;
 use starknet_api::abi::abi_utils::{get_fee_token_var_address, get_storage_var_address};
+use starknet_api::core::ContractAddress;
 use starknet_api::state::StorageKey;
 use starknet_crypto::Felt;
+use std::collections::{BTreeSet, HashSet};
 
 /// EVM state interface. Used to setup the evm state, EOA and contract accounts,
 /// fund them and get their state (balance, nonce, code, storage).
@@ -46,6 +42,7 @@ pub trait Evm {
         _base_fee: U256,
         _prev_randao: U256,
         _block_gas_limit: U256,
+        _blob_base_fee: U256,
     ) -> StateResult<()> {
         panic!("Not implemented, use features flag \"v0\" or \"v1\"")
     }
@@ -74,6 +71,36 @@ pub trait Evm {
         panic!("Not implemented, use features flag \"v0\" or \"v1\"")
     }
 
+    /// Returns the blob base fee read by the BLOBBASEFEE opcode (EIP-7516).
+    fn blob_base_fee(&mut self) -> StateResult<U256> {
+        panic!("Not implemented, use features flag \"v0\" or \"v1\"")
+    }
+
+    /// Returns the address the account delegates its code to (EIP-7702), read
+    /// from the delegation designator stored as the account's code.
+    fn delegation_at(&mut self, evm_address: &Address) -> StateResult<Option<Address>> {
+        Ok(delegated_address(&self.code_at(evm_address)?))
+    }
+
+    /// Returns true if the account holds non-zero EVM storage.
+    fn has_storage(&mut self, _evm_address: &Address) -> StateResult<bool> {
+        panic!("Not implemented, use features flag \"v0\" or \"v1\"")
+    }
+
+    /// Returns the number of storage entries of the account which cannot be
+    /// mapped back to one of the given EVM slots.
+    fn unmapped_storage(
+        &mut self,
+        _evm_address: &Address,
+        _slots: &BTreeSet<U256>,
+    ) -> StateResult<usize> {
+        panic!("Not implemented, use features flag \"v0\" or \"v1\"")
+    }
+
+    fn accounts(&mut self) -> StateResult<Vec<Address>> {
+        panic!("Not implemented, use features flag \"v0\" or \"v1\"")
+    }
+
     fn execute_transaction(
         &mut self,
         _transaction: TransactionSigned,
@@ -89,6 +116,7 @@ impl Evm for KakarotSequencer {
         base_fee: U256,
         prev_randao: U256,
         block_gas_limit: U256,
+        blob_base_fee: U256,
     ) -> StateResult<()> {
         let kakarot_address = self.environment.kakarot_address;
         let coinbase_address = Felt::from_bytes_be_slice(&self.address().0[..]);
@@ -126,6 +154,20 @@ impl Evm for KakarotSequencer {
             high_prev_randao.into(),
         )?;
 
+        // Set the blob base fee (EIP-4844), read by the BLOBBASEFEE opcode.
+        let [low_blob_base_fee, high_blob_base_fee] = split_u256(blob_base_fee);
+        let blob_base_fee_address = get_storage_var_address(KAKAROT_BLOB_BASE_FEE, &[]);
+        self.state_mut().set_storage_at(
+            kakarot_address,
+            blob_base_fee_address,
+            low_blob_base_fee.into(),
+        )?;
+        self.state_mut().set_storage_at(
+            kakarot_address,
+            blob_base_fee_address.next_storage_key()?,
+            high_blob_base_fee.into(),
+        )?;
+
         // Set the block gas limit, considering it fits in a felt.
         let [block_gas_limit, _] = split_u256(block_gas_limit);
         let block_gas_limit_address = get_storage_var_address(KAKAROT_BLOCK_GAS_LIMIT, &[]);
@@ -147,13 +189,10 @@ impl Evm for KakarotSequencer {
 
         self.state_mut().set_nonce(starknet_address, account.nonce);
 
-        storage.append(&mut vec![
-            starknet_storage!(
-                ACCOUNT_IMPLEMENTATION,
-                self.environment.account_contract_class_hash.0
-            ),
-            starknet_storage!(OWNABLE_OWNER, *self.environment.kakarot_address.0.key()),
-        ]);
+        storage.append(&mut self.account_codec().encode_contract(
+            self.environment.account_contract_class_hash.0,
+            *self.environment.kakarot_address.0.key(),
+        ));
 
         // Write all the storage vars to the sequencer state.
         for (k, v) in storage {
@@ -210,68 +249,29 @@ impl Evm for KakarotSequencer {
 
     /// Returns the storage value at the given key evm storage key.
     fn storage_at(&mut self, evm_address: &Address, key: U256) -> StateResult<U256> {
-        let keys = split_u256(key).map(Into::into);
-        let key_low = get_storage_var_address(ACCOUNT_STORAGE, &keys);
-        let key_high = key_low.next_storage_key()?;
-
         let starknet_address = self.compute_starknet_address(evm_address)?;
-
-        let low = self.state_mut().get_storage_at(starknet_address, key_low)?;
-        let high = self
-            .state_mut()
-            .get_storage_at(starknet_address, key_high)?;
-
-        let low = U256::from_be_bytes(low.to_bytes_be());
-        let high = U256::from_be_bytes(high.to_bytes_be());
-
-        Ok(high << 128 | low)
+        let codec = self.account_codec();
+        codec.decode_storage(key, |key| {
+            self.state_mut().get_storage_at(starknet_address, key)
+        })
     }
 
     /// Returns the nonce of the given address.
     /// Uses the Kakarot managed nonce stored in the contract account's storage.
     fn nonce_at(&mut self, evm_address: &Address) -> StateResult<U256> {
         let starknet_address = self.compute_starknet_address(evm_address)?;
-
-        let key = get_storage_var_address(ACCOUNT_NONCE, &[]);
-        let nonce = self.state_mut().get_storage_at(starknet_address, key)?;
-
-        Ok(U256::from_be_bytes(nonce.to_bytes_be()))
+        let codec = self.account_codec();
+        codec.decode_nonce(|key| self.state_mut().get_storage_at(starknet_address, key))
     }
 
     /// Returns the bytecode of the given address. For an EOA, the bytecode_len_ storage variable will return 0,
     /// and the function will return an empty vector. For a contract account, the function will return the bytecode
-    /// stored in the contract_account_bytecode storage variables. The function assumes that the bytecode is stored
-    /// in 31 byte big-endian chunks.
+    /// stored in the contract_account_bytecode storage variables, decoded with the layout of the version of
+    /// Kakarot. For an account delegating its code (EIP-7702), the function returns the delegation designator.
     fn code_at(&mut self, evm_address: &Address) -> StateResult<Bytes> {
-        // Get all storage addresses.
         let starknet_address = self.compute_starknet_address(evm_address)?;
-
-        let bytecode_len = self.state_mut().get_storage_at(
-            starknet_address,
-            get_storage_var_address(ACCOUNT_BYTECODE_LEN, &[]),
-        )?;
-        let bytecode_len: u64 = bytecode_len.to_biguint().try_into()?;
-
-        if bytecode_len == 0 {
-            return Ok(Bytes::default());
-        }
-
-        // Assumes that the bytecode is stored in 31 byte chunks.
-        let num_chunks = bytecode_len / 31;
-        let mut bytecode: Vec<u8> = Vec::with_capacity(bytecode_len as usize);
-
-        for chunk_index in 0..num_chunks {
-            let key = StorageKey::from(chunk_index);
-            let code = self.state_mut().get_storage_at(starknet_address, key)?;
-            bytecode.append(&mut felt_to_bytes(&code, 1).to_vec());
-        }
-
-        let remainder = bytecode_len % 31;
-        let key = StorageKey::from(num_chunks);
-        let code = self.state_mut().get_storage_at(starknet_address, key)?;
-        bytecode.append(&mut felt_to_bytes(&code, (32 - remainder) as usize).to_vec());
-
-        Ok(Bytes::from(bytecode))
+        let codec = self.account_codec();
+        codec.decode_code(|key| self.state_mut().get_storage_at(starknet_address, key))
     }
 
     /// Returns the balance of native tokens at the given address.
@@ -288,6 +288,99 @@ impl Evm for KakarotSequencer {
         Ok(high << 128 | low)
     }
 
+    /// Returns the blob base fee stored in Kakarot by `setup_state`.
+    fn blob_base_fee(&mut self) -> StateResult<U256> {
+        let kakarot_address = self.environment.kakarot_address;
+        let blob_base_fee_address = get_storage_var_address(KAKAROT_BLOB_BASE_FEE, &[]);
+        let low = self
+            .state_mut()
+            .get_storage_at(kakarot_address, blob_base_fee_address)?;
+        let high = self
+            .state_mut()
+            .get_storage_at(kakarot_address, blob_base_fee_address.next_storage_key()?)?;
+
+        let low = U256::from_be_bytes(low.to_bytes_be());
+        let high = U256::from_be_bytes(high.to_bytes_be());
+
+        Ok(high << 128 | low)
+    }
+
+    /// Returns true if the account holds non-zero EVM storage. The EVM slots
+    /// being hashed into the storage keys of the account contract, any non-zero
+    /// entry of the contract which is neither part of the encoding of the nonce
+    /// and code of the account nor one of its other storage variables is taken
+    /// as EVM storage.
+    fn has_storage(&mut self, evm_address: &Address) -> StateResult<bool> {
+        Ok(self.unmapped_storage(evm_address, &BTreeSet::new())? > 0)
+    }
+
+    /// Returns the number of non-zero storage entries of the account contract
+    /// which are neither an account field nor one of the limbs of the given EVM
+    /// slots. Kakarot stores the EVM storage under hashed keys, so the slots
+    /// written by a transaction can only be mapped back from known candidates.
+    fn unmapped_storage(
+        &mut self,
+        evm_address: &Address,
+        slots: &BTreeSet<U256>,
+    ) -> StateResult<usize> {
+        let starknet_address = self.compute_starknet_address(evm_address)?;
+        let account = Account {
+            nonce: self.nonce_at(evm_address)?,
+            code: self.code_at(evm_address)?,
+            storage: slots.iter().map(|slot| (*slot, U256::ZERO)).collect(),
+            ..Default::default()
+        };
+        let codec = self.account_codec();
+        let known_keys: HashSet<StorageKey> = codec
+            .encode(evm_address, &account)?
+            .into_iter()
+            .map(|(key, _)| key)
+            .chain(codec.contract_keys())
+            .collect();
+
+        Ok(self
+            .state()
+            .storage_of(starknet_address)
+            .filter(|(key, value)| **value != Felt::ZERO && !known_keys.contains(key))
+            .count())
+    }
+
+    /// Returns the EVM addresses of all the accounts known to Kakarot.
+    /// Accounts are found by iterating the deployed Starknet contracts with an account
+    /// class hash, and are kept only if registered in the Kakarot evm to starknet mapping.
+    fn accounts(&mut self) -> StateResult<Vec<Address>> {
+        let account_class_hashes = [
+            self.environment.account_contract_class_hash,
+            self.environment.base_account_class_hash,
+        ];
+        let deployed_accounts: Vec<ContractAddress> = self
+            .state()
+            .contracts()
+            .filter(|(_, class_hash)| account_class_hashes.contains(class_hash))
+            .map(|(address, _)| *address)
+            .collect();
+
+        let kakarot_address = self.environment.kakarot_address;
+        let mut accounts = Vec::with_capacity(deployed_accounts.len());
+        for starknet_address in deployed_accounts {
+            let evm_address = self.state_mut().get_storage_at(
+                starknet_address,
+                get_storage_var_address(ACCOUNT_EVM_ADDRESS, &[]),
+            )?;
+            let registered_address = self.state_mut().get_storage_at(
+                kakarot_address,
+                get_storage_var_address(KAKAROT_EVM_TO_STARKNET_ADDRESS, &[evm_address]),
+            )?;
+            if registered_address != *starknet_address.0.key() {
+                continue;
+            }
+            accounts.push(Address::from_slice(&evm_address.to_bytes_be()[12..]));
+        }
+        accounts.sort();
+
+        Ok(accounts)
+    }
+
     /// Converts the given signed transaction to a Starknet-rs transaction and executes it.
     // Since we are still missing the validate for the EOA, the signature is not added
     // to the transaction.
@@ -315,6 +408,9 @@ impl Evm for KakarotSequencer {
                     &transaction,
                     Felt::from(starknet_address),
                     relayer_nonce.0.into(),
+                    self.environment
+                        .version
+                        .expect("Kakarot version not defined, use features flag \"v0\" or \"v1\""),
                 )
                 .map_err(|err| {
                     TransactionExecutionError::ValidateTransactionError {
@@ -350,18 +446,20 @@ mod tests {
             },
             sequencer::{KakarotEnvironment, INITIAL_SEQUENCER_STATE},
         },
-        models::result::extract_output_and_log_execution_result,
+        models::{
+            exception::{ExpectedException, RejectionCategory},
+            result::extract_output_and_log_execution_result,
+        },
     };
-    use alloy_consensus::TxEip1559;
+    use alloy_consensus::{TxEip1559, TxEip4844};
     use alloy_eips::eip2930::AccessList;
     use alloy_primitives::Signature;
     use alloy_primitives::B256;
-    use ef_tests::models::Account;
     use reth_primitives::{sign_message, TransactionSigned};
+    use std::collections::BTreeMap;
+    use std::str::FromStr as _;
 
-    #[test]
-    fn test_execute_simple_contract() {
-        // Given
+    fn sequencer() -> KakarotSequencer {
         let kakarot_environment = KakarotEnvironment::new(
             *KAKAROT_ADDRESS,
             *UNINITIALIZED_ACCOUNT_CLASS_HASH,
@@ -369,14 +467,148 @@ mod tests {
             *ACCOUNT_CONTRACT_CLASS_HASH,
         );
         let coinbase_address = Address::left_padding_from(&0xC01BA5Eu64.to_be_bytes());
-        let mut sequencer = KakarotSequencer::new(
+        KakarotSequencer::new(
             INITIAL_SEQUENCER_STATE.clone(),
             kakarot_environment,
             coinbase_address,
             CHAIN_ID,
             0,
             1,
+        )
+    }
+
+    /// Returns a call to the test contract signed by the test EOA.
+    fn signed_transaction(
+        nonce: u64,
+        gas_limit: u64,
+        max_fee_per_gas: u128,
+        value: U256,
+    ) -> TransactionSigned {
+        let mut transaction = TransactionSigned {
+            hash: B256::default(),
+            signature: Signature::from_rs_and_parity(U256::ZERO, U256::ZERO, false).unwrap(),
+            transaction: reth_primitives::Transaction::Eip1559(TxEip1559 {
+                chain_id: CHAIN_ID,
+                nonce,
+                gas_limit,
+                max_fee_per_gas,
+                max_priority_fee_per_gas: 0,
+                to: alloy_primitives::TxKind::Call(TEST_CONTRACT_ADDRESS),
+                value,
+                access_list: AccessList::default(),
+                input: Bytes::default(),
+            }),
+        };
+        transaction.signature =
+            sign_message(PRIVATE_KEY, transaction.transaction.signature_hash()).unwrap();
+        transaction
+    }
+
+    /// Executes the transaction from an unfunded test EOA with a zero nonce, at
+    /// the given base fee, and returns the rejection category of Kakarot.
+    fn rejection_category(
+        base_fee: U256,
+        transaction: TransactionSigned,
+    ) -> Option<RejectionCategory> {
+        let mut sequencer = sequencer();
+        sequencer
+            .setup_state(base_fee, U256::ZERO, U256::from(30_000_000), U256::ZERO)
+            .unwrap();
+        let contract = KakarotAccount::new(
+            &TEST_CONTRACT_ADDRESS,
+            Account {
+                code: Bytes::from(vec![0x00]), // STOP
+                nonce: U256::from(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let eoa = KakarotAccount::new(&PUBLIC_KEY, Account::default()).unwrap();
+        sequencer.setup_account(contract).unwrap();
+        sequencer.setup_account(eoa).unwrap();
+
+        let execution_result = sequencer.execute_transaction(transaction);
+        RejectionCategory::from_execution_result(&execution_result)
+    }
+
+    /// Asserts that the exception is mapped to the rejection category of Kakarot.
+    fn assert_exception_matches(exception: &str, category: Option<RejectionCategory>) {
+        let expected = ExpectedException::from_str(exception).unwrap();
+        let category = category.expect("transaction should be rejected");
+        assert!(
+            expected.matches(category),
+            "{exception} expected {:?}, got {category}",
+            expected.categories()
         );
+    }
+
+    #[test]
+    fn test_rejection_category_accepted() {
+        // Given
+        let transaction = signed_transaction(0, 1_000_000, 0, U256::ZERO);
+
+        // When
+        let category = rejection_category(U256::ZERO, transaction);
+
+        // Then
+        assert_eq!(category, None);
+    }
+
+    #[test]
+    fn test_rejection_category_nonce_mismatch() {
+        // Given
+        let transaction = signed_transaction(1, 1_000_000, 0, U256::ZERO);
+
+        // When
+        let category = rejection_category(U256::ZERO, transaction);
+
+        // Then
+        assert_exception_matches("TransactionException.NONCE_MISMATCH_TOO_HIGH", category);
+    }
+
+    #[test]
+    fn test_rejection_category_intrinsic_gas_too_low() {
+        // Given
+        let transaction = signed_transaction(0, 20_000, 0, U256::ZERO);
+
+        // When
+        let category = rejection_category(U256::ZERO, transaction);
+
+        // Then
+        assert_exception_matches("TransactionException.INTRINSIC_GAS_TOO_LOW", category);
+    }
+
+    #[test]
+    fn test_rejection_category_insufficient_account_funds() {
+        // Given
+        let transaction = signed_transaction(0, 1_000_000, 0, U256::from(1));
+
+        // When
+        let category = rejection_category(U256::ZERO, transaction);
+
+        // Then
+        assert_exception_matches("TransactionException.INSUFFICIENT_ACCOUNT_FUNDS", category);
+    }
+
+    #[test]
+    fn test_rejection_category_insufficient_max_fee_per_gas() {
+        // Given
+        let transaction = signed_transaction(0, 1_000_000, 1, U256::ZERO);
+
+        // When
+        let category = rejection_category(U256::from(10), transaction);
+
+        // Then
+        assert_exception_matches(
+            "TransactionException.INSUFFICIENT_MAX_FEE_PER_GAS",
+            category,
+        );
+    }
+
+    #[test]
+    fn test_execute_simple_contract() {
+        // Given
+        let mut sequencer = sequencer();
 
         let mut transaction = TransactionSigned {
             hash: B256::default(),
@@ -438,5 +670,128 @@ mod tests {
             .unwrap();
 
         assert_eq!(storage, U256::from(1_u64));
+        assert!(sequencer.has_storage(&TEST_CONTRACT_ADDRESS).unwrap());
+        assert!(!sequencer.has_storage(&PUBLIC_KEY).unwrap());
+    }
+
+    #[test]
+    fn test_execute_blob_opcodes() {
+        // Given
+        let mut sequencer = sequencer();
+        let blob_base_fee = U256::from(7);
+        sequencer
+            .setup_state(
+                U256::ZERO,
+                U256::ZERO,
+                U256::from(30_000_000),
+                blob_base_fee,
+            )
+            .unwrap();
+
+        // Versioned hashes start with the KZG version byte.
+        let mut versioned_hash = B256::with_last_byte(0x2a);
+        versioned_hash.0[0] = 0x01;
+        let mut transaction = TransactionSigned {
+            hash: B256::default(),
+            signature: Signature::from_rs_and_parity(U256::ZERO, U256::ZERO, false).unwrap(),
+            transaction: reth_primitives::Transaction::Eip4844(TxEip4844 {
+                chain_id: CHAIN_ID,
+                nonce: 0,
+                gas_limit: 1_000_000,
+                max_fee_per_gas: 0,
+                max_priority_fee_per_gas: 0,
+                to: TEST_CONTRACT_ADDRESS,
+                value: U256::ZERO,
+                access_list: AccessList::default(),
+                blob_versioned_hashes: vec![versioned_hash],
+                max_fee_per_blob_gas: 7,
+                input: Bytes::default(),
+            }),
+        };
+        transaction.signature =
+            sign_message(PRIVATE_KEY, transaction.transaction.signature_hash()).unwrap();
+        // PUSH1 00 BLOBHASH PUSH1 00 SSTORE BLOBBASEFEE PUSH1 01 SSTORE
+        let contract_bytecode = Bytes::from(vec![
+            0x60, 0x00, 0x49, 0x60, 0x00, 0x55, 0x4a, 0x60, 0x01, 0x55,
+        ]);
+
+        // When
+        let contract = KakarotAccount::new(
+            &TEST_CONTRACT_ADDRESS,
+            Account {
+                code: contract_bytecode,
+                nonce: U256::from(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let eoa = KakarotAccount::new(&PUBLIC_KEY, Account::default()).unwrap();
+        sequencer.setup_account(contract).unwrap();
+        sequencer.setup_account(eoa).unwrap();
+        let execution_result = sequencer.execute_transaction(transaction);
+        let tx_output = extract_output_and_log_execution_result(
+            &execution_result,
+            "test_case",
+            "test_category",
+        )
+        .unwrap_or_default();
+
+        // Then
+        assert!(tx_output.success);
+        let blob_hash = sequencer
+            .storage_at(&TEST_CONTRACT_ADDRESS, U256::ZERO)
+            .unwrap();
+        let blob_base_fee_read = sequencer
+            .storage_at(&TEST_CONTRACT_ADDRESS, U256::from(1))
+            .unwrap();
+        assert_eq!(blob_hash, U256::from_be_bytes(versioned_hash.0));
+        assert_eq!(blob_base_fee_read, blob_base_fee);
+        assert_eq!(sequencer.blob_base_fee().unwrap(), blob_base_fee);
+    }
+
+    #[test]
+    fn test_has_storage() {
+        // Given
+        let mut sequencer = sequencer();
+        // JUMPDEST PUSH1 00 JUMP
+        let code = Bytes::from(vec![0x5b, 0x60, 0x00, 0x56]);
+        // A slot derived from a keccak hash, as for mappings
+        let slot = U256::from_be_bytes(alloy_primitives::keccak256([0u8; 64]).0);
+        let without_storage = Address::left_padding_from(&[0xaa]);
+        let with_storage = Address::left_padding_from(&[0xbb]);
+        let with_zero_storage = Address::left_padding_from(&[0xcc]);
+        let account = |storage: BTreeMap<U256, U256>| Account {
+            nonce: U256::from(1),
+            code: code.clone(),
+            storage,
+            ..Default::default()
+        };
+
+        // When
+        for (address, storage) in [
+            (without_storage, BTreeMap::new()),
+            (with_storage, BTreeMap::from([(slot, U256::from(42))])),
+            (with_zero_storage, BTreeMap::from([(slot, U256::ZERO)])),
+        ] {
+            let account = KakarotAccount::new(&address, account(storage)).unwrap();
+            sequencer.setup_account(account).unwrap();
+        }
+
+        // Then
+        assert!(!sequencer.has_storage(&without_storage).unwrap());
+        assert!(sequencer.has_storage(&with_storage).unwrap());
+        assert!(!sequencer.has_storage(&with_zero_storage).unwrap());
+        assert_eq!(
+            sequencer
+                .unmapped_storage(&with_storage, &BTreeSet::from([slot]))
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            sequencer
+                .unmapped_storage(&with_storage, &BTreeSet::from([U256::ZERO]))
+                .unwrap(),
+            1
+        );
     }
 }
@@ -3,17 +3,18 @@ use starknet::core::types::Felt;
 use std::ops::{Deref, DerefMut};
 
 use crate::evm_sequencer::{
+    account::AccountCodec,
     constants::{
         storage_variables::{
             ACCOUNT_PUBLIC_KEY, ERC20_BALANCES, KAKAROT_ACCOUNT_CONTRACT_CLASS_HASH,
-            KAKAROT_BLOCK_GAS_LIMIT, KAKAROT_CHAIN_ID, KAKAROT_NATIVE_TOKEN_ADDRESS,
-            KAKAROT_UNINITIALIZED_ACCOUNT_CLASS_HASH, OWNABLE_OWNER,
+            KAKAROT_BLOCK_GAS_LIMIT, KAKAROT_CAIRO1_HELPERS_CLASS_HASH, KAKAROT_CHAIN_ID,
+            KAKAROT_NATIVE_TOKEN_ADDRESS, KAKAROT_UNINITIALIZED_ACCOUNT_CLASS_HASH, OWNABLE_OWNER,
         },
-        ACCOUNT_CONTRACT_CLASS, ACCOUNT_CONTRACT_CLASS_HASH, BLOCK_GAS_LIMIT, CHAIN_ID,
+        v0, v1, BLOCK_GAS_LIMIT, CAIRO1_HELPERS_CLASS, CAIRO1_HELPERS_CLASS_HASH, CHAIN_ID,
         ETH_FEE_TOKEN_ADDRESS, FEE_TOKEN_CLASS, FEE_TOKEN_CLASS_HASH, KAKAROT_ADDRESS,
-        KAKAROT_CLASS, KAKAROT_CLASS_HASH, KAKAROT_OWNER_ADDRESS, OPENZEPPELIN_ACCOUNT_CLASS,
+        KAKAROT_OWNER_ADDRESS, KAKAROT_VERSION, OPENZEPPELIN_ACCOUNT_CLASS,
         OPENZEPPELIN_ACCOUNT_CLASS_HASH, RELAYER_ADDRESS, RELAYER_BALANCE, RELAYER_VERIFYING_KEY,
-        STRK_FEE_TOKEN_ADDRESS, UNINITIALIZED_ACCOUNT_CLASS, UNINITIALIZED_ACCOUNT_CLASS_HASH,
+        STRK_FEE_TOKEN_ADDRESS,
     },
     types::contract_class::CasmContractClassWrapper,
     utils::compute_starknet_address,
@@ -26,6 +27,7 @@ use blockifier::{
     execution::contract_class::{CompiledClassV0, CompiledClassV1, RunnableCompiledClass},
     state::state_api::StateResult,
 };
+use build_utils::version::KakarotVersion;
 use cairo_lang_starknet_classes::casm_contract_class::CasmContractClass;
 use cairo_vm::types::errors::program_errors::ProgramError;
 use sequencer::{sequencer::Sequencer, state::State};
@@ -36,11 +38,6 @@ use starknet_api::{
     core::{ChainId, ClassHash, ContractAddress},
 };
 
-#[cfg(feature = "v0")]
-use crate::evm_sequencer::constants::{
-    storage_variables::KAKAROT_CAIRO1_HELPERS_CLASS_HASH, CAIRO1_HELPERS_CLASS,
-    CAIRO1_HELPERS_CLASS_HASH,
-};
 #[allow(unused_imports)]
 use blockifier::state::state_api::{
     State as BlockifierState, StateReader as BlockifierStateReader,
@@ -67,6 +64,8 @@ pub struct KakarotEnvironment {
     /// The class hash of the cairo1 helpers class.
     pub(crate) cairo1_helpers_class_hash: ClassHash,
     pub(crate) account_contract_class_hash: ClassHash,
+    /// The version of Kakarot, which defines the encoding of the transactions.
+    pub(crate) version: Option<KakarotVersion>,
 }
 
 impl KakarotEnvironment {
@@ -81,6 +80,23 @@ impl KakarotEnvironment {
             base_account_class_hash,
             cairo1_helpers_class_hash,
             account_contract_class_hash,
+            version: KAKAROT_VERSION,
+        }
+    }
+
+    /// Returns the environment of the given version of Kakarot, independently of
+    /// the features.
+    pub fn for_version(version: KakarotVersion) -> Self {
+        let [_, account_contract_class_hash, uninitialized_account_class_hash] =
+            kakarot_class_hashes(version);
+        Self {
+            version: Some(version),
+            ..Self::new(
+                *KAKAROT_ADDRESS,
+                uninitialized_account_class_hash,
+                *CAIRO1_HELPERS_CLASS_HASH,
+                account_contract_class_hash,
+            )
         }
     }
 }
@@ -94,50 +110,38 @@ impl KakarotSequencer {
         block_number: u64,
         block_timestamp: u64,
     ) -> Self {
-        let coinbase_constructor_args = [
-            Felt::ONE,
-            Felt::from_bytes_be_slice(&coinbase_address.0[..]),
-        ];
-
-        let block_info = BlockInfo {
-            block_number: BlockNumber(block_number),
-            block_timestamp: BlockTimestamp(block_timestamp),
-            sequencer_address: compute_starknet_address(
-                &coinbase_address,
-                environment.base_account_class_hash.0,
-                &coinbase_constructor_args,
-            )
-            .try_into()
-            .expect("Failed to convert to ContractAddress"),
-            gas_prices: Default::default(),
-            use_kzg_da: false,
-        };
-
-        let chain_info = ChainInfo {
-            chain_id: ChainId::Other(String::from_utf8(chain_id.to_be_bytes().to_vec()).unwrap()),
-            fee_token_addresses: FeeTokenAddresses {
-                eth_fee_token_address: *ETH_FEE_TOKEN_ADDRESS,
-                strk_fee_token_address: *STRK_FEE_TOKEN_ADDRESS,
-            },
-        };
-
-        let versioned_constants: VersionedConstants =
-            serde_json::from_str(include_str!("./resources/versioned_constants.json"))
-                .expect("failed to parse versioned constants");
-
-        let block_context = BlockContext::new(
-            block_info,
-            chain_info,
-            versioned_constants,
-            BouncerConfig::max(),
+        let block_context = block_context(
+            &environment,
+            coinbase_address,
+            chain_id,
+            block_number,
+            block_timestamp,
         );
-
         Self {
             sequencer: Sequencer::new(block_context, initial_state, coinbase_address),
             environment,
         }
     }
 
+    /// Starts a new block with the given coinbase, number and timestamp. The
+    /// state is kept, so that the block is executed on top of the previous ones.
+    pub fn start_block(
+        &mut self,
+        coinbase_address: Address,
+        block_number: u64,
+        block_timestamp: u64,
+    ) {
+        let block_context = block_context(
+            &self.environment,
+            coinbase_address,
+            self.chain_id(),
+            block_number,
+            block_timestamp,
+        );
+        self.sequencer
+            .set_block_context(block_context, coinbase_address);
+    }
+
     pub fn chain_id(&self) -> u64 {
         // Safety: chain_id is always 8 bytes.
         let chain_id = self.block_context().chain_info().chain_id.to_string();
@@ -155,6 +159,59 @@ impl KakarotSequencer {
         )
         .try_into()?)
     }
+
+    /// Returns the codec of the accounts of the sequencer. All the versions of
+    /// Kakarot share the storage layout of their accounts.
+    pub const fn account_codec(&self) -> AccountCodec {
+        AccountCodec
+    }
+}
+
+/// Returns the context of a block with the given coinbase, number and timestamp.
+fn block_context(
+    environment: &KakarotEnvironment,
+    coinbase_address: Address,
+    chain_id: u64,
+    block_number: u64,
+    block_timestamp: u64,
+) -> BlockContext {
+    let coinbase_constructor_args = [
+        Felt::ONE,
+        Felt::from_bytes_be_slice(&coinbase_address.0[..]),
+    ];
+
+    let block_info = BlockInfo {
+        block_number: BlockNumber(block_number),
+        block_timestamp: BlockTimestamp(block_timestamp),
+        sequencer_address: compute_starknet_address(
+            &coinbase_address,
+            environment.base_account_class_hash.0,
+            &coinbase_constructor_args,
+        )
+        .try_into()
+        .expect("Failed to convert to ContractAddress"),
+        gas_prices: Default::default(),
+        use_kzg_da: false,
+    };
+
+    let chain_info = ChainInfo {
+        chain_id: ChainId::Other(String::from_utf8(chain_id.to_be_bytes().to_vec()).unwrap()),
+        fee_token_addresses: FeeTokenAddresses {
+            eth_fee_token_address: *ETH_FEE_TOKEN_ADDRESS,
+            strk_fee_token_address: *STRK_FEE_TOKEN_ADDRESS,
+        },
+    };
+
+    let versioned_constants: VersionedConstants =
+        serde_json::from_str(include_str!("./resources/versioned_constants.json"))
+            .expect("failed to parse versioned constants");
+
+    BlockContext::new(
+        block_info,
+        chain_info,
+        versioned_constants,
+        BouncerConfig::max(),
+    )
 }
 
 impl Deref for KakarotSequencer {
@@ -189,95 +246,179 @@ pub fn convert_contract_class_v1(
 }
 
 lazy_static! {
-    pub static ref INITIAL_SEQUENCER_STATE: SequencerState = {
-        let mut state = SequencerState::default();
-
-
-        #[allow(unused_mut)]
-        let mut storage = vec![
-                (OWNABLE_OWNER, *KAKAROT_OWNER_ADDRESS.0.key()),
-                (KAKAROT_NATIVE_TOKEN_ADDRESS, *ETH_FEE_TOKEN_ADDRESS.0.key()),
-                (KAKAROT_ACCOUNT_CONTRACT_CLASS_HASH, ACCOUNT_CONTRACT_CLASS_HASH.0),
-                (KAKAROT_BLOCK_GAS_LIMIT, Felt::from(BLOCK_GAS_LIMIT)),
-                (KAKAROT_UNINITIALIZED_ACCOUNT_CLASS_HASH, UNINITIALIZED_ACCOUNT_CLASS_HASH.0),
-                (KAKAROT_CHAIN_ID, Felt::from(CHAIN_ID)),
-        ];
-
-        // On KakarotZero we rely on the Cairo1 helpers class for unavailable syscalls and precompiles.
-        #[cfg(feature = "v0")]
-        storage.push((KAKAROT_CAIRO1_HELPERS_CLASS_HASH, CAIRO1_HELPERS_CLASS_HASH.0));
-
-        // Write all the storage vars to the sequencer state.
-        for (k, v) in storage {
-            (&mut state).set_storage_at(*KAKAROT_ADDRESS, get_storage_var_address(k, &[]), v).expect("failed to set storage");
+    pub static ref INITIAL_SEQUENCER_STATE: SequencerState = initial_sequencer_state(
+        KAKAROT_VERSION.expect("Kakarot version not defined, use features flag \"v0\" or \"v1\"")
+    )
+    .clone();
+    static ref INITIAL_SEQUENCER_STATE_V0: SequencerState =
+        build_initial_sequencer_state(KakarotVersion::V0);
+    static ref INITIAL_SEQUENCER_STATE_V1: SequencerState =
+        build_initial_sequencer_state(KakarotVersion::V1);
+    static ref INITIAL_SEQUENCER_STATE_NATIVE: SequencerState =
+        build_initial_sequencer_state(KakarotVersion::Native);
+}
+
+/// Returns the initial state of the sequencer for the given version of Kakarot,
+/// independently of the features.
+pub fn initial_sequencer_state(version: KakarotVersion) -> &'static SequencerState {
+    match version {
+        KakarotVersion::V0 => &INITIAL_SEQUENCER_STATE_V0,
+        KakarotVersion::V1 => &INITIAL_SEQUENCER_STATE_V1,
+        KakarotVersion::Native => &INITIAL_SEQUENCER_STATE_NATIVE,
+    }
+}
+
+/// Returns the class hashes of the Kakarot, account contract and uninitialized
+/// account classes of the given version.
+fn kakarot_class_hashes(version: KakarotVersion) -> [ClassHash; 3] {
+    match version {
+        KakarotVersion::V0 => [
+            *v0::KAKAROT_CLASS_HASH,
+            *v0::ACCOUNT_CONTRACT_CLASS_HASH,
+            *v0::UNINITIALIZED_ACCOUNT_CLASS_HASH,
+        ],
+        KakarotVersion::V1 | KakarotVersion::Native => [
+            *v1::KAKAROT_CLASS_HASH,
+            *v1::ACCOUNT_CONTRACT_CLASS_HASH,
+            *v1::UNINITIALIZED_ACCOUNT_CLASS_HASH,
+        ],
+    }
+}
+
+/// Converts the Kakarot, account contract and uninitialized account classes of
+/// the given version, using the util corresponding to the Cairo version they're
+/// compiled with.
+fn kakarot_classes(version: KakarotVersion) -> [RunnableCompiledClass; 3] {
+    match version {
+        KakarotVersion::V0 => [
+            convert_contract_class_v0(&v0::KAKAROT_CLASS).expect("failed to convert kakarot class"),
+            convert_contract_class_v0(&v0::ACCOUNT_CONTRACT_CLASS)
+                .expect("failed to convert account class"),
+            convert_contract_class_v0(&v0::UNINITIALIZED_ACCOUNT_CLASS)
+                .expect("failed to convert uninitialized class"),
+        ],
+        KakarotVersion::V1 => [
+            convert_contract_class_v1(&v1::KAKAROT_CLASS).expect("failed to convert kakarot class"),
+            convert_contract_class_v1(&v1::ACCOUNT_CONTRACT_CLASS)
+                .expect("failed to convert account class"),
+            convert_contract_class_v1(&v1::UNINITIALIZED_ACCOUNT_CLASS)
+                .expect("failed to convert uninitialized class"),
+        ],
+        #[cfg(feature = "native")]
+        KakarotVersion::Native => {
+            use sequencer::native::class_from_json_str;
+            kakarot_class_hashes(version).map(|class_hash| {
+                let json_class = v1::CLASS_HASH_TO_JSON_CLASS.get(&class_hash).unwrap();
+                class_from_json_str(json_class, class_hash).unwrap_or_else(|err| panic!("{}", err))
+            })
         }
+        #[cfg(not(feature = "native"))]
+        KakarotVersion::Native => panic!("Kakarot native requires the \"native\" feature"),
+    }
+}
 
-        // Convert the contract classes to using the util corresponding to the Cairo version it's compiled with.
-        let (converted_kakarot_class, converted_account_class, converted_uninitialized_class) = {
-            #[cfg(not(feature = "v1"))]
-            {
-                // Default to Cairo 0
-                (
-                    convert_contract_class_v0(&KAKAROT_CLASS).expect("failed to convert kakarot class"),
-                    convert_contract_class_v0(&ACCOUNT_CONTRACT_CLASS).expect("failed to convert account class"),
-                    convert_contract_class_v0(&UNINITIALIZED_ACCOUNT_CLASS).expect("failed to convert uninitialized class")
-                )
-            }
-
-            #[cfg(feature = "v1")]
-            {
-                #[cfg(feature = "native")]
-                {
-                    use sequencer::native::class_from_json_str;
-                    use crate::evm_sequencer::constants::CLASS_HASH_TO_JSON_CLASS;
-                    let kakarot_json = CLASS_HASH_TO_JSON_CLASS.get(&KAKAROT_CLASS_HASH).unwrap();
-                    let account_json = CLASS_HASH_TO_JSON_CLASS.get(&ACCOUNT_CONTRACT_CLASS_HASH).unwrap();
-                    let uninitialized_json = CLASS_HASH_TO_JSON_CLASS.get(&UNINITIALIZED_ACCOUNT_CLASS_HASH).unwrap();
-                    let account_class = class_from_json_str(account_json, *ACCOUNT_CONTRACT_CLASS_HASH).unwrap_or_else(|err| panic!("{}", err));
-                    let uninitialized_class = class_from_json_str(uninitialized_json, *UNINITIALIZED_ACCOUNT_CLASS_HASH).unwrap_or_else(|err| panic!("{}", err));
-                    let kakarot_class = class_from_json_str(kakarot_json, *KAKAROT_CLASS_HASH).unwrap_or_else(|err| panic!("{}", err));
-                    (kakarot_class, account_class, uninitialized_class)
-                }
-                #[cfg(not(feature = "native"))]
-                {
-                (
-                    convert_contract_class_v1(&KAKAROT_CLASS).expect("failed to convert kakarot class"),
-                    convert_contract_class_v1(&ACCOUNT_CONTRACT_CLASS).expect("failed to convert account class"),
-                    convert_contract_class_v1(&UNINITIALIZED_ACCOUNT_CLASS).expect("failed to convert uninitialized class")
-                )
-                }
-            }
-        };
-
-        // Write the kakarot class and class hash.
-        (&mut state).set_class_hash_at(*KAKAROT_ADDRESS, *KAKAROT_CLASS_HASH).expect("failed to set kakarot class hash");
-        (&mut state)
-            .set_contract_class(*KAKAROT_CLASS_HASH, converted_kakarot_class).expect("failed to set kakarot contract class");
+fn build_initial_sequencer_state(version: KakarotVersion) -> SequencerState {
+    let mut state = SequencerState::default();
+    let [kakarot_class_hash, account_contract_class_hash, uninitialized_account_class_hash] =
+        kakarot_class_hashes(version);
+
+    let mut storage = vec![
+        (OWNABLE_OWNER, *KAKAROT_OWNER_ADDRESS.0.key()),
+        (KAKAROT_NATIVE_TOKEN_ADDRESS, *ETH_FEE_TOKEN_ADDRESS.0.key()),
+        (
+            KAKAROT_ACCOUNT_CONTRACT_CLASS_HASH,
+            account_contract_class_hash.0,
+        ),
+        (KAKAROT_BLOCK_GAS_LIMIT, Felt::from(BLOCK_GAS_LIMIT)),
+        (
+            KAKAROT_UNINITIALIZED_ACCOUNT_CLASS_HASH,
+            uninitialized_account_class_hash.0,
+        ),
+        (KAKAROT_CHAIN_ID, Felt::from(CHAIN_ID)),
+    ];
+
+    // On KakarotZero we rely on the Cairo1 helpers class for unavailable syscalls and precompiles.
+    if version == KakarotVersion::V0 {
+        storage.push((
+            KAKAROT_CAIRO1_HELPERS_CLASS_HASH,
+            CAIRO1_HELPERS_CLASS_HASH.0,
+        ));
+    }
 
-        // Write contract account, uninitialized_account and erc20 classes and class hashes.
-        (&mut state).set_contract_class(
-            *ACCOUNT_CONTRACT_CLASS_HASH,
-            converted_account_class).expect("failed to set contract account class");
+    // Write all the storage vars to the sequencer state.
+    for (k, v) in storage {
         (&mut state)
-            .set_contract_class(*UNINITIALIZED_ACCOUNT_CLASS_HASH, converted_uninitialized_class).expect("failed to set eoa contract class");
+            .set_storage_at(*KAKAROT_ADDRESS, get_storage_var_address(k, &[]), v)
+            .expect("failed to set storage");
+    }
+
+    let [converted_kakarot_class, converted_account_class, converted_uninitialized_class] =
+        kakarot_classes(version);
+
+    // Write the kakarot class and class hash.
+    (&mut state)
+        .set_class_hash_at(*KAKAROT_ADDRESS, kakarot_class_hash)
+        .expect("failed to set kakarot class hash");
+    (&mut state)
+        .set_contract_class(kakarot_class_hash, converted_kakarot_class)
+        .expect("failed to set kakarot contract class");
+
+    // Write contract account, uninitialized_account and erc20 classes and class hashes.
+    (&mut state)
+        .set_contract_class(account_contract_class_hash, converted_account_class)
+        .expect("failed to set contract account class");
+    (&mut state)
+        .set_contract_class(
+            uninitialized_account_class_hash,
+            converted_uninitialized_class,
+        )
+        .expect("failed to set eoa contract class");
 
-        (&mut state).set_contract_class(
+    (&mut state)
+        .set_contract_class(
             *FEE_TOKEN_CLASS_HASH,
-            convert_contract_class_v0(&FEE_TOKEN_CLASS).expect("failed to convert FEE TOKEN CLASS to contract class"),
-        ).expect("failed to set sequencer contract class");
-        (&mut state).set_class_hash_at(*ETH_FEE_TOKEN_ADDRESS, *FEE_TOKEN_CLASS_HASH).expect("failed to set fee token class hash");
+            convert_contract_class_v0(&FEE_TOKEN_CLASS)
+                .expect("failed to convert FEE TOKEN CLASS to contract class"),
+        )
+        .expect("failed to set sequencer contract class");
+    (&mut state)
+        .set_class_hash_at(*ETH_FEE_TOKEN_ADDRESS, *FEE_TOKEN_CLASS_HASH)
+        .expect("failed to set fee token class hash");
 
-        #[cfg(feature = "v0")]
-        (&mut state).set_contract_class(*CAIRO1_HELPERS_CLASS_HASH, convert_contract_class_v1(&CAIRO1_HELPERS_CLASS).expect("failed to convert CAIRO1_HELPERS Class to contract class")).expect("failed to set cairo1_helpers contract class");
+    if version == KakarotVersion::V0 {
+        (&mut state)
+            .set_contract_class(
+                *CAIRO1_HELPERS_CLASS_HASH,
+                convert_contract_class_v1(&CAIRO1_HELPERS_CLASS)
+                    .expect("failed to convert CAIRO1_HELPERS Class to contract class"),
+            )
+            .expect("failed to set cairo1_helpers contract class");
+    }
 
-        (&mut state).set_contract_class(
+    (&mut state)
+        .set_contract_class(
             *OPENZEPPELIN_ACCOUNT_CLASS_HASH,
-            convert_contract_class_v0(&OPENZEPPELIN_ACCOUNT_CLASS).expect("failed to convert OPENZEPPELIN ACCOUNT CLASS to contract class")
-        ).expect("failed to set openzeppelin account contract class");
-        (&mut state).set_class_hash_at(*RELAYER_ADDRESS, *OPENZEPPELIN_ACCOUNT_CLASS_HASH).expect("failed to set relayer class hash");
-        (&mut state).set_storage_at(*RELAYER_ADDRESS, get_storage_var_address(ACCOUNT_PUBLIC_KEY, &[]), RELAYER_VERIFYING_KEY.scalar()).expect("failed to set relayer public key");
-        (&mut state).set_storage_at(*ETH_FEE_TOKEN_ADDRESS, get_storage_var_address(ERC20_BALANCES, &[*RELAYER_ADDRESS.0.key()]), RELAYER_BALANCE).expect("failed to set relayer balance");
+            convert_contract_class_v0(&OPENZEPPELIN_ACCOUNT_CLASS)
+                .expect("failed to convert OPENZEPPELIN ACCOUNT CLASS to contract class"),
+        )
+        .expect("failed to set openzeppelin account contract class");
+    (&mut state)
+        .set_class_hash_at(*RELAYER_ADDRESS, *OPENZEPPELIN_ACCOUNT_CLASS_HASH)
+        .expect("failed to set relayer class hash");
+    (&mut state)
+        .set_storage_at(
+            *RELAYER_ADDRESS,
+            get_storage_var_address(ACCOUNT_PUBLIC_KEY, &[]),
+            RELAYER_VERIFYING_KEY.scalar(),
+        )
+        .expect("failed to set relayer public key");
+    (&mut state)
+        .set_storage_at(
+            *ETH_FEE_TOKEN_ADDRESS,
+            get_storage_var_address(ERC20_BALANCES, &[*RELAYER_ADDRESS.0.key()]),
+            RELAYER_BALANCE,
+        )
+        .expect("failed to set relayer balance");
 
-        state
-    };
+    state
 }
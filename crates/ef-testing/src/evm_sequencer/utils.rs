@@ -2,6 +2,7 @@ use super::constants::KAKAROT_ADDRESS;
 use crate::evm_sequencer::constants::RELAYER_ADDRESS;
 use alloy_consensus::transaction::Transaction;
 use alloy_primitives::{Address, Bytes, U256};
+use build_utils::version::KakarotVersion;
 use bytes::BytesMut;
 use reth_primitives::{TransactionSigned, TxType};
 use starknet::core::{
@@ -37,21 +38,22 @@ pub fn felt_to_bytes(felt: &Felt, start: usize) -> Bytes {
     felt.to_bytes_be()[start..].to_vec().into()
 }
 
-/// Converts an signed transaction and a signature to a Starknet-rs transaction.
-#[allow(unused_variables)] // necessary for starknet_address which is behind a flag
+/// Converts an signed transaction and a signature to a Starknet-rs transaction,
+/// encoded for the given version of Kakarot.
 pub fn to_broadcasted_starknet_transaction(
     transaction: &TransactionSigned,
     starknet_address: Felt,
     relayer_nonce: Option<Felt>,
+    version: KakarotVersion,
 ) -> Result<BroadcastedInvokeTransaction, eyre::Error> {
+    // Blob transactions (EIP-4844) are encoded without their sidecar: the
+    // versioned hashes, read by BLOBHASH, are part of the encoded transaction.
     let mut bytes = BytesMut::new();
     transaction.transaction.encode_without_signature(&mut bytes);
 
-    #[allow(unused_mut)]
-    let mut calldata: Vec<Felt> = {
+    let mut calldata: Vec<Felt> = match version {
         // Pack the calldata in 31-byte chunks.
-        #[cfg(feature = "v0")]
-        {
+        KakarotVersion::V0 => {
             use crate::evm_sequencer::account::pack_byte_array_to_starkfelt_array;
             std::iter::once((bytes.len()).into())
                 .chain(pack_byte_array_to_starkfelt_array(
@@ -59,10 +61,7 @@ pub fn to_broadcasted_starknet_transaction(
                 ))
                 .collect()
         }
-        #[cfg(not(feature = "v0"))]
-        {
-            bytes.into_iter().map(Felt::from).collect()
-        }
+        KakarotVersion::V1 | KakarotVersion::Native => bytes.into_iter().map(Felt::from).collect(),
     };
 
     let signature = transaction.signature();
@@ -84,8 +83,8 @@ pub fn to_broadcasted_starknet_transaction(
         v.into(),
     ];
 
-    let mut execute_from_outside_calldata = if cfg!(feature = "v0") {
-        vec![
+    let mut execute_from_outside_calldata = match version {
+        KakarotVersion::V0 => vec![
             *RELAYER_ADDRESS.0.key(),           // OutsideExecution caller
             Felt::ZERO,                         // OutsideExecution nonce
             Felt::ZERO,                         // OutsideExecution execute_after
@@ -96,9 +95,8 @@ pub fn to_broadcasted_starknet_transaction(
             Felt::ZERO,                         // CallArray data_offset
             calldata.len().into(),              // CallArray data_len
             calldata.len().into(),              // calldata_len
-        ]
-    } else if cfg!(feature = "v1") {
-        vec![
+        ],
+        KakarotVersion::V1 | KakarotVersion::Native => vec![
             *RELAYER_ADDRESS.0.key(),           // OutsideExecution caller
             Felt::ZERO,                         // OutsideExecution nonce
             Felt::ZERO,                         // OutsideExecution execute_after
@@ -107,9 +105,7 @@ pub fn to_broadcasted_starknet_transaction(
             *KAKAROT_ADDRESS.0.key(),           // CallArray to
             selector!("eth_send_transaction"),  // CallArray selector
             calldata.len().into(),              // CallArray data_len
-        ]
-    } else {
-        panic!("Either 'v0' or 'v1' feature must be enabled")
+        ],
     };
 
     execute_from_outside_calldata.append(&mut calldata);
@@ -166,6 +162,56 @@ pub fn to_broadcasted_starknet_transaction(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloy_consensus::TxEip4844;
+    use alloy_primitives::{b256, Signature};
+    use reth_primitives::Transaction as RethTransaction;
+
+    #[test]
+    fn test_blob_transaction_calldata_holds_versioned_hashes() {
+        // Given
+        let versioned_hash =
+            b256!("0100000000000000000000000000000000000000000000000000000000000001");
+        let transaction = TransactionSigned::from_transaction_and_signature(
+            RethTransaction::Eip4844(TxEip4844 {
+                chain_id: 1,
+                gas_limit: 100_000,
+                to: Address::with_last_byte(0xaa),
+                blob_versioned_hashes: vec![versioned_hash],
+                max_fee_per_blob_gas: 1,
+                ..Default::default()
+            }),
+            Signature::from_rs_and_parity(U256::from(1), U256::from(1), false).unwrap(),
+        );
+
+        // When
+        let BroadcastedInvokeTransaction::V1(invoke) = to_broadcasted_starknet_transaction(
+            &transaction,
+            Felt::ONE,
+            Some(Felt::ZERO),
+            KakarotVersion::V1,
+        )
+        .unwrap() else {
+            panic!("expected an invoke v1 transaction");
+        };
+
+        // Then
+        // The execute_from_outside calldata follows the 6 felts of the call array,
+        // and holds the encoded transaction after its 8 header felts.
+        let outside_calldata = &invoke.calldata[6..];
+        let len: usize = outside_calldata[7].to_biguint().try_into().unwrap();
+        let encoded: Vec<u8> = outside_calldata[8..8 + len]
+            .iter()
+            .map(|felt| felt.to_bytes_be()[31])
+            .collect();
+        let mut expected = BytesMut::new();
+        transaction
+            .transaction
+            .encode_without_signature(&mut expected);
+        assert_eq!(encoded, expected.to_vec());
+        assert!(encoded
+            .windows(32)
+            .any(|window| window == versioned_hash.as_slice()));
+    }
 
     macro_rules! test_felt_to_bytes {
         ($input: expr, $output: expr, $start: expr, $test_name: ident) => {
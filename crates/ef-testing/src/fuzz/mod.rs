@@ -0,0 +1,350 @@
+//! Fuzzing of Kakarot with generated EVM bytecode. The bytecode is deployed as a
+//! contract called by a transaction, and the execution of the transaction is
+//! checked against the following invariants:
+//! - the sequencer neither panics nor fails to execute the transaction;
+//! - the nonces of the accounts don't decrease, and the nonce of the sender is
+//!   incremented;
+//! - when a reference is given, the accounts, storage, logs and gas used match
+//!   the ones of the reference.
+//!
+//! Transactions are executed with a zero gas price and base fee, so that the
+//! balances don't depend on the fee flow of Kakarot.
+pub mod strategy;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::panic::{self, AssertUnwindSafe};
+
+use alloy_consensus::TxEip1559;
+use alloy_eips::eip2930::AccessList;
+use alloy_primitives::{address, b256, Address, Bytes, Signature, TxKind, B256, U256};
+use ef_tests::models::Account;
+use reth_primitives::{sign_message, Transaction, TransactionSigned};
+use revm::primitives::BlockEnv;
+use sequencer::state::State as SequencerState;
+
+use crate::differential::{self, AccountSnapshot, ExecutionSnapshot, Reference};
+use crate::evm_sequencer::{
+    account::KakarotAccount,
+    constants::{
+        ACCOUNT_CONTRACT_CLASS_HASH, BLOCK_GAS_LIMIT, CAIRO1_HELPERS_CLASS_HASH, CHAIN_ID,
+        KAKAROT_ADDRESS, UNINITIALIZED_ACCOUNT_CLASS_HASH,
+    },
+    evm_state::Evm,
+    sequencer::{
+        initial_sequencer_state, KakarotEnvironment, KakarotSequencer, INITIAL_SEQUENCER_STATE,
+    },
+};
+use crate::models::{
+    error::RunnerError,
+    result::{extract_output_and_log_execution_result, kakarot_logs},
+    Fork,
+};
+
+/// Address of the contract holding the generated bytecode.
+pub const FUZZ_TARGET: Address = address!("00000000000000000000000000000000000f0220");
+
+/// Secret key of the sender of the transactions.
+pub const FUZZ_SENDER_KEY: B256 =
+    b256!("45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8");
+
+/// Address of the sender of the transactions, derived from [`FUZZ_SENDER_KEY`].
+pub const FUZZ_SENDER: Address = address!("a94f5374fce5edbc8e2a8697c15331677e6ebf0b");
+
+const COINBASE: Address = address!("2adc25665018aa1fe0e6bc666dac8fc2697ff9ba");
+const FORK: Fork = Fork::Cancun;
+const GAS_LIMIT: u64 = 1_000_000;
+const SENDER_BALANCE: u64 = 1_000_000_000_000_000_000;
+const BLOCK_NUMBER: u64 = 1;
+const BLOCK_TIMESTAMP: u64 = 1_000;
+
+/// Input of the fuzzer.
+#[derive(Debug, Clone)]
+pub struct FuzzInput {
+    /// Code of the called contract.
+    pub code: Bytes,
+    pub calldata: Bytes,
+    pub value: U256,
+}
+
+impl FuzzInput {
+    /// Returns the accounts of the sender and of the called contract.
+    pub fn pre_state(&self) -> BTreeMap<Address, Account> {
+        BTreeMap::from([
+            (
+                FUZZ_SENDER,
+                Account {
+                    balance: U256::from(SENDER_BALANCE),
+                    ..Default::default()
+                },
+            ),
+            (
+                FUZZ_TARGET,
+                Account {
+                    code: self.code.clone(),
+                    nonce: U256::from(1),
+                    ..Default::default()
+                },
+            ),
+        ])
+    }
+
+    /// Returns the signed transaction calling the contract.
+    pub fn transaction(&self) -> Result<TransactionSigned, RunnerError> {
+        let transaction = Transaction::Eip1559(TxEip1559 {
+            chain_id: CHAIN_ID,
+            nonce: 0,
+            gas_limit: GAS_LIMIT,
+            max_fee_per_gas: 0,
+            max_priority_fee_per_gas: 0,
+            to: TxKind::Call(FUZZ_TARGET),
+            value: self.value,
+            access_list: AccessList::default(),
+            input: self.calldata.clone(),
+        });
+        let signature: Signature = sign_message(FUZZ_SENDER_KEY, transaction.signature_hash())
+            .map_err(|err| RunnerError::Other(vec![err.to_string()].into()))?;
+        Ok(TransactionSigned::from_transaction_and_signature(
+            transaction,
+            signature,
+        ))
+    }
+}
+
+/// Executes the input in Kakarot and, if any, in the reference. Returns the
+/// violated invariants.
+pub fn check(input: &FuzzInput, reference: Option<Reference>) -> Result<Vec<String>, RunnerError> {
+    let pre = input.pre_state();
+    let transaction = input.transaction()?;
+
+    let expected = match reference {
+        None => None,
+        Some(Reference::Revm) => {
+            let mut block_env = BlockEnv {
+                number: U256::from(BLOCK_NUMBER),
+                coinbase: COINBASE,
+                timestamp: U256::from(BLOCK_TIMESTAMP),
+                gas_limit: U256::from(BLOCK_GAS_LIMIT),
+                prevrandao: Some(B256::ZERO),
+                ..Default::default()
+            };
+            block_env.set_blob_excess_gas_and_price(0);
+            Some(differential::revm::execute_transactions(
+                &pre,
+                block_env,
+                std::slice::from_ref(&transaction),
+                FORK,
+                CHAIN_ID,
+            )?)
+        }
+        Some(Reference::Kakarot(version)) => {
+            let (snapshot, violations) = execute_kakarot(
+                &pre,
+                &transaction,
+                KakarotEnvironment::for_version(version),
+                initial_sequencer_state(version).clone(),
+                None,
+            )?;
+            if !violations.is_empty() {
+                return Ok(violations
+                    .into_iter()
+                    .map(|violation| format!("{}: {violation}", Reference::Kakarot(version)))
+                    .collect());
+            }
+            Some(snapshot)
+        }
+    };
+
+    let environment = KakarotEnvironment::new(
+        *KAKAROT_ADDRESS,
+        *UNINITIALIZED_ACCOUNT_CLASS_HASH,
+        *CAIRO1_HELPERS_CLASS_HASH,
+        *ACCOUNT_CONTRACT_CLASS_HASH,
+    );
+    let (actual, mut violations) = execute_kakarot(
+        &pre,
+        &transaction,
+        environment,
+        INITIAL_SEQUENCER_STATE.clone(),
+        expected.as_ref(),
+    )?;
+
+    if let (Some(reference), Some(expected)) = (reference, expected) {
+        if violations.is_empty() {
+            violations.extend(
+                actual
+                    .diff(&expected)
+                    .into_iter()
+                    .map(|diff| format!("kakarot vs {reference}: {diff}")),
+            );
+        }
+    }
+    Ok(violations)
+}
+
+/// Executes the transaction in Kakarot from the pre-state. Returns the snapshot
+/// of the accounts and slots of the pre-state and of the known snapshot, along
+/// with the violated invariants.
+fn execute_kakarot(
+    pre: &BTreeMap<Address, Account>,
+    transaction: &TransactionSigned,
+    environment: KakarotEnvironment,
+    initial_state: SequencerState,
+    known: Option<&ExecutionSnapshot>,
+) -> Result<(ExecutionSnapshot, Vec<String>), RunnerError> {
+    let mut sequencer = KakarotSequencer::new(
+        initial_state,
+        environment,
+        COINBASE,
+        CHAIN_ID,
+        BLOCK_NUMBER,
+        BLOCK_TIMESTAMP,
+    );
+    sequencer.setup_state(
+        U256::ZERO,
+        U256::ZERO,
+        U256::from(BLOCK_GAS_LIMIT),
+        U256::ZERO,
+    )?;
+    let codec = sequencer.account_codec();
+    for (address, account) in pre.iter() {
+        sequencer.setup_account(KakarotAccount::with_codec(
+            address,
+            account.clone(),
+            &codec,
+        )?)?;
+        sequencer.fund(address, account.balance)?;
+    }
+
+    let mut nonces = BTreeMap::new();
+    for address in pre.keys() {
+        nonces.insert(*address, sequencer.nonce_at(address)?);
+    }
+
+    let mut violations = Vec::new();
+    let execution_result = match panic::catch_unwind(AssertUnwindSafe(|| {
+        sequencer.execute_transaction(transaction.clone())
+    })) {
+        Ok(execution_result) => execution_result,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(ToString::to_string)
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            violations.push(format!("sequencer panicked: {message}"));
+            return Ok((ExecutionSnapshot::default(), violations));
+        }
+    };
+    match execution_result.as_ref() {
+        Err(err) => violations.push(format!(
+            "sequencer failed to execute the transaction: {err}"
+        )),
+        Ok(info) => {
+            if let Some(err) = info.revert_error.as_ref() {
+                violations.push(format!("sequencer reverted the transaction: {err}"));
+            }
+        }
+    }
+    let Some(output) = extract_output_and_log_execution_result(&execution_result, "fuzz", "fuzz")
+    else {
+        if violations.is_empty() {
+            violations.push("missing execution event".to_string());
+        }
+        return Ok((ExecutionSnapshot::default(), violations));
+    };
+
+    for (address, before) in nonces {
+        let after = sequencer.nonce_at(&address)?;
+        if address == FUZZ_SENDER && after != before + U256::from(1) {
+            violations.push(format!(
+                "sender nonce not incremented: {before:#x} -> {after:#x}"
+            ));
+        } else if after < before {
+            violations.push(format!(
+                "nonce decreased for {address:#20x}: {before:#x} -> {after:#x}"
+            ));
+        }
+    }
+
+    let logs = match execution_result
+        .as_ref()
+        .ok()
+        .and_then(|info| info.execute_call_info.as_ref())
+    {
+        Some(call_info) if output.success => kakarot_logs(&mut sequencer, call_info)?,
+        _ => Vec::new(),
+    };
+    let mut snapshot = ExecutionSnapshot {
+        logs: Some(logs),
+        gas_used: output.gas_used,
+        ..Default::default()
+    };
+    let accounts = pre
+        .iter()
+        .map(|(address, account)| (address, &account.storage))
+        .chain(
+            known
+                .into_iter()
+                .flat_map(|known| known.accounts.iter())
+                .map(|(address, account)| (address, &account.storage)),
+        );
+    let mut keys: BTreeMap<Address, BTreeSet<U256>> = BTreeMap::new();
+    for (address, storage) in accounts {
+        keys.entry(*address)
+            .or_default()
+            .extend(storage.keys().copied());
+    }
+    for (address, keys) in keys {
+        let mut storage = BTreeMap::new();
+        for key in keys {
+            storage.insert(key, sequencer.storage_at(&address, key)?);
+        }
+        snapshot.accounts.insert(
+            address,
+            AccountSnapshot {
+                balance: sequencer.balance_at(&address)?,
+                nonce: sequencer.nonce_at(&address)?,
+                code: sequencer.code_at(&address)?,
+                storage,
+            },
+        );
+    }
+
+    Ok((snapshot, violations))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strategy::fuzz_input;
+    use super::*;
+    use alloy_signer_local::PrivateKeySigner;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_fuzz_sender() {
+        // Given
+        let signer = PrivateKeySigner::from_bytes(&FUZZ_SENDER_KEY).unwrap();
+
+        // When
+        let sender = signer.address();
+
+        // Then
+        assert_eq!(sender, FUZZ_SENDER);
+    }
+
+    proptest! {
+        #[test]
+        #[ignore = "long running, use `make fuzz-v0` or `make fuzz-v1`"]
+        fn fuzz_bytecode(input in fuzz_input()) {
+            let violations = check(&input, None).unwrap();
+            prop_assert!(violations.is_empty(), "{violations:#?}");
+        }
+
+        #[test]
+        #[ignore = "long running, use `make fuzz-v0` or `make fuzz-v1`"]
+        fn fuzz_bytecode_against_revm(input in fuzz_input()) {
+            let violations = check(&input, Some(Reference::Revm)).unwrap();
+            prop_assert!(violations.is_empty(), "{violations:#?}");
+        }
+    }
+}
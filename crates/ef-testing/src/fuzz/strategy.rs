@@ -0,0 +1,59 @@
+//! Strategies generating the inputs of the fuzzer: random and structured EVM
+//! bytecode, calldata and value.
+use alloy_primitives::{Bytes, U256};
+use proptest::{collection::vec, prelude::*, sample::select};
+use revm_interpreter::{
+    opcode::{PUSH1, PUSH32},
+    OpCode,
+};
+
+use super::FuzzInput;
+
+/// Maximum number of instructions of the generated bytecode.
+const MAX_INSTRUCTIONS: usize = 64;
+
+/// Maximum size of the generated calldata.
+const MAX_CALLDATA_SIZE: usize = 96;
+
+/// Returns the opcodes defined by the latest fork, other than the pushes with
+/// an immediate, which are generated along with their immediate.
+fn defined_opcodes() -> Vec<u8> {
+    (0..=u8::MAX)
+        .filter(|op| OpCode::new(*op).is_some() && !(PUSH1..=PUSH32).contains(op))
+        .collect()
+}
+
+/// Returns a strategy generating the bytes of an instruction.
+fn instruction() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        // Any byte, including undefined opcodes and truncated pushes
+        1 => any::<u8>().prop_map(|byte| vec![byte]),
+        6 => select(defined_opcodes()).prop_map(|op| vec![op]),
+        // Small values, likely to be valid offsets, sizes or jump destinations
+        3 => (0u8..=64).prop_map(|value| vec![PUSH1, value]),
+        2 => vec(any::<u8>(), 1..=32).prop_map(|immediate| {
+            let mut bytes = vec![PUSH1 + immediate.len() as u8 - 1];
+            bytes.extend(immediate);
+            bytes
+        }),
+    ]
+}
+
+/// Returns a strategy generating bytecode as a sequence of instructions.
+pub fn bytecode() -> impl Strategy<Value = Bytes> {
+    vec(instruction(), 0..MAX_INSTRUCTIONS).prop_map(|instructions| instructions.concat().into())
+}
+
+/// Returns a strategy generating the inputs of the fuzzer.
+pub fn fuzz_input() -> impl Strategy<Value = FuzzInput> {
+    (
+        bytecode(),
+        vec(any::<u8>(), 0..MAX_CALLDATA_SIZE),
+        0u64..1_000,
+    )
+        .prop_map(|(code, calldata, value)| FuzzInput {
+            code,
+            calldata: calldata.into(),
+            value: U256::from(value),
+        })
+}
@@ -1,5 +1,11 @@
+pub mod coverage;
+pub mod differential;
 pub mod evm_sequencer;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 pub mod models;
+pub mod runner;
 pub mod test_utils;
+pub mod trace;
 pub mod traits;
 pub mod utils;
@@ -1,37 +1,79 @@
 // Inspired by https://github.com/paradigmxyz/reth/tree/main/testing/ef-tests
 use super::error::RunnerError;
-use super::result::{extract_output_and_log_execution_result, EVMOutput};
+use super::exception::{ExpectedException, RejectionCategory};
+use super::fees::{BlockFees, FeeFlow, FeeTolerance, TransactionFees};
+use super::metrics::CaseMetrics;
+use super::result::{extract_output_and_log_execution_result, kakarot_logs, EVMOutput};
+use super::Fork;
+use crate::coverage::CaseCoverage;
+use crate::differential::{self, ExecutionSnapshot, Reference};
+use crate::evm_sequencer::account::delegated_address;
 use crate::evm_sequencer::constants::{
     ACCOUNT_CONTRACT_CLASS_HASH, BEACON_ROOT_ADDRESS, CAIRO1_HELPERS_CLASS_HASH, KAKAROT_ADDRESS,
-    UNINITIALIZED_ACCOUNT_CLASS_HASH,
+    KAKAROT_CLASS_HASH, KAKAROT_VERSION, UNINITIALIZED_ACCOUNT_CLASS_HASH,
 };
 use crate::evm_sequencer::evm_state::Evm;
 use crate::evm_sequencer::sequencer::{
-    KakarotEnvironment, KakarotSequencer, INITIAL_SEQUENCER_STATE,
+    initial_sequencer_state, KakarotEnvironment, KakarotSequencer, INITIAL_SEQUENCER_STATE,
 };
 use crate::{
     evm_sequencer::{account::KakarotAccount, constants::CHAIN_ID},
+    trace::{self, divergence::Divergence, kakarot::kakarot_trace, Trace},
     traits::Case,
     utils::update_post_state,
 };
+use alloy_consensus::Transaction as _;
+use alloy_eips::eip4844::DATA_GAS_PER_BLOB;
 use alloy_rlp::Decodable as _;
 use async_trait::async_trait;
+use build_utils::constants::ADDRESSES_KEYS;
 use ef_tests::models::Account;
 use ef_tests::models::Block;
 use ef_tests::models::State;
-use std::collections::BTreeMap;
+use sequencer::state::State as SequencerState;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::str::FromStr as _;
+use tracing::warn;
 
 use alloy_primitives::{Address, B256, U256};
-use reth_primitives::{sign_message, SealedBlock};
+use reth_primitives::{sign_message, SealedBlock, Transaction, TransactionSigned};
+
+/// A block of the case, along with the exception expected by the fixture if
+/// the block is invalid.
+struct CaseBlock<'a> {
+    block: &'a Block,
+    /// The decoded block, `None` if the block is expected not to decode.
+    sealed_block: Option<SealedBlock>,
+    expected_exception: Option<ExpectedException>,
+}
+
+/// Number of steps preceding the first divergence between two traces which are
+/// reported.
+const DIVERGENCE_CONTEXT: usize = 10;
 
 #[derive(Debug)]
 pub struct BlockchainTestCase {
     case_name: String,
     case_category: String,
-    block: Block,
+    blocks: Vec<Block>,
     pre: State,
     post: Option<BTreeMap<Address, Account>>,
     secret_key: B256,
+    /// Exceptions expected by the fixture for each block, `None` for the valid
+    /// blocks.
+    expected_exceptions: Vec<Option<String>>,
+    fork: Fork,
+    /// Reference the execution is compared to, if any.
+    reference: Option<Reference>,
+    /// Directory the EIP-3155 traces of the transactions are written to, if any.
+    trace_dir: Option<PathBuf>,
+    /// Whether the opcodes and precompiles covered by the case are collected.
+    coverage: bool,
+    /// Directory the Cairo profile of the Kakarot class is written to, if any.
+    profile_dir: Option<PathBuf>,
+    /// Tolerance allowed on each fee component when comparing the balances.
+    fee_tolerance: FeeTolerance,
 }
 
 // Division of logic:
@@ -41,7 +83,7 @@ impl BlockchainTestCase {
     pub const fn new(
         case_name: String,
         case_category: String,
-        block: Block,
+        blocks: Vec<Block>,
         pre: State,
         post: Option<BTreeMap<Address, Account>>,
         secret_key: B256,
@@ -49,16 +91,138 @@ impl BlockchainTestCase {
         Self {
             case_name,
             case_category,
-            block,
+            blocks,
             pre,
             post,
             secret_key,
+            expected_exceptions: Vec::new(),
+            fork: Fork::DEFAULT,
+            reference: None,
+            trace_dir: None,
+            coverage: false,
+            profile_dir: None,
+            fee_tolerance: FeeTolerance {
+                base_fee: U256::ZERO,
+                priority_fee: U256::ZERO,
+                blob_fee: U256::ZERO,
+            },
         }
     }
 
+    /// Sets the exceptions expected by the fixture for each block, `None` for
+    /// the valid blocks. All the blocks are expected to be valid by default.
+    pub fn with_expected_exceptions(mut self, expected_exceptions: Vec<Option<String>>) -> Self {
+        self.expected_exceptions = expected_exceptions;
+        self
+    }
+
+    /// Sets the fork the case is run for, `Fork::DEFAULT` by default.
+    pub const fn with_fork(mut self, fork: Fork) -> Self {
+        self.fork = fork;
+        self
+    }
+
+    /// Compares the execution of the block by Kakarot to its execution by the
+    /// reference. Divergences are reported as failures of the case.
+    pub const fn with_reference(mut self, reference: Option<Reference>) -> Self {
+        self.reference = reference;
+        self
+    }
+
+    /// Writes the EIP-3155 trace of each transaction of the block to the given
+    /// directory, as `<case name>-<transaction index>.jsonl`. When comparing the
+    /// execution to a reference, the traces of the reference are written as
+    /// `<case name>-<transaction index>.<reference>.jsonl`, and the first
+    /// divergence between the traces of a failing case is reported.
+    pub fn with_trace_dir(mut self, trace_dir: Option<PathBuf>) -> Self {
+        self.trace_dir = trace_dir;
+        self
+    }
+
+    /// Collects the opcodes and precompiles covered by the case in its metrics.
+    /// The executed coverage is collected from the opcode steps of the traces of
+    /// the transactions, which Kakarot doesn't emit yet: until then, the coverage
+    /// is only collected from the code of the case.
+    pub const fn with_coverage(mut self, coverage: bool) -> Self {
+        self.coverage = coverage;
+        self
+    }
+
+    /// Profiles the Cairo steps of the Kakarot class while executing the block,
+    /// and writes the profile to the given directory as folded stacks, as
+    /// `<case name>.folded`. Only Kakarot v0, a Cairo 0 class, can be profiled.
+    pub fn with_profile_dir(mut self, profile_dir: Option<PathBuf>) -> Self {
+        self.profile_dir = profile_dir;
+        self
+    }
+
+    /// Allows the balances held by Kakarot to deviate from the expected ones by
+    /// the given tolerance on each fee component of the executed transactions.
+    pub const fn with_fee_tolerance(mut self, fee_tolerance: FeeTolerance) -> Self {
+        self.fee_tolerance = fee_tolerance;
+        self
+    }
+
+    /// Returns the coverage of the code of the pre-state and of the
+    /// transactions of the case, without executing it.
+    pub fn code_coverage(&self) -> Result<CaseCoverage, RunnerError> {
+        let blocks = self.decode_blocks()?;
+        Ok(CaseCoverage::new(
+            &self.pre,
+            &Self::transactions(&blocks),
+            &[],
+            self.fork,
+        ))
+    }
+
+    /// Decodes the blocks of the case and parses their expected exceptions. A
+    /// block which cannot be decoded is only accepted if an RLP exception is
+    /// expected for it.
+    fn decode_blocks(&self) -> Result<Vec<CaseBlock<'_>>, RunnerError> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| {
+                let expected_exception = self
+                    .expected_exceptions
+                    .get(index)
+                    .and_then(Option::as_deref)
+                    .map(ExpectedException::from_str)
+                    .transpose()?;
+                let sealed_block = match SealedBlock::decode(&mut block.rlp.as_ref()) {
+                    Ok(sealed_block) => Some(sealed_block),
+                    // An undecodable block is the expected outcome for RLP exceptions.
+                    Err(_)
+                        if expected_exception
+                            .as_ref()
+                            .is_some_and(|e| e.matches(RejectionCategory::RlpDecode)) =>
+                    {
+                        None
+                    }
+                    Err(err) => return Err(RunnerError::RlpDecodeError(err)),
+                };
+                Ok(CaseBlock {
+                    block,
+                    sealed_block,
+                    expected_exception,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the transactions of the decoded blocks.
+    fn transactions(blocks: &[CaseBlock<'_>]) -> Vec<TransactionSigned> {
+        blocks
+            .iter()
+            .filter_map(|block| block.sealed_block.as_ref())
+            .flat_map(|sealed_block| sealed_block.body.transactions.iter().cloned())
+            .collect()
+    }
+
     fn handle_pre_state(&self, sequencer: &mut KakarotSequencer) -> Result<(), RunnerError> {
         for (address, account) in self.pre.iter() {
-            let kakarot_account = KakarotAccount::new(address, account.clone())?;
+            let kakarot_account =
+                KakarotAccount::with_codec(address, account.clone(), &sequencer.account_codec())?;
             sequencer.setup_account(kakarot_account)?;
             sequencer.fund(address, account.balance)?;
         }
@@ -69,89 +233,430 @@ impl BlockchainTestCase {
     fn handle_transaction(
         &self,
         sequencer: &mut KakarotSequencer,
+        block: &SealedBlock,
+        metrics: &mut CaseMetrics,
     ) -> Result<EVMOutput, RunnerError> {
-        // we extract the transactions from the block
-        let block = SealedBlock::decode(&mut self.block.rlp.as_ref())
-            .map_err(RunnerError::RlpDecodeError)?;
-
         let mut output = EVMOutput::default();
 
         // Iterate over all transactions in the block
         for tx in block.body.transactions.iter() {
-            // Encode body as transaction
-            let mut tx_signed = tx.clone();
-            tx_signed.transaction.set_chain_id(CHAIN_ID);
-            // TODO: this will not support blocks with transactions from different senders (different secret key)
-            let signature = sign_message(self.secret_key, tx_signed.signature_hash())
-                .map_err(|err| RunnerError::Other(vec![err.to_string()].into()))?;
-            tx_signed.signature = signature;
-
+            let tx_signed = self.sign_transaction(tx)?;
             let execution_result = sequencer.execute_transaction(tx_signed);
+            if let Ok(info) = execution_result.as_ref() {
+                metrics.record_execution(info);
+            }
 
             // Update the output with the execution result of the current transaction
-            let tx_output = extract_output_and_log_execution_result(
+            let mut tx_output = extract_output_and_log_execution_result(
                 &execution_result,
                 &self.case_name,
                 &self.case_category,
             )
             .unwrap_or_default();
 
+            // Logs are only needed to compare the execution to the reference
+            if self.reference.is_some() && tx_output.success {
+                if let Some(call_info) = execution_result
+                    .as_ref()
+                    .ok()
+                    .and_then(|info| info.execute_call_info.as_ref())
+                {
+                    tx_output.logs = kakarot_logs(sequencer, call_info)?;
+                }
+            }
+
+            if self.trace_dir.is_some() || self.coverage {
+                let call_info = execution_result
+                    .as_ref()
+                    .ok()
+                    .and_then(|info| info.execute_call_info.as_ref());
+                let trace = kakarot_trace(call_info, &tx_output)?;
+                if self.trace_dir.is_some() && call_info.is_some() && trace.steps.is_empty() {
+                    warn!(
+                        "{}: no opcode_executed event found for the transaction, its trace only holds the summary",
+                        self.case_name
+                    );
+                }
+                tx_output.traces = vec![trace];
+            }
+
+            tx_output.transactions_gas_used = vec![tx_output.gas_used];
+
+            // Kakarot doesn't report the blob gas of a transaction: blob gas is
+            // consumed by the blob transactions Kakarot accepts, from the versioned
+            // hashes sent in their calldata.
+            tx_output.blob_gas_used = Self::blob_gas_used(
+                &tx.transaction,
+                RejectionCategory::from_execution_result(&execution_result),
+            );
+
             output.merge(&tx_output);
         }
 
         Ok(output)
     }
 
-    fn handle_post_state(
+    /// Executes the transactions of a block expected to be invalid and checks
+    /// that Kakarot rejects it for the expected reason, without any change to
+    /// the accounts of the case, balances included. The block is then rolled
+    /// back, so that the next blocks are executed on top of the last valid one.
+    /// Returns the list of mismatches.
+    fn handle_expected_exception(
         &self,
         sequencer: &mut KakarotSequencer,
-        output: EVMOutput,
+        block: &SealedBlock,
+        expected_exception: &ExpectedException,
+        metrics: &mut CaseMetrics,
+    ) -> Result<Vec<String>, RunnerError> {
+        let accounts = self.read_accounts(sequencer)?;
+        let checkpoint = sequencer.clone();
+
+        let mut rejection = None;
+        for tx in block.body.transactions.iter() {
+            let tx_signed = self.sign_transaction(tx)?;
+            let execution_result = sequencer.execute_transaction(tx_signed);
+            if let Ok(info) = execution_result.as_ref() {
+                metrics.record_execution(info);
+            }
+            extract_output_and_log_execution_result(
+                &execution_result,
+                &self.case_name,
+                &self.case_category,
+            );
+
+            rejection = RejectionCategory::from_execution_result(&execution_result);
+            if rejection.is_some() {
+                break;
+            }
+        }
+
+        let mut errors = Vec::new();
+        match rejection {
+            None => errors.push(format!(
+                "expected exception {expected_exception}, but the block was accepted"
+            )),
+            Some(category) if !expected_exception.matches(category) => errors.push(format!(
+                "expected exception {expected_exception}, got {category}"
+            )),
+            Some(_) => (),
+        }
+
+        // A rejected block should leave the state of the last valid block untouched.
+        for (address, expected_state) in accounts.iter() {
+            errors.append(&mut Self::assert_account_state(
+                sequencer,
+                address,
+                expected_state,
+            )?);
+            let actual = sequencer.balance_at(address)?;
+            if actual != expected_state.balance {
+                errors.push(format!(
+                    "balance mismatch for {:#20x}: expected {:#32x}, got {:#32x}",
+                    address, expected_state.balance, actual
+                ));
+            }
+        }
+
+        *sequencer = checkpoint;
+        Ok(errors)
+    }
+
+    /// Reads the accounts of the pre and post states from Kakarot, with the
+    /// slots listed in either state.
+    fn read_accounts(
+        &self,
+        sequencer: &mut KakarotSequencer,
+    ) -> Result<BTreeMap<Address, Account>, RunnerError> {
+        let mut slots: BTreeMap<Address, BTreeSet<U256>> = BTreeMap::new();
+        for (address, account) in self.pre.iter().chain(self.post.iter().flatten()) {
+            slots
+                .entry(*address)
+                .or_default()
+                .extend(account.storage.keys().copied());
+        }
+
+        let mut accounts = BTreeMap::new();
+        for (address, keys) in slots {
+            let mut storage = BTreeMap::new();
+            for key in keys {
+                storage.insert(key, sequencer.storage_at(&address, key)?);
+            }
+            accounts.insert(
+                address,
+                Account {
+                    balance: sequencer.balance_at(&address)?,
+                    code: sequencer.code_at(&address)?,
+                    nonce: sequencer.nonce_at(&address)?,
+                    storage,
+                },
+            );
+        }
+        Ok(accounts)
+    }
+
+    /// Compares the storage, nonce and code of the account at the given address
+    /// to the expected state. Returns the list of mismatches.
+    fn assert_account_state(
+        sequencer: &mut KakarotSequencer,
+        address: &Address,
+        expected_state: &Account,
+    ) -> Result<Vec<String>, RunnerError> {
+        let mut errors = Vec::new();
+
+        // Storage
+        for (k, v) in expected_state.storage.iter() {
+            let actual = sequencer.storage_at(address, *k)?;
+            if actual != *v {
+                let storage_diff = format!(
+                    "storage mismatch for {:#20x} at {:#32x}: expected {:#32x}, got {:#32x}",
+                    address, k, v, actual
+                );
+                errors.push(storage_diff);
+            }
+        }
+
+        // Nonce
+        let actual = sequencer.nonce_at(address)?;
+        if actual != expected_state.nonce {
+            let nonce_diff = format!(
+                "nonce mismatch for {:#20x}: expected {:#32x}, got {:#32x}",
+                address, expected_state.nonce, actual
+            );
+            errors.push(nonce_diff);
+        }
+
+        // Bytecode
+        let actual = sequencer.code_at(address)?;
+        if actual != expected_state.code {
+            let bytecode_diff = match (
+                delegated_address(&expected_state.code),
+                sequencer.delegation_at(address)?,
+            ) {
+                (Some(expected), Some(delegation)) => format!(
+                    "delegation mismatch for {:#20x}: expected {:#20x}, got {:#20x}",
+                    address, expected, delegation
+                ),
+                (Some(expected), None) => format!(
+                    "delegation mismatch for {:#20x}: expected {:#20x}, got code {:#x}",
+                    address, expected, actual
+                ),
+                _ => format!(
+                    "code mismatch for {:#20x}: expected {:#x}, got {:#x}",
+                    address, expected_state.code, actual
+                ),
+            };
+            errors.push(bytecode_diff);
+        }
+
+        Ok(errors)
+    }
+
+    /// Compares the set of accounts known to Kakarot with the expected post state.
+    /// Flags any account with a non-empty state that is either not expected in the
+    /// post state, or expected but unknown to Kakarot.
+    #[cfg(feature = "strict")]
+    fn assert_no_unexpected_accounts(
+        &self,
+        sequencer: &mut KakarotSequencer,
+        post_state: &BTreeMap<Address, Account>,
+    ) -> Result<Vec<String>, RunnerError> {
+        let mut errors = Vec::new();
+        let kakarot_accounts = sequencer.accounts()?;
+
+        for address in kakarot_accounts.iter() {
+            if post_state.contains_key(address) || self.is_beacon_root(address) {
+                continue;
+            }
+            let nonce = sequencer.nonce_at(address)?;
+            let code = sequencer.code_at(address)?;
+            let balance = sequencer.balance_at(address)?;
+            let has_storage = sequencer.has_storage(address)?;
+            if nonce != U256::ZERO || !code.is_empty() || balance != U256::ZERO || has_storage {
+                errors.push(format!(
+                    "unexpected account {:#20x}: nonce {:#x}, balance {:#x}, code {:#x}, non-empty storage {}",
+                    address, nonce, balance, code, has_storage
+                ));
+            }
+        }
+
+        for (address, expected_state) in post_state.iter() {
+            if kakarot_accounts.contains(address) || self.is_beacon_root(address) {
+                continue;
+            }
+            let is_empty = expected_state.nonce == U256::ZERO
+                && expected_state.code.is_empty()
+                && expected_state.balance == U256::ZERO
+                && expected_state.storage.values().all(|v| *v == U256::ZERO);
+            if !is_empty {
+                errors.push(format!("missing account {:#20x}", address));
+            }
+        }
+
+        Ok(errors)
+    }
+
+    /// Returns true if the address is the beacon roots contract and the fork
+    /// of the case includes it (EIP-4788).
+    fn is_beacon_root(&self, address: &Address) -> bool {
+        self.fork.has_beacon_root() && *address == BEACON_ROOT_ADDRESS
+    }
+
+    /// Sets the chain id of the transaction and signs it with the case's secret key.
+    fn sign_transaction(&self, tx: &TransactionSigned) -> Result<TransactionSigned, RunnerError> {
+        let mut tx_signed = tx.clone();
+        let fixture_chain_id = tx.chain_id().unwrap_or(CHAIN_ID);
+        tx_signed.transaction.set_chain_id(CHAIN_ID);
+        Self::sign_authorizations(&mut tx_signed.transaction, fixture_chain_id)?;
+        // TODO: this will not support blocks with transactions from different senders (different secret key)
+        let signature = sign_message(self.secret_key, tx_signed.signature_hash())
+            .map_err(|err| RunnerError::Other(vec![err.to_string()].into()))?;
+        tx_signed.signature = signature;
+        Ok(tx_signed)
+    }
+
+    /// Returns the blob gas consumed by the transaction: one blob of gas per
+    /// versioned hash of a blob transaction, provided Kakarot accepted it.
+    fn blob_gas_used(transaction: &Transaction, rejection: Option<RejectionCategory>) -> u64 {
+        match (transaction, rejection) {
+            (Transaction::Eip4844(tx), None) => {
+                tx.blob_versioned_hashes.len() as u64 * DATA_GAS_PER_BLOB
+            }
+            _ => 0,
+        }
+    }
+
+    /// Compares the blob gas used by the transactions of the block accepted by
+    /// Kakarot to the blob gas used of the header (EIP-4844). A blob transaction
+    /// included by the fixture and rejected by Kakarot, or the opposite, is
+    /// reported as a mismatch.
+    fn assert_blob_gas(expected: Option<u64>, output: &EVMOutput) -> Option<String> {
+        let expected = expected?;
+        (expected != output.blob_gas_used).then(|| {
+            format!(
+                "blob gas used mismatch: expected {expected}, got {}",
+                output.blob_gas_used
+            )
+        })
+    }
+
+    /// Signs the authorizations (EIP-7702) of the transaction issued for the chain id
+    /// of the fixture transaction with the Kakarot chain id. Authorizations valid on
+    /// any chain, with an invalid signature or from an authority with an unknown
+    /// secret key are kept as is, so that invalid authorizations remain invalid.
+    fn sign_authorizations(
+        transaction: &mut Transaction,
+        fixture_chain_id: u64,
     ) -> Result<(), RunnerError> {
-        let maybe_revert_reason = String::from_utf8(output.return_data.as_slice().to_vec());
+        let Transaction::Eip7702(tx) = transaction else {
+            return Ok(());
+        };
 
-        // Get gas_used and base_fee from RLP block - as in some cases, the block header is not present in the test data.
-        let sealed_block = SealedBlock::decode(&mut self.block.rlp.as_ref())
-            .map_err(RunnerError::RlpDecodeError)?;
-        let sealed_header = sealed_block.header.unseal();
+        for authorization in tx.authorization_list.iter_mut() {
+            if authorization.chain_id != fixture_chain_id {
+                continue;
+            }
+            let Ok(authority) = authorization.recover_authority() else {
+                continue;
+            };
+            let Some(secret_key) = ADDRESSES_KEYS.get(&authority) else {
+                continue;
+            };
+            let secret_key = B256::from_str(secret_key)
+                .map_err(|err| RunnerError::Other(vec![err.to_string()].into()))?;
+
+            let mut unsigned = authorization.inner().clone();
+            unsigned.chain_id = CHAIN_ID;
+            let signature = sign_message(secret_key, unsigned.signature_hash())
+                .map_err(|err| RunnerError::Other(vec![err.to_string()].into()))?;
+            *authorization = unsigned.into_signed(signature);
+        }
 
-        let base_fee_per_gas: U256 = U256::from(sealed_header.base_fee_per_gas.unwrap_or_default());
+        Ok(())
+    }
 
-        let expected_gas_used = U256::from(sealed_header.gas_used);
+    /// Returns the fees paid according to the EF fixture, from the block header
+    /// and the transactions of the block, given the gas used by each of them.
+    fn block_fees(sealed_block: &SealedBlock, gas_used: &[u64]) -> Result<BlockFees, RunnerError> {
+        let header = &sealed_block.header;
+        let base_fee = header.base_fee_per_gas.unwrap_or_default();
 
-        // Get gas price from transaction
-        let maybe_transaction = self
-            .block
+        let transactions = sealed_block
+            .body
             .transactions
-            .as_ref()
-            .and_then(|transactions| transactions.first());
-        let gas_price = maybe_transaction
-            .and_then(|transaction| transaction.gas_price)
-            .unwrap_or_default();
-        let max_priority_fee_per_gas = maybe_transaction
-            .and_then(|transaction| transaction.max_priority_fee_per_gas)
-            .unwrap_or_default();
-        let effective_gas_price = maybe_transaction
-            .and_then(|transaction| transaction.max_fee_per_gas)
-            .map(|max_fee_per_gas| {
-                max_priority_fee_per_gas.min(max_fee_per_gas - base_fee_per_gas) + base_fee_per_gas
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                let sender = tx.recover_signer().ok_or_else(|| {
+                    RunnerError::Other(
+                        vec![format!(
+                            "failed to recover the sender of transaction {index}"
+                        )]
+                        .into(),
+                    )
+                })?;
+                // <https://eips.ethereum.org/EIPS/eip-1559>: priority fee is capped because the base fee is filled first.
+                // Legacy transactions have a max fee and a priority fee equal to their gas price.
+                let effective_gas_price = tx
+                    .transaction
+                    .max_fee_per_gas()
+                    .min(u128::from(base_fee) + tx.transaction.priority_fee_or_price());
+                Ok(TransactionFees {
+                    sender,
+                    gas_used: U256::from(gas_used.get(index).copied().unwrap_or_default()),
+                    effective_gas_price: U256::from(effective_gas_price),
+                    blob_gas_used: U256::from(tx.transaction.blob_gas_used().unwrap_or_default()),
+                })
             })
-            .unwrap_or_default();
-        // <https://eips.ethereum.org/EIPS/eip-1559>: priority fee is capped because the base fee is filled first
-        if gas_price != U256::ZERO && effective_gas_price != U256::ZERO {
-            return Err(RunnerError::Other(
-                vec!["max_fee_per_gas and gas_price are both set".to_string()].into(),
-            ));
-        }
+            .collect::<Result<Vec<_>, RunnerError>>()?;
+
+        Ok(BlockFees {
+            coinbase: header.beneficiary,
+            base_fee: U256::from(base_fee),
+            blob_base_fee: U256::from(header.blob_fee().unwrap_or_default()),
+            transactions,
+        })
+    }
+
+    /// Compares the state of Kakarot after the execution of the valid blocks to
+    /// the post state of the fixture.
+    fn handle_post_state(
+        &self,
+        sequencer: &mut KakarotSequencer,
+        output: EVMOutput,
+        executed: &[(&Block, &SealedBlock)],
+    ) -> Result<(), RunnerError> {
+        let maybe_revert_reason = String::from_utf8(output.return_data.as_slice().to_vec());
+
+        // Get the fees from the RLP block - as in some cases, the block header is not present in the test data.
+        // The gas used by each transaction is the one used by Kakarot, the total being compared to the fixture.
+        let mut transactions_gas_used = output.transactions_gas_used.as_slice();
+        let block_fees = executed
+            .iter()
+            .map(|(_, sealed_block)| {
+                let count = sealed_block
+                    .body
+                    .transactions
+                    .len()
+                    .min(transactions_gas_used.len());
+                let (gas_used, rest) = transactions_gas_used.split_at(count);
+                transactions_gas_used = rest;
+                Self::block_fees(sealed_block, gas_used)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let expected_gas_used: u64 = executed
+            .iter()
+            .map(|(_, sealed_block)| sealed_block.header.gas_used)
+            .sum();
         let post_state = self.post.clone().expect("Post state not found");
         let post_state = update_post_state(post_state, self.pre.clone());
+        let fee_flow = FeeFlow::current();
 
         let mut errors = Vec::new();
 
         let actual_gas_used = output.gas_used;
-        let expected_gas_u64: u64 = expected_gas_used.try_into().unwrap();
-        if expected_gas_u64 != actual_gas_used {
+        if expected_gas_used != actual_gas_used {
             errors.push(format!(
-                "gas used mismatch: expected {expected_gas_u64}, got {actual_gas_used}"
+                "gas used mismatch: expected {expected_gas_used}, got {actual_gas_used}"
             ));
         }
 
@@ -159,89 +664,343 @@ impl BlockchainTestCase {
             //TODO: this should not be a part of the post-state of EF-Tests and can
             // be removed once we base ourself on the next EF-Tests release, which fixes this issue
             // Beacon-related features are not supported in Kakarot
-            if *address == BEACON_ROOT_ADDRESS {
+            if self.is_beacon_root(address) {
                 continue;
             }
 
-            // Storage
-            for (k, v) in expected_state.storage.iter() {
-                let actual = sequencer.storage_at(address, *k)?;
-                if actual != *v {
-                    let storage_diff = format!(
-                        "storage mismatch for {:#20x} at {:#32x}: expected {:#32x}, got {:#32x}",
-                        address, k, v, actual
-                    );
-                    errors.push(storage_diff);
-                }
-            }
+            // Storage, nonce and bytecode
+            errors.append(&mut Self::assert_account_state(
+                sequencer,
+                address,
+                expected_state,
+            )?);
 
-            // Nonce
-            let actual = sequencer.nonce_at(address)?;
-            if actual != expected_state.nonce {
-                let nonce_diff = format!(
-                    "nonce mismatch for {:#20x}: expected {:#32x}, got {:#32x}",
-                    address, expected_state.nonce, actual
+            // Balance
+            let actual = sequencer.balance_at(address)?;
+            let expected = block_fees
+                .iter()
+                .fold(expected_state.balance, |balance, fees| {
+                    fees.kakarot_balance(fee_flow, address, balance)
+                });
+            let tolerance = block_fees.iter().fold(U256::ZERO, |tolerance, fees| {
+                tolerance.saturating_add(fees.tolerance(fee_flow, address, &self.fee_tolerance))
+            });
+            if actual.abs_diff(expected) > tolerance {
+                let balance_diff = format!(
+                    "balance mismatch for {:#20x}: expected {:#32x} ({:#32x} before {:?} adjustment, tolerance {:#x}), got {:#32x}",
+                    address, expected, expected_state.balance, fee_flow, tolerance, actual
                 );
-                errors.push(nonce_diff);
+                errors.push(balance_diff);
             }
+        }
 
-            // Bytecode
-            let actual = sequencer.code_at(address)?;
-            if actual != expected_state.code {
-                let bytecode_diff = format!(
-                    "code mismatch for {:#20x}: expected {:#x}, got {:#x}",
-                    address, expected_state.code, actual
-                );
-                errors.push(bytecode_diff);
+        #[cfg(feature = "strict")]
+        errors.append(&mut self.assert_no_unexpected_accounts(sequencer, &post_state)?);
+
+        if !errors.is_empty() {
+            if let Ok(revert_reason) = maybe_revert_reason {
+                errors.push(format!("revert reason: {}", revert_reason));
             }
+            return Err(RunnerError::Other(errors.into()));
+        }
 
-            // Balance
+        Ok(())
+    }
+
+    /// Executes the block with the reference and compares the resulting state,
+    /// logs and gas to the ones of Kakarot and of the fixture. Returns the list of
+    /// divergences.
+    fn handle_differential(
+        &self,
+        sequencer: &mut KakarotSequencer,
+        sealed_block: &SealedBlock,
+        output: &EVMOutput,
+        reference: Reference,
+    ) -> Result<Vec<String>, RunnerError> {
+        let mut fixture = self.post.clone().map(|post_state| {
+            let post_state = update_post_state(post_state, self.pre.clone());
+            ExecutionSnapshot::from_post_state(&post_state, sealed_block.header.gas_used)
+        });
+
+        let mut reference_traces = Vec::new();
+        let mut expected = match reference {
+            Reference::Revm => {
+                if self.trace_dir.is_some() {
+                    reference_traces =
+                        trace::revm::trace_block(&self.pre, sealed_block, self.fork, CHAIN_ID)?;
+                }
+                differential::revm::execute_block(&self.pre, sealed_block, self.fork, CHAIN_ID)?
+            }
+            Reference::Kakarot(version) => {
+                let mut reference_sequencer = self.new_sequencer(
+                    sealed_block,
+                    KakarotEnvironment::for_version(version),
+                    initial_sequencer_state(version).clone(),
+                )?;
+                let mut output = self.handle_transaction(
+                    &mut reference_sequencer,
+                    sealed_block,
+                    &mut CaseMetrics::default(),
+                )?;
+                reference_traces = std::mem::take(&mut output.traces);
+                self.kakarot_snapshot(
+                    &mut reference_sequencer,
+                    &output,
+                    sealed_block,
+                    FeeFlow::for_version(version),
+                    fixture.iter(),
+                )?
+            }
+        };
+        let mut actual = self.kakarot_snapshot(
+            sequencer,
+            output,
+            sealed_block,
+            FeeFlow::current(),
+            std::iter::once(&expected).chain(fixture.iter()),
+        )?;
+
+        // revm doesn't run the system calls of the block
+        if self.fork.has_beacon_root() {
+            for snapshot in [&mut expected, &mut actual]
+                .into_iter()
+                .chain(fixture.as_mut())
+            {
+                snapshot.accounts.remove(&BEACON_ROOT_ADDRESS);
+            }
+        }
+
+        let kakarot = KAKAROT_VERSION
+            .map(|version| Reference::Kakarot(version).to_string())
+            .unwrap_or_else(|| "kakarot".to_string());
+        let mut divergences: Vec<String> = actual
+            .diff(&expected)
+            .into_iter()
+            .map(|diff| format!("{kakarot} vs {reference}: {diff}"))
+            .collect();
+        if let Some(fixture) = fixture {
+            let fixture_divergences: Vec<String> = expected
+                .diff(&fixture)
+                .into_iter()
+                .map(|diff| format!("{reference} vs fixture: {diff}"))
+                .collect();
+            if fixture_divergences.is_empty() && !divergences.is_empty() {
+                divergences.push(format!("{reference} agrees with the fixture"));
+            }
+            divergences.extend(fixture_divergences);
+        }
 
-            #[cfg(feature = "v0")]
-            //TODO Charging fees is not enabled yet for SSJ
+        self.write_traces(&reference_traces, Some(&reference.to_string()))?;
+        if !divergences.is_empty() {
+            for (index, (actual, expected)) in output
+                .traces
+                .iter()
+                .zip(reference_traces.iter())
+                .enumerate()
             {
-                let coinbase = sealed_header.beneficiary;
-                let mut actual = sequencer.balance_at(address)?;
-                // Our coinbase should receive all of the txs fees, not only the priority fee.
-                if *address == coinbase {
-                    actual -= base_fee_per_gas * expected_gas_used;
+                // Kakarot doesn't emit opcode steps yet, traces only hold the summary
+                if actual.steps.is_empty() {
+                    continue;
                 }
-                if actual != expected_state.balance {
-                    let balance_diff = format!(
-                        "balance mismatch for {:#20x}: expected {:#32x}, got {:#32x}",
-                        address, expected_state.balance, actual
-                    );
-                    errors.push(balance_diff);
+                if let Some(divergence) = Divergence::find(actual, expected) {
+                    divergences.push(format!(
+                        "transaction {index}: {}",
+                        divergence.report(
+                            actual,
+                            expected,
+                            DIVERGENCE_CONTEXT,
+                            &kakarot,
+                            &reference.to_string()
+                        )
+                    ));
                 }
             }
         }
+        Ok(divergences)
+    }
 
-        if !errors.is_empty() {
-            if let Ok(revert_reason) = maybe_revert_reason {
-                errors.push(format!("revert reason: {}", revert_reason));
+    /// Writes the Cairo profile of the Kakarot class to the profile directory,
+    /// if any.
+    fn write_profile(&self, sequencer: &KakarotSequencer) -> Result<(), RunnerError> {
+        let (Some(profile_dir), Some(profiler)) = (self.profile_dir.as_ref(), sequencer.profiler())
+        else {
+            return Ok(());
+        };
+        let file_name = format!("{}.folded", self.case_name.replace('/', "_"));
+        profiler
+            .profile()
+            .write_folded(&profile_dir.join(file_name))
+            .map_err(eyre::Error::from)?;
+        Ok(())
+    }
+
+    /// Writes the traces of the transactions to the trace directory, if any,
+    /// suffixing the file names with the given label.
+    fn write_traces(&self, traces: &[Trace], label: Option<&str>) -> Result<(), RunnerError> {
+        let Some(trace_dir) = self.trace_dir.as_ref() else {
+            return Ok(());
+        };
+        let name = self.case_name.replace('/', "_");
+        for (index, trace) in traces.iter().enumerate() {
+            let file_name = match label {
+                Some(label) => format!("{name}-{index}.{label}.jsonl"),
+                None => format!("{name}-{index}.jsonl"),
+            };
+            trace.write_json_lines(&trace_dir.join(file_name))?;
+        }
+        Ok(())
+    }
+
+    /// Reads the snapshot of the state of Kakarot after the execution of the
+    /// block. All the accounts and slots of the pre-state and of the given
+    /// snapshots are read, and the balances are normalized to the fee flow of
+    /// the fixtures, from the gas used by each transaction of the output.
+    fn kakarot_snapshot<'a>(
+        &self,
+        sequencer: &mut KakarotSequencer,
+        output: &EVMOutput,
+        sealed_block: &SealedBlock,
+        fee_flow: FeeFlow,
+        known: impl Iterator<Item = &'a ExecutionSnapshot> + Clone,
+    ) -> Result<ExecutionSnapshot, RunnerError> {
+        let block_fees = Self::block_fees(sealed_block, &output.transactions_gas_used)?;
+        let addresses: BTreeSet<Address> = known
+            .clone()
+            .flat_map(|snapshot| snapshot.accounts.keys())
+            .chain(self.pre.keys())
+            .copied()
+            .collect();
+
+        let mut snapshot = ExecutionSnapshot {
+            logs: Some(output.logs.clone()),
+            gas_used: output.gas_used,
+            ..Default::default()
+        };
+        for address in addresses {
+            let keys: BTreeSet<U256> = known
+                .clone()
+                .filter_map(|snapshot| snapshot.accounts.get(&address))
+                .flat_map(|account| account.storage.keys().copied())
+                .chain(
+                    self.pre
+                        .get(&address)
+                        .into_iter()
+                        .flat_map(|account| account.storage.keys().copied()),
+                )
+                .collect();
+            let mut storage = BTreeMap::new();
+            for key in keys {
+                storage.insert(key, sequencer.storage_at(&address, key)?);
             }
-            return Err(RunnerError::Other(errors.into()));
+            let balance = sequencer.balance_at(&address)?;
+            snapshot.accounts.insert(
+                address,
+                differential::AccountSnapshot {
+                    balance: block_fees.ef_balance(fee_flow, &address, balance),
+                    nonce: sequencer.nonce_at(&address)?,
+                    code: sequencer.code_at(&address)?,
+                    storage,
+                },
+            );
         }
+        Ok(snapshot)
+    }
+
+    /// Returns a sequencer for the block, from the given initial state, with the
+    /// pre-state of the case set up.
+    fn new_sequencer(
+        &self,
+        sealed_block: &SealedBlock,
+        environment: KakarotEnvironment,
+        initial_state: SequencerState,
+    ) -> Result<KakarotSequencer, RunnerError> {
+        let header = &sealed_block.header;
+        let mut sequencer = KakarotSequencer::new(
+            initial_state,
+            environment,
+            header.beneficiary,
+            CHAIN_ID,
+            header.number,
+            header.timestamp,
+        );
+
+        Self::setup_block_state(&mut sequencer, sealed_block)?;
 
+        self.handle_pre_state(&mut sequencer)?;
+
+        Ok(sequencer)
+    }
+
+    /// Starts the block in the sequencer, on top of the state of the previous
+    /// blocks.
+    fn start_block(
+        sequencer: &mut KakarotSequencer,
+        sealed_block: &SealedBlock,
+    ) -> Result<(), RunnerError> {
+        let header = &sealed_block.header;
+        sequencer.start_block(header.beneficiary, header.number, header.timestamp);
+        Self::setup_block_state(sequencer, sealed_block)
+    }
+
+    /// Sets the base fee, prev randao, gas limit and blob base fee of the block
+    /// in Kakarot.
+    fn setup_block_state(
+        sequencer: &mut KakarotSequencer,
+        sealed_block: &SealedBlock,
+    ) -> Result<(), RunnerError> {
+        let header = &sealed_block.header;
+        let prev_randao: U256 = header.mix_hash.into();
+        let base_fee = U256::from(header.base_fee_per_gas.unwrap_or_default());
+        let block_gas_limit = U256::from(header.gas_limit);
+        let blob_base_fee = U256::from(header.blob_fee().unwrap_or_default());
+
+        sequencer.setup_state(base_fee, prev_randao, block_gas_limit, blob_base_fee)?;
+
+        // The blob base fee is derived from the excess blob gas of the header.
+        let stored_blob_base_fee = sequencer.blob_base_fee()?;
+        if stored_blob_base_fee != blob_base_fee {
+            return Err(RunnerError::Other(
+                vec![format!(
+                    "blob base fee mismatch: expected {blob_base_fee}, got {stored_blob_base_fee}"
+                )]
+                .into(),
+            ));
+        }
         Ok(())
     }
-}
 
-#[async_trait]
-impl Case for BlockchainTestCase {
-    fn run(&self) -> Result<(), RunnerError> {
-        // Get gas_used and base_fee from RLP block - as in some cases, the block header is not present in the test data.
-        let sealed_block = SealedBlock::decode(&mut self.block.rlp.as_ref())
-            .map_err(RunnerError::RlpDecodeError)?;
-        let sealed_header = sealed_block.header.clone().unseal();
+    /// Runs the case, recording its metrics as the blocks are executed. The
+    /// metrics collected before a failure or a panic are kept in `metrics`.
+    pub fn run_with_metrics(&self, metrics: &mut CaseMetrics) -> Result<(), RunnerError> {
+        self.execute(metrics)
+    }
 
-        let coinbase_address = sealed_header.beneficiary;
+    /// Executes the blocks of the case in order. Valid blocks are executed on
+    /// top of each other, invalid blocks are checked to be rejected and rolled
+    /// back. The state after the last valid block is then compared to the post
+    /// state of the fixture.
+    fn execute(&self, metrics: &mut CaseMetrics) -> Result<(), RunnerError> {
+        let blocks = self.decode_blocks()?;
+        let executed: Vec<(&Block, &SealedBlock)> = blocks
+            .iter()
+            .filter(|block| block.expected_exception.is_none())
+            .filter_map(|block| Some((block.block, block.sealed_block.as_ref()?)))
+            .collect();
+        metrics.expected_gas_used = Some(
+            executed
+                .iter()
+                .map(|(_, sealed_block)| sealed_block.header.gas_used)
+                .sum(),
+        );
+        let transactions = Self::transactions(&blocks);
+        if self.coverage {
+            metrics.coverage = Some(CaseCoverage::new(&self.pre, &transactions, &[], self.fork));
+        }
 
-        let prev_randao: U256 = sealed_header.mix_hash.into();
-        let base_fee = U256::from(sealed_header.base_fee_per_gas.unwrap_or_default());
-        let block_gas_limit = U256::from(sealed_header.gas_limit);
-        let block_number = U256::from(sealed_header.number);
-        let block_timestamp = U256::from(sealed_block.timestamp);
+        // Undecodable blocks are rejected as expected, without executing anything.
+        let Some(first_block) = blocks.iter().find_map(|block| block.sealed_block.as_ref()) else {
+            return Ok(());
+        };
 
         let kakarot_environment = KakarotEnvironment::new(
             *KAKAROT_ADDRESS,
@@ -249,22 +1008,133 @@ impl Case for BlockchainTestCase {
             *CAIRO1_HELPERS_CLASS_HASH,
             *ACCOUNT_CONTRACT_CLASS_HASH,
         );
-        let mut sequencer = KakarotSequencer::new(
-            INITIAL_SEQUENCER_STATE.clone(),
+        let mut sequencer = self.new_sequencer(
+            first_block,
             kakarot_environment,
-            coinbase_address,
-            CHAIN_ID,
-            block_number.try_into().unwrap_or_default(),
-            block_timestamp.try_into().unwrap_or_default(),
-        );
+            INITIAL_SEQUENCER_STATE.clone(),
+        )?;
 
-        sequencer.setup_state(base_fee, prev_randao, block_gas_limit)?;
+        if self.profile_dir.is_some() {
+            sequencer.enable_profiling(*KAKAROT_CLASS_HASH);
+        }
 
-        self.handle_pre_state(&mut sequencer)?;
+        let mut errors = Vec::new();
+        let mut output = EVMOutput::default();
+        for (index, block) in blocks.iter().enumerate() {
+            let Some(sealed_block) = block.sealed_block.as_ref() else {
+                continue;
+            };
+            if !std::ptr::eq(sealed_block, first_block) {
+                Self::start_block(&mut sequencer, sealed_block)?;
+            }
+
+            match block.expected_exception.as_ref() {
+                Some(expected_exception) => {
+                    let block_errors = self.handle_expected_exception(
+                        &mut sequencer,
+                        sealed_block,
+                        expected_exception,
+                        metrics,
+                    )?;
+                    errors.extend(
+                        block_errors
+                            .into_iter()
+                            .map(|error| format!("block {index}: {error}")),
+                    );
+                }
+                None => {
+                    let block_output =
+                        self.handle_transaction(&mut sequencer, sealed_block, metrics)?;
+                    if let Some(error) =
+                        Self::assert_blob_gas(sealed_block.header.blob_gas_used, &block_output)
+                    {
+                        errors.push(format!("block {index}: {error}"));
+                    }
+                    output.merge(&block_output);
+                }
+            }
+        }
+
+        metrics.gas_used = Some(output.gas_used);
+        if self.coverage {
+            metrics.coverage = Some(CaseCoverage::new(
+                &self.pre,
+                &transactions,
+                &output.traces,
+                self.fork,
+            ));
+        }
+        self.write_traces(&output.traces, None)?;
+        self.write_profile(&sequencer)?;
+
+        let divergences = match (self.reference, executed.as_slice()) {
+            (Some(reference), [(_, sealed_block)]) => {
+                self.handle_differential(&mut sequencer, sealed_block, &output, reference)?
+            }
+            (Some(_), _) => {
+                warn!(
+                    "{}: differential execution is only supported for cases with a single valid block",
+                    self.case_name
+                );
+                Vec::new()
+            }
+            (None, _) => Vec::new(),
+        };
 
-        let output = self.handle_transaction(&mut sequencer)?;
+        // Cases made of invalid blocks only may not provide a post state, their
+        // rejection being checked against the pre state.
+        if self.post.is_some() || !executed.is_empty() {
+            if let Err(err) = self.handle_post_state(&mut sequencer, output, &executed) {
+                errors.extend(err.messages());
+            }
+        }
+        errors.extend(divergences);
 
-        self.handle_post_state(&mut sequencer, output)?;
+        if !errors.is_empty() {
+            return Err(RunnerError::Other(errors.into()));
+        }
         Ok(())
     }
 }
+
+#[async_trait]
+impl Case for BlockchainTestCase {
+    fn run(&self) -> Result<(), RunnerError> {
+        self.execute(&mut CaseMetrics::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::TxEip4844;
+
+    #[test]
+    fn test_rejected_blob_transaction_fails_blob_gas_check() {
+        // Given
+        let transaction = Transaction::Eip4844(TxEip4844 {
+            blob_versioned_hashes: vec![B256::repeat_byte(1), B256::repeat_byte(2)],
+            ..Default::default()
+        });
+        let expected = Some(2 * DATA_GAS_PER_BLOB);
+
+        // When
+        let accepted = EVMOutput {
+            blob_gas_used: BlockchainTestCase::blob_gas_used(&transaction, None),
+            ..Default::default()
+        };
+        let rejected = EVMOutput {
+            blob_gas_used: BlockchainTestCase::blob_gas_used(
+                &transaction,
+                Some(RejectionCategory::Validate),
+            ),
+            ..Default::default()
+        };
+
+        // Then
+        assert_eq!(accepted.blob_gas_used, 2 * DATA_GAS_PER_BLOB);
+        assert_eq!(rejected.blob_gas_used, 0);
+        assert!(BlockchainTestCase::assert_blob_gas(expected, &accepted).is_none());
+        assert!(BlockchainTestCase::assert_blob_gas(expected, &rejected).is_some());
+    }
+}
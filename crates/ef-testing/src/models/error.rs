@@ -22,6 +22,16 @@ pub enum RunnerError {
     Other(Messages),
 }
 
+impl RunnerError {
+    /// Returns the messages of the error, one per failed check.
+    pub fn messages(&self) -> Vec<String> {
+        match self {
+            Self::Other(messages) => messages.0.clone(),
+            err => vec![err.to_string()],
+        }
+    }
+}
+
 pub struct Messages(Vec<String>);
 
 impl std::error::Error for Messages {}
@@ -0,0 +1,184 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use blockifier::transaction::objects::{TransactionExecutionInfo, TransactionExecutionResult};
+use eyre::eyre;
+
+/// Category of rejection of an invalid block or transaction by Kakarot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionCategory {
+    /// The block or one of its transactions could not be RLP decoded.
+    RlpDecode,
+    /// The Starknet transaction failed during validation and was not executed.
+    Validate,
+    /// The Starknet transaction reverted, discarding all EVM state changes.
+    Revert,
+}
+
+impl RejectionCategory {
+    /// Returns the rejection category of the execution result, if the transaction
+    /// was rejected.
+    pub fn from_execution_result(
+        result: &TransactionExecutionResult<TransactionExecutionInfo>,
+    ) -> Option<Self> {
+        match result {
+            Ok(info) if info.revert_error.is_some() => Some(Self::Revert),
+            Ok(_) => None,
+            Err(_) => Some(Self::Validate),
+        }
+    }
+}
+
+impl Display for RejectionCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RlpDecode => write!(f, "rlp decode error"),
+            Self::Validate => write!(f, "validate failure"),
+            Self::Revert => write!(f, "revert"),
+        }
+    }
+}
+
+/// Mapping of the EF exceptions to the rejection category they trigger in Kakarot.
+/// Exceptions are matched against both the execution-spec-tests names
+/// (`TransactionException.INTRINSIC_GAS_TOO_LOW`) and the retesteth names
+/// (`TR_IntrinsicGas`).
+const EXCEPTION_CATEGORIES: &[(&str, RejectionCategory)] = &[
+    // Decoding errors
+    ("RLP_", RejectionCategory::RlpDecode),
+    ("TYPE_NOT_SUPPORTED", RejectionCategory::RlpDecode),
+    ("TR_RLP", RejectionCategory::RlpDecode),
+    ("TR_TypeNotSupported", RejectionCategory::RlpDecode),
+    // Checks performed by Kakarot during `eth_send_transaction`, which revert the Starknet transaction
+    ("INTRINSIC_GAS_TOO_LOW", RejectionCategory::Revert),
+    ("TR_IntrinsicGas", RejectionCategory::Revert),
+    ("NONCE_MISMATCH", RejectionCategory::Revert),
+    ("NONCE_IS_MAX", RejectionCategory::Revert),
+    ("TR_NonceTooHigh", RejectionCategory::Revert),
+    ("TR_NonceTooLow", RejectionCategory::Revert),
+    ("TR_NonceHasMaxValue", RejectionCategory::Revert),
+    ("INSUFFICIENT_ACCOUNT_FUNDS", RejectionCategory::Revert),
+    ("TR_NoFunds", RejectionCategory::Revert),
+    ("INSUFFICIENT_MAX_FEE_PER_GAS", RejectionCategory::Revert),
+    ("TR_FeeCapLessThanBlocks", RejectionCategory::Revert),
+    (
+        "PRIORITY_GREATER_THAN_MAX_FEE_PER_GAS",
+        RejectionCategory::Revert,
+    ),
+    ("TR_TipGtFeeCap", RejectionCategory::Revert),
+    ("GAS_ALLOWANCE_EXCEEDED", RejectionCategory::Revert),
+    ("TR_GasLimitReached", RejectionCategory::Revert),
+    ("GASLIMIT_PRICE_PRODUCT_OVERFLOW", RejectionCategory::Revert),
+    ("INITCODE_SIZE_EXCEEDED", RejectionCategory::Revert),
+    ("TR_InitCodeLimitExceeded", RejectionCategory::Revert),
+    ("SENDER_NOT_EOA", RejectionCategory::Revert),
+    ("SenderNotEOA", RejectionCategory::Revert),
+];
+
+/// An exception expected by the EF fixture for an invalid block, as found in
+/// the `expectException` field of the block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedException {
+    /// The raw exception, as found in the fixture.
+    raw: String,
+    /// The rejection categories accepted for this exception. Fixtures can
+    /// list alternative exceptions separated by a `|`.
+    categories: Vec<RejectionCategory>,
+}
+
+impl ExpectedException {
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn categories(&self) -> &[RejectionCategory] {
+        &self.categories
+    }
+
+    /// Returns true if the rejection category is accepted for this exception.
+    pub fn matches(&self, category: RejectionCategory) -> bool {
+        self.categories.contains(&category)
+    }
+}
+
+impl FromStr for ExpectedException {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut categories = Vec::new();
+        for exception in s.split('|').map(str::trim) {
+            let category = EXCEPTION_CATEGORIES
+                .iter()
+                .find(|(pattern, _)| exception.contains(pattern))
+                .map(|(_, category)| *category)
+                .ok_or_else(|| eyre!("unsupported expected exception: {exception}"))?;
+            if !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+
+        Ok(Self {
+            raw: s.to_string(),
+            categories,
+        })
+    }
+}
+
+impl Display for ExpectedException {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let categories = self
+            .categories
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" or ");
+        write!(f, "{} ({})", self.raw, categories)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_exception_from_str() {
+        // Given
+        let exception = "TransactionException.INTRINSIC_GAS_TOO_LOW";
+
+        // When
+        let expected = ExpectedException::from_str(exception).unwrap();
+
+        // Then
+        assert_eq!(expected.raw(), exception);
+        assert_eq!(expected.categories(), &[RejectionCategory::Revert]);
+    }
+
+    #[test]
+    fn test_expected_exception_from_str_alternatives() {
+        // Given
+        let exception = "TransactionException.TYPE_NOT_SUPPORTED|TR_NoFunds|TR_IntrinsicGas";
+
+        // When
+        let expected = ExpectedException::from_str(exception).unwrap();
+
+        // Then
+        assert_eq!(
+            expected.categories(),
+            &[RejectionCategory::RlpDecode, RejectionCategory::Revert]
+        );
+        assert!(expected.matches(RejectionCategory::Revert));
+        assert!(!expected.matches(RejectionCategory::Validate));
+    }
+
+    #[test]
+    fn test_expected_exception_from_str_unsupported() {
+        // Given
+        let exception = "BlockException.INCORRECT_EXCESS_BLOB_GAS";
+
+        // When
+        let expected = ExpectedException::from_str(exception);
+
+        // Then
+        assert!(expected.is_err());
+    }
+}
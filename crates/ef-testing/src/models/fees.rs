@@ -0,0 +1,400 @@
+use std::str::FromStr;
+
+use alloy_primitives::{Address, U256};
+use build_utils::version::KakarotVersion;
+
+/// Fee flow of the executed Kakarot version, used to reconcile the balances
+/// expected by the EF fixtures with the balances held by Kakarot.
+///
+/// The EF fixtures expect the sender to pay `gas_used * effective_gas_price`,
+/// the base fee to be burnt and the coinbase to receive the priority fee.
+/// Kakarot deviates from this flow in the following ways:
+/// - v0: the coinbase receives all of the fees, including the base fee.
+/// - v1: fees are not charged, so the sender is not debited and the coinbase
+///   receives nothing.
+///
+/// In both versions, the blob fee (EIP-4844) burnt by the EF fixtures is not
+/// charged to the sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeFlow {
+    /// The coinbase receives the base fee on top of the priority fee.
+    CoinbaseReceivesBaseFee,
+    /// No fee is charged.
+    NoFees,
+}
+
+impl FeeFlow {
+    /// Returns the fee flow of the Kakarot version the tests are compiled with.
+    pub const fn current() -> Self {
+        if cfg!(feature = "v1") {
+            Self::NoFees
+        } else {
+            Self::CoinbaseReceivesBaseFee
+        }
+    }
+
+    /// Returns the fee flow of the given Kakarot version.
+    pub const fn for_version(version: KakarotVersion) -> Self {
+        match version {
+            KakarotVersion::V0 => Self::CoinbaseReceivesBaseFee,
+            KakarotVersion::V1 | KakarotVersion::Native => Self::NoFees,
+        }
+    }
+}
+
+/// Tolerance, in wei, allowed on each fee component of every transaction when
+/// comparing the balances held by Kakarot to the balances expected by the EF
+/// fixtures. An address is allowed the tolerance of each component its balance
+/// is adjusted by, for each transaction of the executed blocks: a sender under
+/// [`FeeFlow::NoFees`] is allowed `base_fee + priority_fee + blob_fee` per
+/// transaction it sent. The tolerance is zero by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeTolerance {
+    pub base_fee: U256,
+    pub priority_fee: U256,
+    pub blob_fee: U256,
+}
+
+impl FromStr for FeeTolerance {
+    type Err = eyre::Error;
+
+    /// Parses a comma separated list of `component=wei`, where the component is
+    /// `base`, `priority` or `blob`. Missing components have no tolerance.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tolerance = Self::default();
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (component, wei) = entry.split_once('=').ok_or_else(|| {
+                eyre::eyre!("invalid fee tolerance {entry}, expected component=wei")
+            })?;
+            let wei = U256::from_str(wei.trim())
+                .map_err(|err| eyre::eyre!("invalid fee tolerance {entry}: {err}"))?;
+            match component.trim() {
+                "base" => tolerance.base_fee = wei,
+                "priority" => tolerance.priority_fee = wei,
+                "blob" => tolerance.blob_fee = wei,
+                component => {
+                    return Err(eyre::eyre!(
+                        "unknown fee component {component}, expected base, priority or blob"
+                    ))
+                }
+            }
+        }
+        Ok(tolerance)
+    }
+}
+
+/// Fee components of a transaction, as paid according to the EF fixtures.
+#[derive(Debug, Clone)]
+pub struct TransactionFees {
+    pub sender: Address,
+    pub gas_used: U256,
+    pub effective_gas_price: U256,
+    pub blob_gas_used: U256,
+}
+
+/// Fee components of the transactions of a block, as paid according to the EF
+/// fixtures.
+#[derive(Debug, Clone)]
+pub struct BlockFees {
+    pub coinbase: Address,
+    pub base_fee: U256,
+    pub blob_base_fee: U256,
+    pub transactions: Vec<TransactionFees>,
+}
+
+impl BlockFees {
+    /// Total fee paid by the sender of the transaction.
+    pub fn total_fee(&self, tx: &TransactionFees) -> U256 {
+        tx.gas_used.saturating_mul(tx.effective_gas_price)
+    }
+
+    /// Fee of the transaction burnt by the protocol.
+    pub fn base_fee(&self, tx: &TransactionFees) -> U256 {
+        tx.gas_used.saturating_mul(self.base_fee)
+    }
+
+    /// Blob fee paid by the sender of the transaction and burnt by the protocol.
+    pub fn blob_fee(&self, tx: &TransactionFees) -> U256 {
+        tx.blob_gas_used.saturating_mul(self.blob_base_fee)
+    }
+
+    /// Fee of the transaction received by the coinbase.
+    pub fn priority_fee(&self, tx: &TransactionFees) -> U256 {
+        self.total_fee(tx).saturating_sub(self.base_fee(tx))
+    }
+
+    /// Converts the balance expected by the EF fixture for the given address
+    /// into the balance expected in Kakarot, given its fee flow.
+    pub fn kakarot_balance(&self, flow: FeeFlow, address: &Address, expected: U256) -> U256 {
+        let mut balance = expected;
+        for tx in &self.transactions {
+            if *address == tx.sender {
+                balance = balance.saturating_add(self.blob_fee(tx));
+            }
+            match flow {
+                FeeFlow::CoinbaseReceivesBaseFee => {
+                    if *address == self.coinbase {
+                        balance = balance.saturating_add(self.base_fee(tx));
+                    }
+                }
+                FeeFlow::NoFees => {
+                    if *address == tx.sender {
+                        balance = balance.saturating_add(self.total_fee(tx));
+                    }
+                    if *address == self.coinbase {
+                        balance = balance.saturating_sub(self.priority_fee(tx));
+                    }
+                }
+            }
+        }
+        balance
+    }
+
+    /// Converts the balance held by Kakarot for the given address into the
+    /// balance expected by the EF fixtures, given its fee flow. This is the
+    /// inverse of [`BlockFees::kakarot_balance`].
+    pub fn ef_balance(&self, flow: FeeFlow, address: &Address, actual: U256) -> U256 {
+        let mut balance = actual;
+        for tx in &self.transactions {
+            if *address == tx.sender {
+                balance = balance.saturating_sub(self.blob_fee(tx));
+            }
+            match flow {
+                FeeFlow::CoinbaseReceivesBaseFee => {
+                    if *address == self.coinbase {
+                        balance = balance.saturating_sub(self.base_fee(tx));
+                    }
+                }
+                FeeFlow::NoFees => {
+                    if *address == tx.sender {
+                        balance = balance.saturating_sub(self.total_fee(tx));
+                    }
+                    if *address == self.coinbase {
+                        balance = balance.saturating_add(self.priority_fee(tx));
+                    }
+                }
+            }
+        }
+        balance
+    }
+
+    /// Returns the tolerance allowed on the balance of the given address, from
+    /// the fee components its balance is adjusted by in
+    /// [`BlockFees::kakarot_balance`].
+    pub fn tolerance(&self, flow: FeeFlow, address: &Address, tolerance: &FeeTolerance) -> U256 {
+        let mut allowed = U256::ZERO;
+        for tx in &self.transactions {
+            if *address == tx.sender && tx.blob_gas_used != U256::ZERO {
+                allowed = allowed.saturating_add(tolerance.blob_fee);
+            }
+            match flow {
+                FeeFlow::CoinbaseReceivesBaseFee => {
+                    if *address == self.coinbase {
+                        allowed = allowed.saturating_add(tolerance.base_fee);
+                    }
+                }
+                FeeFlow::NoFees => {
+                    if *address == tx.sender {
+                        allowed = allowed
+                            .saturating_add(tolerance.base_fee)
+                            .saturating_add(tolerance.priority_fee);
+                    }
+                    if *address == self.coinbase {
+                        allowed = allowed.saturating_add(tolerance.priority_fee);
+                    }
+                }
+            }
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+
+    const SENDER: Address = address!("a94f5374fce5edbc8e2a8697c15331677e6ebf0b");
+    const OTHER_SENDER: Address = address!("8a0a19589531694250d570040a0c4b74576919b8");
+    const COINBASE: Address = address!("2adc25665018aa1fe0e6bc666dac8fc2697ff9ba");
+    const OTHER: Address = address!("cccccccccccccccccccccccccccccccccccccccc");
+
+    fn transaction_fees(sender: Address) -> TransactionFees {
+        TransactionFees {
+            sender,
+            gas_used: U256::from(21_000),
+            effective_gas_price: U256::from(12),
+            blob_gas_used: U256::ZERO,
+        }
+    }
+
+    fn block_fees() -> BlockFees {
+        BlockFees {
+            coinbase: COINBASE,
+            base_fee: U256::from(10),
+            blob_base_fee: U256::from(1),
+            transactions: vec![transaction_fees(SENDER)],
+        }
+    }
+
+    #[test]
+    fn test_coinbase_receives_base_fee() {
+        // Given
+        let fees = block_fees();
+        let flow = FeeFlow::CoinbaseReceivesBaseFee;
+
+        // When
+        let coinbase = fees.kakarot_balance(flow, &COINBASE, U256::from(42_000));
+        let sender = fees.kakarot_balance(flow, &SENDER, U256::from(1_000));
+
+        // Then
+        assert_eq!(coinbase, U256::from(42_000 + 210_000));
+        assert_eq!(sender, U256::from(1_000));
+    }
+
+    #[test]
+    fn test_no_fees() {
+        // Given
+        let fees = block_fees();
+        let flow = FeeFlow::NoFees;
+
+        // When
+        let coinbase = fees.kakarot_balance(flow, &COINBASE, U256::from(42_000));
+        let sender = fees.kakarot_balance(flow, &SENDER, U256::from(1_000));
+        let other = fees.kakarot_balance(flow, &OTHER, U256::from(7));
+
+        // Then
+        assert_eq!(coinbase, U256::ZERO);
+        assert_eq!(sender, U256::from(1_000 + 252_000));
+        assert_eq!(other, U256::from(7));
+    }
+
+    #[test]
+    fn test_no_fees_sender_is_coinbase() {
+        // Given
+        let fees = BlockFees {
+            coinbase: SENDER,
+            ..block_fees()
+        };
+
+        // When
+        let balance = fees.kakarot_balance(FeeFlow::NoFees, &SENDER, U256::from(1_000));
+
+        // Then
+        assert_eq!(balance, U256::from(1_000 + 210_000));
+    }
+
+    #[test]
+    fn test_no_fees_per_transaction() {
+        // Given
+        let fees = BlockFees {
+            transactions: vec![
+                transaction_fees(SENDER),
+                TransactionFees {
+                    gas_used: U256::from(50_000),
+                    effective_gas_price: U256::from(20),
+                    ..transaction_fees(OTHER_SENDER)
+                },
+                TransactionFees {
+                    gas_used: U256::from(30_000),
+                    effective_gas_price: U256::from(11),
+                    ..transaction_fees(SENDER)
+                },
+            ],
+            ..block_fees()
+        };
+        let flow = FeeFlow::NoFees;
+
+        // When
+        let sender = fees.kakarot_balance(flow, &SENDER, U256::from(1_000));
+        let other_sender = fees.kakarot_balance(flow, &OTHER_SENDER, U256::from(1_000));
+        let coinbase = fees.kakarot_balance(flow, &COINBASE, U256::from(1_000_000));
+
+        // Then
+        assert_eq!(sender, U256::from(1_000 + 252_000 + 330_000));
+        assert_eq!(other_sender, U256::from(1_000 + 1_000_000));
+        assert_eq!(coinbase, U256::from(1_000_000 - 42_000 - 500_000 - 30_000));
+    }
+
+    #[test]
+    fn test_blob_fee_not_charged() {
+        // Given
+        let fees = BlockFees {
+            transactions: vec![TransactionFees {
+                blob_gas_used: U256::from(131_072),
+                ..transaction_fees(SENDER)
+            }],
+            ..block_fees()
+        };
+        let flow = FeeFlow::CoinbaseReceivesBaseFee;
+
+        // When
+        let sender = fees.kakarot_balance(flow, &SENDER, U256::from(1_000));
+        let coinbase = fees.kakarot_balance(flow, &COINBASE, U256::from(42_000));
+
+        // Then
+        assert_eq!(sender, U256::from(1_000 + 131_072));
+        assert_eq!(coinbase, U256::from(42_000 + 210_000));
+    }
+
+    #[test]
+    fn test_ef_balance_is_inverse_of_kakarot_balance() {
+        // Given
+        let fees = BlockFees {
+            transactions: vec![
+                TransactionFees {
+                    blob_gas_used: U256::from(131_072),
+                    ..transaction_fees(SENDER)
+                },
+                transaction_fees(OTHER_SENDER),
+            ],
+            ..block_fees()
+        };
+
+        for flow in [FeeFlow::CoinbaseReceivesBaseFee, FeeFlow::NoFees] {
+            for address in [SENDER, OTHER_SENDER, COINBASE, OTHER] {
+                // When
+                let expected = U256::from(1_000_000);
+                let kakarot = fees.kakarot_balance(flow, &address, expected);
+
+                // Then
+                assert_eq!(fees.ef_balance(flow, &address, kakarot), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tolerance() {
+        // Given
+        let fees = BlockFees {
+            transactions: vec![
+                TransactionFees {
+                    blob_gas_used: U256::from(131_072),
+                    ..transaction_fees(SENDER)
+                },
+                transaction_fees(SENDER),
+            ],
+            ..block_fees()
+        };
+        let tolerance = FeeTolerance::from_str("base=100, priority=10,blob=1").unwrap();
+
+        // When
+        let sender = fees.tolerance(FeeFlow::NoFees, &SENDER, &tolerance);
+        let coinbase = fees.tolerance(FeeFlow::NoFees, &COINBASE, &tolerance);
+        let other = fees.tolerance(FeeFlow::NoFees, &OTHER, &tolerance);
+        let v0_coinbase = fees.tolerance(FeeFlow::CoinbaseReceivesBaseFee, &COINBASE, &tolerance);
+
+        // Then
+        assert_eq!(sender, U256::from(2 * 110 + 1));
+        assert_eq!(coinbase, U256::from(2 * 10));
+        assert_eq!(other, U256::ZERO);
+        assert_eq!(v0_coinbase, U256::from(2 * 100));
+        assert_eq!(FeeTolerance::from_str("").unwrap(), FeeTolerance::default());
+        assert!(FeeTolerance::from_str("gas=1").is_err());
+        assert!(FeeTolerance::from_str("base").is_err());
+    }
+}
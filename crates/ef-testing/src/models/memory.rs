@@ -0,0 +1,264 @@
+//! Resident memory of the process, read from `/proc/self/status` on Linux and
+//! from the Mach task info on macOS. Other platforms don't report memory.
+//!
+//! The memory is the one of the whole process: the memory of a case run
+//! concurrently with other cases includes theirs. Run the tests on a single
+//! thread to measure the memory of each case on its own.
+//!
+//! On Linux, the peak is reset before each case through `/proc/self/clear_refs`.
+//! macOS doesn't allow resetting it, so the peak is the one since the start of
+//! the process, and only the cases raising it are reported.
+use serde::{Deserialize, Serialize};
+
+const BYTES_PER_MIB: u64 = 1024 * 1024;
+
+/// Resident memory of the process, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    /// Resident set size.
+    pub rss: u64,
+    /// Peak resident set size since the start of the process, or since the last
+    /// reset of the peak.
+    pub peak_rss: u64,
+}
+
+impl MemoryUsage {
+    /// Returns the current memory of the process, if the platform reports it.
+    #[cfg(target_os = "linux")]
+    pub fn current() -> Option<Self> {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        Self::from_proc_status(&status)
+    }
+
+    /// Returns the current memory of the process, if the platform reports it.
+    #[cfg(target_os = "macos")]
+    pub fn current() -> Option<Self> {
+        mach::task_memory().ok()
+    }
+
+    /// Returns the current memory of the process, if the platform reports it.
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub fn current() -> Option<Self> {
+        None
+    }
+
+    /// Resets the peak resident set size of the process to its current resident
+    /// set size, returning false if the platform doesn't support it.
+    #[cfg(target_os = "linux")]
+    pub fn reset_peak() -> bool {
+        // Writing 5 to clear_refs resets VmHWM, see proc(5).
+        std::fs::write("/proc/self/clear_refs", "5").is_ok()
+    }
+
+    /// Resets the peak resident set size of the process to its current resident
+    /// set size, returning false if the platform doesn't support it.
+    #[cfg(not(target_os = "linux"))]
+    pub fn reset_peak() -> bool {
+        false
+    }
+
+    /// Parses the content of `/proc/<pid>/status`, whose `VmRSS` and `VmHWM`
+    /// lines hold the resident set size and its peak, in kB.
+    pub fn from_proc_status(status: &str) -> Option<Self> {
+        let field = |name: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .and_then(|value| value.trim().strip_suffix("kB"))
+                .and_then(|kb| kb.trim().parse::<u64>().ok())
+                .map(|kb| kb * 1024)
+        };
+        Some(Self {
+            rss: field("VmRSS")?,
+            peak_rss: field("VmHWM")?,
+        })
+    }
+}
+
+/// Memory of the process measured around the run of a case, in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaseMemory {
+    /// Resident set size before running the case.
+    pub rss_start: u64,
+    /// Resident set size after running the case.
+    pub rss_end: u64,
+    /// Peak resident set size of the process after running the case, since the
+    /// start of the case if the platform resets the peak.
+    pub peak_rss: u64,
+    /// Growth of the peak resident set size of the process while running the
+    /// case, zero if the case didn't raise the peak.
+    pub peak_rss_growth: u64,
+}
+
+impl CaseMemory {
+    pub fn new(start: MemoryUsage, end: MemoryUsage) -> Self {
+        Self {
+            rss_start: start.rss,
+            rss_end: end.rss,
+            peak_rss: end.peak_rss,
+            peak_rss_growth: end.peak_rss.saturating_sub(start.peak_rss),
+        }
+    }
+
+    /// Returns true if the resident set size after the case exceeds the
+    /// threshold, or if the case raised the peak beyond the threshold. A peak
+    /// raised by a previous case doesn't count, as the peak may not be reset.
+    pub const fn exceeds(&self, threshold: u64) -> bool {
+        self.rss_end > threshold || (self.peak_rss_growth > 0 && self.peak_rss > threshold)
+    }
+
+    /// Returns a warning for the case if its memory exceeds the threshold.
+    pub fn warning(&self, name: &str, threshold: u64) -> Option<String> {
+        self.exceeds(threshold).then(|| {
+            format!(
+                "{name} exceeded the memory threshold of {} MiB: rss {} -> {} MiB, peak {} MiB (+{} MiB)",
+                threshold / BYTES_PER_MIB,
+                self.rss_start / BYTES_PER_MIB,
+                self.rss_end / BYTES_PER_MIB,
+                self.peak_rss / BYTES_PER_MIB,
+                self.peak_rss_growth / BYTES_PER_MIB,
+            )
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod mach {
+    use std::mem;
+
+    use super::MemoryUsage;
+
+    #[link(name = "c")]
+    extern "C" {
+        fn mach_task_self() -> libc::c_uint;
+        fn task_info(
+            task: libc::c_uint,
+            flavor: libc::c_uint,
+            task_info: *mut libc::c_void,
+            inout_count: *mut libc::c_uint,
+        ) -> libc::c_int;
+    }
+
+    const MACH_TASK_BASIC_INFO: libc::c_uint = 20;
+
+    #[repr(C)]
+    struct MachTaskBasicInfo {
+        virtual_size: u64,
+        resident_size: u64,
+        resident_size_max: u64,
+        user_time: u64,
+        system_time: u64,
+        policy: i32,
+        suspend_count: i32,
+    }
+
+    pub fn task_memory() -> Result<MemoryUsage, String> {
+        unsafe {
+            let mut info = MachTaskBasicInfo {
+                virtual_size: 0,
+                resident_size: 0,
+                resident_size_max: 0,
+                user_time: 0,
+                system_time: 0,
+                policy: 0,
+                suspend_count: 0,
+            };
+            let mut count = mem::size_of::<MachTaskBasicInfo>() as libc::c_uint
+                / mem::size_of::<libc::integer_t>() as libc::c_uint;
+            let kr = task_info(
+                mach_task_self(),
+                MACH_TASK_BASIC_INFO,
+                &mut info as *mut _ as *mut libc::c_void,
+                &mut count,
+            );
+            if kr == 0 {
+                Ok(MemoryUsage {
+                    rss: info.resident_size,
+                    peak_rss: info.resident_size_max,
+                })
+            } else {
+                Err(format!("Failed to get task info. Error code: {}", kr))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_proc_status() {
+        // Given
+        let status = "Name:\tef-runner\nVmPeak:\t  900000 kB\nVmHWM:\t  204800 kB\nVmRSS:\t  102400 kB\nThreads:\t8\n";
+
+        // When
+        let usage = MemoryUsage::from_proc_status(status).unwrap();
+
+        // Then
+        assert_eq!(
+            usage,
+            MemoryUsage {
+                rss: 100 * BYTES_PER_MIB,
+                peak_rss: 200 * BYTES_PER_MIB,
+            }
+        );
+        assert_eq!(MemoryUsage::from_proc_status("Name:\tef-runner\n"), None);
+    }
+
+    #[test]
+    fn test_case_memory_exceeds() {
+        // Given
+        let start = MemoryUsage {
+            rss: 100 * BYTES_PER_MIB,
+            peak_rss: 500 * BYTES_PER_MIB,
+        };
+        let raised = CaseMemory::new(
+            start,
+            MemoryUsage {
+                rss: 120 * BYTES_PER_MIB,
+                peak_rss: 800 * BYTES_PER_MIB,
+            },
+        );
+        let kept = CaseMemory::new(start, start);
+
+        // When / Then
+        assert_eq!(raised.peak_rss_growth, 300 * BYTES_PER_MIB);
+        assert!(raised.exceeds(600 * BYTES_PER_MIB));
+        assert!(!kept.exceeds(400 * BYTES_PER_MIB));
+        assert!(kept.exceeds(50 * BYTES_PER_MIB));
+        assert_eq!(
+            raised.warning("test_a", 600 * BYTES_PER_MIB).unwrap(),
+            "test_a exceeded the memory threshold of 600 MiB: rss 100 -> 120 MiB, peak 800 MiB (+300 MiB)"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_current() {
+        // When
+        let usage = MemoryUsage::current().unwrap();
+
+        // Then
+        assert!(usage.rss > 0);
+        assert!(usage.peak_rss >= usage.rss);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_reset_peak() {
+        // Given
+        // Raise the peak with a buffer released before the reset.
+        let buffer = vec![1u8; 64 * BYTES_PER_MIB as usize];
+        drop(std::hint::black_box(buffer));
+        let before = MemoryUsage::current().unwrap();
+
+        // When
+        let reset = MemoryUsage::reset_peak();
+
+        // Then
+        let after = MemoryUsage::current().unwrap();
+        assert!(reset);
+        assert!(after.peak_rss < before.peak_rss);
+    }
+}
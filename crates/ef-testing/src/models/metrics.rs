@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use blockifier::transaction::objects::TransactionExecutionInfo;
+use serde::{Deserialize, Serialize};
+
+use crate::coverage::CaseCoverage;
+use crate::models::memory::CaseMemory;
+
+/// Cairo resources used to execute the transactions of a case.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CairoResources {
+    pub steps: usize,
+    pub memory_holes: usize,
+    /// Number of instances of each builtin, indexed by builtin name
+    /// (e.g. `range_check_builtin`).
+    pub builtins: BTreeMap<String, usize>,
+    /// L1 gas consumed by the transactions.
+    pub l1_gas: u64,
+    /// Gas consumed by the data availability of the state diff, either as L1
+    /// gas or as L1 data gas.
+    pub da_gas: u64,
+    /// Number of entries of the state diff: storage updates, class hash updates
+    /// and modified contracts.
+    pub state_diff_size: usize,
+}
+
+impl CairoResources {
+    /// Adds the resources used by the other execution.
+    pub fn merge(&mut self, other: &Self) {
+        self.steps += other.steps;
+        self.memory_holes += other.memory_holes;
+        for (builtin, count) in other.builtins.iter() {
+            *self.builtins.entry(builtin.clone()).or_default() += count;
+        }
+        self.l1_gas += other.l1_gas;
+        self.da_gas += other.da_gas;
+        self.state_diff_size += other.state_diff_size;
+    }
+}
+
+impl From<&TransactionExecutionInfo> for CairoResources {
+    fn from(info: &TransactionExecutionInfo) -> Self {
+        let receipt = &info.receipt;
+        let vm_resources = &receipt.resources.computation.vm_resources;
+        let state_changes = &receipt
+            .resources
+            .starknet_resources
+            .state
+            .state_changes_for_fee
+            .state_changes_count;
+        Self {
+            steps: vm_resources.n_steps,
+            memory_holes: vm_resources.n_memory_holes,
+            builtins: vm_resources
+                .builtin_instance_counter
+                .iter()
+                .map(|(builtin, count)| (builtin.to_str_with_suffix().to_string(), *count))
+                .collect(),
+            l1_gas: receipt.gas.l1_gas.0,
+            da_gas: receipt.da_gas.l1_gas.0 + receipt.da_gas.l1_data_gas.0,
+            state_diff_size: state_changes.n_storage_updates
+                + state_changes.n_class_hash_updates
+                + state_changes.n_compiled_class_hash_updates
+                + state_changes.n_modified_contracts,
+        }
+    }
+}
+
+/// Metrics collected while running a case.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaseMetrics {
+    /// Gas used by the block, according to the fixture.
+    pub expected_gas_used: Option<u64>,
+    /// Gas used by Kakarot.
+    pub gas_used: Option<u64>,
+    /// Cairo resources used by the executed transactions, if any was executed.
+    pub resources: Option<CairoResources>,
+    /// Opcodes and precompiles covered by the case, if collected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<CaseCoverage>,
+    /// Memory of the process measured around the run of the case, on the
+    /// platforms reporting it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<CaseMemory>,
+}
+
+impl CaseMetrics {
+    /// Records the resources used by an executed transaction.
+    pub fn record_execution(&mut self, info: &TransactionExecutionInfo) {
+        self.resources
+            .get_or_insert_with(Default::default)
+            .merge(&CairoResources::from(info));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_resources() {
+        // Given
+        let mut resources = CairoResources {
+            steps: 10,
+            memory_holes: 1,
+            builtins: BTreeMap::from([("range_check_builtin".to_string(), 2)]),
+            l1_gas: 100,
+            da_gas: 10,
+            state_diff_size: 3,
+        };
+        let other = CairoResources {
+            steps: 5,
+            memory_holes: 0,
+            builtins: BTreeMap::from([
+                ("range_check_builtin".to_string(), 3),
+                ("poseidon_builtin".to_string(), 1),
+            ]),
+            l1_gas: 0,
+            da_gas: 5,
+            state_diff_size: 2,
+        };
+
+        // When
+        resources.merge(&other);
+
+        // Then
+        assert_eq!(resources.steps, 15);
+        assert_eq!(resources.memory_holes, 1);
+        assert_eq!(resources.builtins["range_check_builtin"], 5);
+        assert_eq!(resources.builtins["poseidon_builtin"], 1);
+        assert_eq!(resources.l1_gas, 100);
+        assert_eq!(resources.da_gas, 15);
+        assert_eq!(resources.state_diff_size, 5);
+    }
+}
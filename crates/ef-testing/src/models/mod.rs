@@ -1,10 +1,20 @@
 pub mod case;
 pub mod error;
+pub mod exception;
+pub mod fees;
+pub mod memory;
+pub mod metrics;
 pub mod result;
+pub mod state_case;
 
+pub use build_utils::fork::Fork;
+
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
+use alloy_eips::eip2930::AccessList;
 use alloy_primitives::{Address, Bytes, B256, U256, U64};
+use ef_tests::models::State;
 use serde::{self, de, Deserialize, Deserializer};
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
@@ -35,6 +45,73 @@ pub struct Transaction {
     pub to: Option<Address>,
     pub sender: Address,
     pub value: Vec<String>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    #[serde(default)]
+    pub access_lists: Vec<Option<AccessList>>,
+    /// Versioned hashes of the blobs of a blob transaction (EIP-4844).
+    pub blob_versioned_hashes: Option<Vec<B256>>,
+    pub max_fee_per_blob_gas: Option<U256>,
+}
+
+impl Transaction {
+    /// Returns the value at the given index, parsing it from its hex representation.
+    pub fn value_at(&self, index: usize) -> Result<U256, eyre::Error> {
+        let value = self
+            .value
+            .get(index)
+            .ok_or_else(|| eyre::eyre!("value index {index} out of bounds"))?;
+        // Some fillers encode a zero value as "0x"
+        if value == "0x" {
+            return Ok(U256::ZERO);
+        }
+        Ok(U256::from_str(value)?)
+    }
+}
+
+/// A test from the `GeneralStateTests` folder. Each test describes
+/// multiple transactions, built from the `data`, `gasLimit` and
+/// `value` arrays of the transaction.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct StateTest {
+    pub env: Env,
+    pub pre: State,
+    pub transaction: Transaction,
+    /// Mapping from the fork name to the expected post states.
+    pub post: BTreeMap<String, Vec<PostStateEntry>>,
+}
+
+/// Block environment of a state test.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Env {
+    pub current_coinbase: Address,
+    pub current_gas_limit: U256,
+    pub current_number: U256,
+    pub current_timestamp: U256,
+    pub current_base_fee: Option<U256>,
+    pub current_random: Option<B256>,
+    pub current_excess_blob_gas: Option<U256>,
+}
+
+/// Expected post state of a state test, for a given set of indexes.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostStateEntry {
+    /// The expected state root.
+    pub hash: B256,
+    /// The expected hash of the RLP encoded logs.
+    pub logs: B256,
+    pub indexes: Indexes,
+    pub expect_exception: Option<String>,
+}
+
+/// Indexes in the `data`, `gasLimit` and `value` arrays of a state test transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Indexes {
+    pub data: usize,
+    pub gas: usize,
+    pub value: usize,
 }
 
 #[cfg(test)]
@@ -62,4 +139,47 @@ mod tests {
         // Check if deserialization was successful
         assert!(result.is_ok(), "Failed to deserialize: {:?}", result.err());
     }
+
+    #[test]
+    fn test_blob_transaction_deserialization() {
+        let data = r#"
+        {
+            "data": ["0x"],
+            "gasLimit": ["0x5208"],
+            "maxFeePerGas": "0x0a",
+            "maxPriorityFeePerGas": "0x01",
+            "maxFeePerBlobGas": "0x64",
+            "blobVersionedHashes": ["0x0100000000000000000000000000000000000000000000000000000000000000"],
+            "nonce": "0x00",
+            "secretKey": "0x0000000000000000000000000000000000000000000000000000000000000123",
+            "sender": "0x00000000000000000000000000000000deadbeef",
+            "to": "0x00000000000000000000000000000000deadbeef",
+            "value": ["0x00"]
+        }
+        "#;
+
+        let transaction: Transaction = serde_json::from_str(data).unwrap();
+
+        assert_eq!(
+            transaction.blob_versioned_hashes.map(|hashes| hashes.len()),
+            Some(1)
+        );
+        assert_eq!(transaction.max_fee_per_blob_gas, Some(U256::from(100)));
+    }
+
+    #[test]
+    fn test_state_test_deserialization() {
+        let data = std::fs::read_to_string(
+            "ethereum-tests/GeneralStateTests/VmTests/vmArithmeticTest/add.json",
+        )
+        .unwrap();
+
+        // Attempt to deserialize the JSON data
+        let result: BTreeMap<String, StateTest> = serde_json::from_str(&data).unwrap();
+
+        // Check the indexed variants were deserialized
+        let test = result.get("add").unwrap();
+        assert_eq!(test.post.get("Cancun").map(Vec::len), Some(5));
+        assert_eq!(test.transaction.value_at(0).unwrap(), U256::from(1));
+    }
 }
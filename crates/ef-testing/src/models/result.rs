@@ -1,12 +1,21 @@
+use alloy_primitives::{Address, Bytes, Log, B256, U256};
 use blockifier::{
     execution::call_info::CallInfo,
+    state::state_api::StateReader as _,
     transaction::objects::{TransactionExecutionInfo, TransactionExecutionResult},
 };
 use eyre::{eyre, Result};
 use starknet::macros::selector;
+use starknet_api::abi::abi_utils::get_storage_var_address;
 use starknet_api::transaction::{EventContent, EventData};
 use tracing::{error, info, warn};
 
+use crate::evm_sequencer::{
+    constants::storage_variables::ACCOUNT_EVM_ADDRESS, sequencer::KakarotSequencer,
+};
+use crate::models::metrics::CairoResources;
+use crate::trace::Trace;
+
 use std::convert::TryFrom;
 
 #[derive(Debug)]
@@ -14,6 +23,15 @@ pub struct EVMOutput {
     pub return_data: Vec<u8>,
     pub gas_used: u64,
     pub success: bool,
+    /// Blob gas used by the accepted transactions (EIP-4844).
+    pub blob_gas_used: u64,
+    /// Logs emitted by the transactions, only collected when comparing the
+    /// execution to a reference.
+    pub logs: Vec<Log>,
+    /// Traces of the transactions, only collected when tracing the execution.
+    pub traces: Vec<Trace>,
+    /// Gas used by each transaction, in the order of execution.
+    pub transactions_gas_used: Vec<u64>,
 }
 
 impl EVMOutput {
@@ -21,6 +39,11 @@ impl EVMOutput {
         self.return_data.extend_from_slice(&other.return_data);
         self.gas_used += other.gas_used;
         self.success &= other.success;
+        self.blob_gas_used += other.blob_gas_used;
+        self.logs.extend_from_slice(&other.logs);
+        self.traces.extend_from_slice(&other.traces);
+        self.transactions_gas_used
+            .extend_from_slice(&other.transactions_gas_used);
     }
 }
 
@@ -30,6 +53,10 @@ impl Default for EVMOutput {
             return_data: vec![],
             gas_used: 0,
             success: true,
+            blob_gas_used: 0,
+            logs: Vec::new(),
+            traces: Vec::new(),
+            transactions_gas_used: Vec::new(),
         }
     }
 }
@@ -74,66 +101,14 @@ impl TryFrom<&EventData> for EVMOutput {
             return_data,
             gas_used,
             success: success == 1,
+            blob_gas_used: 0,
+            logs: Vec::new(),
+            traces: Vec::new(),
+            transactions_gas_used: Vec::new(),
         })
     }
 }
 
-#[cfg(target_os = "macos")]
-mod debug_ram {
-    use std::mem;
-
-    #[link(name = "c")]
-    extern "C" {
-        fn mach_task_self() -> libc::c_uint;
-        fn task_info(
-            task: libc::c_uint,
-            flavor: libc::c_uint,
-            task_info: *mut libc::c_void,
-            inout_count: *mut libc::c_uint,
-        ) -> libc::c_int;
-    }
-
-    const MACH_TASK_BASIC_INFO: libc::c_uint = 20;
-
-    #[repr(C)]
-    struct MachTaskBasicInfo {
-        virtual_size: u64,
-        resident_size: u64,
-        resident_size_max: u64,
-        user_time: u64,
-        system_time: u64,
-        policy: i32,
-        suspend_count: i32,
-    }
-
-    pub fn debug_ram() -> Result<usize, String> {
-        unsafe {
-            let mut info = MachTaskBasicInfo {
-                virtual_size: 0,
-                resident_size: 0,
-                resident_size_max: 0,
-                user_time: 0,
-                system_time: 0,
-                policy: 0,
-                suspend_count: 0,
-            };
-            let mut count = mem::size_of::<MachTaskBasicInfo>() as libc::c_uint
-                / mem::size_of::<libc::integer_t>() as libc::c_uint;
-            let kr = task_info(
-                mach_task_self(),
-                MACH_TASK_BASIC_INFO,
-                &mut info as *mut _ as *mut libc::c_void,
-                &mut count,
-            );
-            if kr == 0 {
-                Ok(info.resident_size as usize)
-            } else {
-                Err(format!("Failed to get task info. Error code: {}", kr))
-            }
-        }
-    }
-}
-
 #[allow(clippy::cognitive_complexity)]
 pub(crate) fn extract_output_and_log_execution_result(
     result: &TransactionExecutionResult<TransactionExecutionInfo>,
@@ -148,11 +123,11 @@ pub(crate) fn extract_output_and_log_execution_result(
                 return None;
             }
 
-            info!("{} passed: {:?}", case, info.receipt.resources);
-            #[cfg(target_os = "macos")]
-            {
-                println!("Current memory usage: {:?} bytes", debug_ram::debug_ram());
-            }
+            info!(
+                "{} passed: {}",
+                case,
+                serde_json::to_string(&CairoResources::from(info)).unwrap_or_default()
+            );
             if let Some(call) = info.execute_call_info.as_ref() {
                 use starknet::core::types::Felt;
                 let events = kakarot_execution_events(call);
@@ -183,6 +158,61 @@ pub(crate) fn extract_output_and_log_execution_result(
     }
 }
 
+/// Extracts the EVM logs emitted during the execution of a Kakarot transaction.
+///
+/// EVM logs are emitted as Starknet events whose keys are the topics, each split
+/// into a low and a high part, and whose data holds one byte per felt. Events
+/// emitted by an account contract are attributed to the account's EVM address.
+/// Events emitted by the Kakarot contract itself carry the EVM address as their
+/// first key.
+pub(crate) fn kakarot_logs(
+    sequencer: &mut KakarotSequencer,
+    call_info: &CallInfo,
+) -> Result<Vec<Log>> {
+    let kakarot_address = sequencer.environment.kakarot_address;
+    let mut logs = Vec::new();
+    for c in call_info.iter() {
+        for event in c.execution.events.iter().map(|e| &e.event) {
+            if event.keys.first().map(|k| k.0) == Some(selector!("transaction_executed")) {
+                continue;
+            }
+            let mut keys = event.keys.iter().map(|k| k.0);
+            let emitter = c.call.storage_address;
+            let evm_address = if emitter == kakarot_address {
+                keys.next()
+                    .ok_or_else(|| eyre!("Missing evm address in Kakarot event"))?
+            } else {
+                sequencer
+                    .state_mut()
+                    .get_storage_at(emitter, get_storage_var_address(ACCOUNT_EVM_ADDRESS, &[]))?
+            };
+            let address = Address::from_slice(&evm_address.to_bytes_be()[12..]);
+
+            let keys: Vec<_> = keys.collect();
+            let topics = keys
+                .chunks(2)
+                .map(|chunk| {
+                    let low = U256::from_be_bytes(chunk[0].to_bytes_be());
+                    let high = chunk
+                        .get(1)
+                        .map(|high| U256::from_be_bytes(high.to_bytes_be()))
+                        .unwrap_or_default();
+                    B256::from(high << 128 | low)
+                })
+                .collect();
+            let data: Bytes = event
+                .data
+                .0
+                .iter()
+                .flat_map(|felt| felt.to_bytes_be().last().cloned())
+                .collect();
+
+            logs.push(Log::new_unchecked(address, topics, data));
+        }
+    }
+    Ok(logs)
+}
+
 #[allow(dead_code)]
 fn kakarot_execution_events(call_info: &CallInfo) -> Vec<EventContent> {
     let mut events = Vec::new();
@@ -0,0 +1,459 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr as _;
+
+use alloy_consensus::{TxEip1559, TxEip2930, TxEip4844, TxLegacy};
+use alloy_eips::eip4844::{calc_blob_gasprice, DATA_GAS_PER_BLOB};
+use alloy_primitives::{keccak256, Address, TxKind, B256, U256};
+use async_trait::async_trait;
+use ef_tests::models::Account;
+use reth_primitives::{sign_message, Transaction as RethTransaction, TransactionSigned};
+use revm::primitives::BlockEnv;
+
+use super::error::RunnerError;
+use super::exception::{ExpectedException, RejectionCategory};
+use super::fees::{BlockFees, FeeFlow, TransactionFees};
+use super::result::{extract_output_and_log_execution_result, kakarot_logs};
+use super::{Fork, Indexes, PostStateEntry, StateTest};
+use crate::differential;
+use crate::evm_sequencer::account::KakarotAccount;
+use crate::evm_sequencer::constants::{
+    ACCOUNT_CONTRACT_CLASS_HASH, CAIRO1_HELPERS_CLASS_HASH, CHAIN_ID, KAKAROT_ADDRESS,
+    UNINITIALIZED_ACCOUNT_CLASS_HASH,
+};
+use crate::evm_sequencer::evm_state::Evm;
+use crate::evm_sequencer::sequencer::{
+    KakarotEnvironment, KakarotSequencer, INITIAL_SEQUENCER_STATE,
+};
+use crate::traits::Case;
+use crate::utils::state_root;
+
+/// Number of storage slots, starting from slot 0, read from each account when
+/// rebuilding the post state. Kakarot stores EVM storage under hashed Starknet
+/// keys, so the written slots are mapped back from candidates: the slots of the
+/// pre-state, the slots accessed by revm executing the same transaction, and this
+/// range.
+const STORAGE_SLOT_CANDIDATES: u64 = 256;
+
+/// A test case from the `GeneralStateTests` folder. The case is expanded into one
+/// transaction per `(data, gas, value)` indexes combination found in the post
+/// state of the fork, each executed on a fresh sequencer.
+#[derive(Debug)]
+pub struct StateTestCase {
+    case_name: String,
+    case_category: String,
+    fork: Fork,
+    test: StateTest,
+    /// Name of the variants which should not be executed, formatted as
+    /// `{case_name}_d{data}g{gas}v{value}_{fork}`.
+    skipped_variants: Vec<String>,
+}
+
+impl StateTestCase {
+    pub const fn new(
+        case_name: String,
+        case_category: String,
+        fork: Fork,
+        test: StateTest,
+        skipped_variants: Vec<String>,
+    ) -> Self {
+        Self {
+            case_name,
+            case_category,
+            fork,
+            test,
+            skipped_variants,
+        }
+    }
+
+    /// Returns the name of the variant of the case for the given indexes.
+    pub fn variant_name(case_name: &str, indexes: &Indexes, fork: Fork) -> String {
+        format!(
+            "{}_d{}g{}v{}_{}",
+            case_name, indexes.data, indexes.gas, indexes.value, fork
+        )
+    }
+
+    fn new_sequencer(&self) -> Result<KakarotSequencer, RunnerError> {
+        let env = &self.test.env;
+        let kakarot_environment = KakarotEnvironment::new(
+            *KAKAROT_ADDRESS,
+            *UNINITIALIZED_ACCOUNT_CLASS_HASH,
+            *CAIRO1_HELPERS_CLASS_HASH,
+            *ACCOUNT_CONTRACT_CLASS_HASH,
+        );
+        let mut sequencer = KakarotSequencer::new(
+            INITIAL_SEQUENCER_STATE.clone(),
+            kakarot_environment,
+            env.current_coinbase,
+            CHAIN_ID,
+            env.current_number.try_into().unwrap_or_default(),
+            env.current_timestamp.try_into().unwrap_or_default(),
+        );
+
+        let base_fee = env.current_base_fee.unwrap_or_default();
+        let prev_randao: U256 = env.current_random.unwrap_or_default().into();
+        sequencer.setup_state(
+            base_fee,
+            prev_randao,
+            env.current_gas_limit,
+            self.blob_base_fee(),
+        )?;
+
+        for (address, account) in self.test.pre.iter() {
+            let kakarot_account =
+                KakarotAccount::with_codec(address, account.clone(), &sequencer.account_codec())?;
+            sequencer.setup_account(kakarot_account)?;
+            sequencer.fund(address, account.balance)?;
+        }
+
+        Ok(sequencer)
+    }
+
+    /// Builds and signs the transaction for the given indexes.
+    fn transaction(&self, indexes: &Indexes) -> Result<TransactionSigned, RunnerError> {
+        let tx = &self.test.transaction;
+        let input = tx
+            .data
+            .get(indexes.data)
+            .cloned()
+            .ok_or_else(|| eyre::eyre!("data index {} out of bounds", indexes.data))?;
+        let gas_limit: u64 = tx
+            .gas_limit
+            .get(indexes.gas)
+            .ok_or_else(|| eyre::eyre!("gas index {} out of bounds", indexes.gas))?
+            .to();
+        let value = tx.value_at(indexes.value)?;
+        let to = tx.to.map_or(TxKind::Create, TxKind::Call);
+        let nonce: u64 = tx.nonce.to();
+        let access_list = tx.access_lists.get(indexes.data).cloned().flatten();
+
+        let transaction = match (tx.gas_price, tx.max_fee_per_gas, access_list) {
+            // Blob transactions can't create contracts (EIP-4844).
+            (None, Some(max_fee_per_gas), access_list) if tx.blob_versioned_hashes.is_some() => {
+                let to = tx
+                    .to
+                    .ok_or_else(|| eyre::eyre!("blob transaction without a destination"))?;
+                RethTransaction::Eip4844(TxEip4844 {
+                    chain_id: CHAIN_ID,
+                    nonce,
+                    gas_limit,
+                    max_fee_per_gas: max_fee_per_gas.saturating_to(),
+                    max_priority_fee_per_gas: tx
+                        .max_priority_fee_per_gas
+                        .unwrap_or_default()
+                        .saturating_to(),
+                    to,
+                    value,
+                    access_list: access_list.unwrap_or_default(),
+                    blob_versioned_hashes: tx.blob_versioned_hashes.clone().unwrap_or_default(),
+                    max_fee_per_blob_gas: tx
+                        .max_fee_per_blob_gas
+                        .unwrap_or_default()
+                        .saturating_to(),
+                    input,
+                })
+            }
+            (Some(_), _, _) if tx.blob_versioned_hashes.is_some() => {
+                return Err(RunnerError::Other(
+                    vec!["blob transaction with a gas_price".to_string()].into(),
+                ))
+            }
+            (Some(gas_price), _, None) => RethTransaction::Legacy(TxLegacy {
+                chain_id: Some(CHAIN_ID),
+                nonce,
+                gas_price: gas_price.saturating_to(),
+                gas_limit,
+                to,
+                value,
+                input,
+            }),
+            (Some(gas_price), _, Some(access_list)) => RethTransaction::Eip2930(TxEip2930 {
+                chain_id: CHAIN_ID,
+                nonce,
+                gas_price: gas_price.saturating_to(),
+                gas_limit,
+                to,
+                value,
+                access_list,
+                input,
+            }),
+            (None, Some(max_fee_per_gas), access_list) => RethTransaction::Eip1559(TxEip1559 {
+                chain_id: CHAIN_ID,
+                nonce,
+                gas_limit,
+                max_fee_per_gas: max_fee_per_gas.saturating_to(),
+                max_priority_fee_per_gas: tx
+                    .max_priority_fee_per_gas
+                    .unwrap_or_default()
+                    .saturating_to(),
+                to,
+                value,
+                access_list: access_list.unwrap_or_default(),
+                input,
+            }),
+            (None, None, _) => {
+                return Err(RunnerError::Other(
+                    vec!["transaction has neither gas_price nor max_fee_per_gas".to_string()]
+                        .into(),
+                ))
+            }
+        };
+
+        let signature = sign_message(tx.secret_key, transaction.signature_hash())
+            .map_err(|err| RunnerError::Other(vec![err.to_string()].into()))?;
+        Ok(TransactionSigned::from_transaction_and_signature(
+            transaction,
+            signature,
+        ))
+    }
+
+    /// Returns the blob base fee of the environment (EIP-4844).
+    fn blob_base_fee(&self) -> U256 {
+        self.test
+            .env
+            .current_excess_blob_gas
+            .map(|excess_blob_gas| U256::from(calc_blob_gasprice(excess_blob_gas.saturating_to())))
+            .unwrap_or_default()
+    }
+
+    /// Returns the fees paid according to the EF specification, for the given gas
+    /// and blob gas used.
+    fn block_fees(&self, gas_used: u64, blob_gas_used: u64) -> BlockFees {
+        let tx = &self.test.transaction;
+        let base_fee = self.test.env.current_base_fee.unwrap_or_default();
+        let effective_gas_price = tx.gas_price.unwrap_or_else(|| {
+            let max_fee_per_gas = tx.max_fee_per_gas.unwrap_or_default();
+            let max_priority_fee_per_gas = tx.max_priority_fee_per_gas.unwrap_or_default();
+            max_priority_fee_per_gas.min(max_fee_per_gas.saturating_sub(base_fee)) + base_fee
+        });
+        BlockFees {
+            coinbase: self.test.env.current_coinbase,
+            base_fee,
+            blob_base_fee: self.blob_base_fee(),
+            transactions: vec![TransactionFees {
+                sender: tx.sender,
+                gas_used: U256::from(gas_used),
+                effective_gas_price,
+                blob_gas_used: U256::from(blob_gas_used),
+            }],
+        }
+    }
+
+    /// Returns the block environment of the case, for revm.
+    fn block_env(&self) -> BlockEnv {
+        let env = &self.test.env;
+        let mut block_env = BlockEnv {
+            number: env.current_number,
+            coinbase: env.current_coinbase,
+            timestamp: env.current_timestamp,
+            gas_limit: env.current_gas_limit,
+            basefee: env.current_base_fee.unwrap_or_default(),
+            difficulty: U256::ZERO,
+            prevrandao: env.current_random,
+            blob_excess_gas_and_price: None,
+        };
+        if let Some(excess_blob_gas) = env.current_excess_blob_gas {
+            block_env.set_blob_excess_gas_and_price(excess_blob_gas.saturating_to());
+        }
+        block_env
+    }
+
+    /// Returns the storage slots of each account accessed by revm executing the
+    /// transaction from the pre-state.
+    fn accessed_slots(
+        &self,
+        transaction: &TransactionSigned,
+    ) -> Result<BTreeMap<Address, BTreeSet<U256>>, RunnerError> {
+        let snapshot = differential::revm::execute_transactions(
+            &self.test.pre,
+            self.block_env(),
+            std::slice::from_ref(transaction),
+            self.fork,
+            CHAIN_ID,
+        )?;
+        Ok(snapshot
+            .accounts
+            .into_iter()
+            .map(|(address, account)| (address, account.storage.into_keys().collect()))
+            .collect())
+    }
+
+    /// Rebuilds the post state from the Kakarot state, as expected by the EF
+    /// specification. Empty accounts are not part of the post state. Returns
+    /// the post state along with the accounts whose storage holds entries which
+    /// couldn't be mapped back to one of the candidate slots, in which case the
+    /// post state is incomplete.
+    fn post_state(
+        &self,
+        sequencer: &mut KakarotSequencer,
+        fees: &BlockFees,
+        accessed_slots: &BTreeMap<Address, BTreeSet<U256>>,
+    ) -> Result<(BTreeMap<Address, Account>, Vec<String>), RunnerError> {
+        let mut addresses: BTreeSet<Address> = sequencer.accounts()?.into_iter().collect();
+        addresses.extend(self.test.pre.keys());
+        addresses.extend(fees.transactions.iter().map(|tx| tx.sender));
+        addresses.insert(fees.coinbase);
+
+        let candidates: BTreeSet<U256> = self
+            .test
+            .pre
+            .values()
+            .flat_map(|account| account.storage.keys().copied())
+            .chain((0..STORAGE_SLOT_CANDIDATES).map(U256::from))
+            .collect();
+
+        let fee_flow = FeeFlow::current();
+        let mut post_state = BTreeMap::new();
+        let mut unmapped = Vec::new();
+        for address in addresses {
+            let nonce = sequencer.nonce_at(&address)?;
+            let code = sequencer.code_at(&address)?;
+            let balance = fees.ef_balance(fee_flow, &address, sequencer.balance_at(&address)?);
+            if nonce.is_zero() && code.is_empty() && balance.is_zero() {
+                continue;
+            }
+
+            let mut slots = candidates.clone();
+            slots.extend(accessed_slots.get(&address).into_iter().flatten());
+            let unmapped_entries = sequencer.unmapped_storage(&address, &slots)?;
+            if unmapped_entries > 0 {
+                unmapped.push(format!(
+                    "{unmapped_entries} storage entries of {address:#20x} couldn't be mapped back to an EVM slot"
+                ));
+            }
+
+            let mut storage = BTreeMap::new();
+            for slot in slots.iter() {
+                let value = sequencer.storage_at(&address, *slot)?;
+                if !value.is_zero() {
+                    storage.insert(*slot, value);
+                }
+            }
+
+            post_state.insert(
+                address,
+                Account {
+                    nonce,
+                    balance,
+                    code,
+                    storage,
+                },
+            );
+        }
+
+        Ok((post_state, unmapped))
+    }
+
+    /// Runs the variant of the case for the given post state entry. Returns the
+    /// list of mismatches.
+    fn run_variant(&self, entry: &PostStateEntry) -> Result<Vec<String>, RunnerError> {
+        let mut sequencer = self.new_sequencer()?;
+        let transaction = self.transaction(&entry.indexes)?;
+        let accessed_slots = match self.accessed_slots(&transaction) {
+            Ok(accessed_slots) => accessed_slots,
+            // A transaction rejected as expected doesn't write any slot.
+            Err(_) if entry.expect_exception.is_some() => BTreeMap::new(),
+            Err(err) => {
+                return Err(RunnerError::Other(
+                    vec![format!("reference failed: {err}")].into(),
+                ))
+            }
+        };
+        let blob_count = match &transaction.transaction {
+            RethTransaction::Eip4844(tx) => tx.blob_versioned_hashes.len() as u64,
+            _ => 0,
+        };
+        let execution_result = sequencer.execute_transaction(transaction);
+        let output = extract_output_and_log_execution_result(
+            &execution_result,
+            &self.case_name,
+            &self.case_category,
+        );
+
+        let mut errors = Vec::new();
+        if let Some(expected_exception) = entry.expect_exception.as_deref() {
+            let expected_exception = ExpectedException::from_str(expected_exception)?;
+            match RejectionCategory::from_execution_result(&execution_result) {
+                None => errors.push(format!(
+                    "expected exception {expected_exception}, but the transaction was accepted"
+                )),
+                Some(category) if !expected_exception.matches(category) => errors.push(format!(
+                    "expected exception {expected_exception}, got {category}"
+                )),
+                Some(_) => (),
+            }
+        }
+
+        let logs = match (execution_result.as_ref(), output.as_ref()) {
+            (Ok(info), Some(_)) => match info.execute_call_info.as_ref() {
+                Some(call_info) => kakarot_logs(&mut sequencer, call_info)?,
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+        let logs_hash = keccak256(alloy_rlp::encode(&logs));
+        if logs_hash != entry.logs {
+            errors.push(format!(
+                "logs hash mismatch: expected {:#x}, got {:#x}",
+                entry.logs, logs_hash
+            ));
+        }
+
+        let gas_used = output.map(|output| output.gas_used).unwrap_or_default();
+        // Blob gas is only charged for the transactions accepted by Kakarot.
+        let blob_gas_used = if RejectionCategory::from_execution_result(&execution_result).is_none()
+        {
+            blob_count * DATA_GAS_PER_BLOB
+        } else {
+            0
+        };
+        let fees = self.block_fees(gas_used, blob_gas_used);
+        let (post_state, unmapped) = self.post_state(&mut sequencer, &fees, &accessed_slots)?;
+        if !unmapped.is_empty() {
+            // The post state misses the unmapped slots, its root can't be compared.
+            errors.push(format!("state root not checked: {}", unmapped.join(", ")));
+            return Ok(errors);
+        }
+        let root: B256 = state_root(&post_state)?;
+        if root != entry.hash {
+            errors.push(format!(
+                "state root mismatch: expected {:#x}, got {:#x}",
+                entry.hash, root
+            ));
+        }
+
+        Ok(errors)
+    }
+}
+
+#[async_trait]
+impl Case for StateTestCase {
+    fn run(&self) -> Result<(), RunnerError> {
+        let Some(entries) = self.test.post.get(&self.fork.to_string()) else {
+            return Ok(());
+        };
+
+        let mut errors = Vec::new();
+        for entry in entries {
+            let variant = Self::variant_name(&self.case_name, &entry.indexes, self.fork);
+            if self.skipped_variants.contains(&variant) {
+                continue;
+            }
+            let variant_errors = match self.run_variant(entry) {
+                Ok(errors) => errors,
+                Err(err) => vec![err.to_string()],
+            };
+            errors.extend(
+                variant_errors
+                    .into_iter()
+                    .map(|err| format!("{variant}: {err}")),
+            );
+        }
+
+        if !errors.is_empty() {
+            return Err(RunnerError::Other(errors.into()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+//! Resource budgets of the tests. The Cairo resources of a run are compared to
+//! the JSON report of a baseline run, in order to catch the changes of Kakarot
+//! which increase the cost of the execution.
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::report::Report;
+use crate::models::metrics::CairoResources;
+
+/// Name of the resource used to report the Cairo steps.
+pub const STEPS: &str = "steps";
+
+/// Growth of a resource of a test beyond the budget.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceRegression {
+    /// Name of the test.
+    pub name: String,
+    /// Name of the directory containing the fixture file.
+    pub category: String,
+    /// Either [`STEPS`] or the name of a builtin.
+    pub resource: String,
+    pub baseline: usize,
+    pub current: usize,
+}
+
+impl ResourceRegression {
+    /// Returns the growth of the resource in percent, infinite if the resource
+    /// wasn't used in the baseline.
+    pub fn growth_percent(&self) -> f64 {
+        if self.baseline == 0 {
+            return f64::INFINITY;
+        }
+        (self.current as f64 - self.baseline as f64) * 100. / self.baseline as f64
+    }
+}
+
+/// Maximum growth of the Cairo resources of each test, compared to a baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResourceBudget {
+    max_growth_percent: f64,
+}
+
+impl ResourceBudget {
+    pub const fn new(max_growth_percent: f64) -> Self {
+        Self { max_growth_percent }
+    }
+
+    /// Returns the resources of the tests which grew beyond the budget, sorted
+    /// from the largest growth. Steps and builtins are checked independently, and
+    /// only the tests which executed in both runs are compared.
+    pub fn check(&self, baseline: &Report, current: &Report) -> Vec<ResourceRegression> {
+        let baseline: BTreeMap<&str, &CairoResources> = baseline
+            .cases
+            .iter()
+            .filter_map(|case| Some((case.name.as_str(), case.metrics.resources.as_ref()?)))
+            .collect();
+
+        let mut regressions = Vec::new();
+        for case in current.cases.iter() {
+            let (Some(resources), Some(baseline_resources)) = (
+                case.metrics.resources.as_ref(),
+                baseline.get(case.name.as_str()),
+            ) else {
+                continue;
+            };
+
+            let builtins: BTreeSet<&String> = resources
+                .builtins
+                .keys()
+                .chain(baseline_resources.builtins.keys())
+                .collect();
+            let usages = std::iter::once((STEPS, baseline_resources.steps, resources.steps)).chain(
+                builtins.into_iter().map(|builtin| {
+                    let count = |resources: &CairoResources| {
+                        resources.builtins.get(builtin).copied().unwrap_or_default()
+                    };
+                    (
+                        builtin.as_str(),
+                        count(baseline_resources),
+                        count(resources),
+                    )
+                }),
+            );
+
+            for (resource, baseline, current) in usages {
+                let regression = ResourceRegression {
+                    name: case.name.clone(),
+                    category: case.category.clone(),
+                    resource: resource.to_string(),
+                    baseline,
+                    current,
+                };
+                if current > baseline && regression.growth_percent() > self.max_growth_percent {
+                    regressions.push(regression);
+                }
+            }
+        }
+
+        regressions.sort_by(|lhs, rhs| {
+            rhs.growth_percent()
+                .total_cmp(&lhs.growth_percent())
+                .then_with(|| lhs.name.cmp(&rhs.name))
+                .then_with(|| lhs.resource.cmp(&rhs.resource))
+        });
+        regressions
+    }
+}
+
+/// Prints the largest regressions of each category, with the categories sorted
+/// by their largest regression.
+pub fn print_regressions(regressions: &[ResourceRegression], top: usize) {
+    if regressions.is_empty() {
+        return;
+    }
+
+    // Regressions are sorted, so the categories are listed from the largest regression
+    let mut categories: Vec<(&str, Vec<&ResourceRegression>)> = Vec::new();
+    for regression in regressions {
+        match categories
+            .iter_mut()
+            .find(|(category, _)| *category == regression.category)
+        {
+            Some((_, category_regressions)) => category_regressions.push(regression),
+            None => categories.push((regression.category.as_str(), vec![regression])),
+        }
+    }
+
+    println!();
+    println!("resource regressions:");
+    for (category, category_regressions) in categories.iter() {
+        println!();
+        println!("{category} ({} regressions):", category_regressions.len());
+        for regression in category_regressions.iter().take(top) {
+            let growth = if regression.baseline == 0 {
+                "new".to_string()
+            } else {
+                format!("+{:.1}%", regression.growth_percent())
+            };
+            println!(
+                "    {} {}: {} -> {} ({growth})",
+                regression.name, regression.resource, regression.baseline, regression.current
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::metrics::CaseMetrics;
+    use crate::runner::report::{CaseReport, Status};
+    use build_utils::fork::Fork;
+    use std::path::PathBuf;
+
+    fn case(name: &str, steps: usize, range_checks: usize) -> CaseReport {
+        CaseReport {
+            name: name.to_string(),
+            case_name: name.to_string(),
+            category: "stExample".to_string(),
+            fork: Fork::Cancun,
+            path: PathBuf::from("stExample/example.json"),
+            status: Status::Passed,
+            messages: Vec::new(),
+            metrics: CaseMetrics {
+                expected_gas_used: None,
+                gas_used: None,
+                resources: Some(CairoResources {
+                    steps,
+                    builtins: BTreeMap::from([("range_check_builtin".to_string(), range_checks)]),
+                    ..Default::default()
+                }),
+                coverage: None,
+                memory: None,
+            },
+            duration_secs: 1.,
+        }
+    }
+
+    fn report(cases: Vec<CaseReport>) -> Report {
+        Report {
+            cases,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_resource_budget() {
+        // Given
+        let baseline = report(vec![
+            case("test_a", 1000, 100),
+            case("test_b", 1000, 100),
+            case("test_c", 1000, 0),
+        ]);
+        let current = report(vec![
+            case("test_a", 1050, 100),
+            case("test_b", 1200, 150),
+            case("test_c", 900, 10),
+            case("test_d", 5000, 500),
+        ]);
+
+        // When
+        let regressions = ResourceBudget::new(10.).check(&baseline, &current);
+
+        // Then
+        let regressions: Vec<(&str, &str, f64)> = regressions
+            .iter()
+            .map(|regression| {
+                (
+                    regression.name.as_str(),
+                    regression.resource.as_str(),
+                    regression.growth_percent(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            regressions,
+            vec![
+                ("test_c", "range_check_builtin", f64::INFINITY),
+                ("test_b", "range_check_builtin", 50.),
+                ("test_b", STEPS, 20.),
+            ]
+        );
+    }
+}
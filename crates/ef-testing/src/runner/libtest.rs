@@ -0,0 +1,111 @@
+//! Output of the runner, formatted as the libtest output so that existing
+//! tooling (log parsing, skip file generation) keeps working.
+use std::time::Duration;
+
+use super::{TestEntry, TestOutcome, TestResult};
+
+/// Prints the list of tests, as `cargo test -- --list` does.
+pub fn print_list(entries: &[TestEntry]) {
+    for entry in entries {
+        println!("{}: test", entry.name);
+    }
+    println!();
+    println!("{} tests, 0 benchmarks", entries.len());
+}
+
+/// Prints the header of the test run.
+pub fn print_header(count: usize) {
+    let plural = if count == 1 { "" } else { "s" };
+    println!();
+    println!("running {count} test{plural}");
+}
+
+/// Prints the outcome of a single test.
+pub fn print_result(result: &TestResult) {
+    let status = match &result.outcome {
+        TestOutcome::Passed => "ok".to_string(),
+        TestOutcome::Failed(_) => "FAILED".to_string(),
+        TestOutcome::Ignored(Some(reason)) => format!("ignored, {reason}"),
+        TestOutcome::Ignored(None) => "ignored".to_string(),
+        TestOutcome::ExpectedFailure(_) => "ok, expected failure".to_string(),
+        TestOutcome::UnexpectedPass(_) => "ok, unexpected pass".to_string(),
+    };
+    println!("test {} ... {}", result.entry.name, status);
+}
+
+/// Prints the failures and the summary of the test run. Returns true if no test failed.
+pub fn print_summary(results: &[TestResult], filtered_out: usize, elapsed: Duration) -> bool {
+    let mut failures: Vec<(&str, String)> = results
+        .iter()
+        .filter_map(|result| match &result.outcome {
+            TestOutcome::Failed(messages) => {
+                Some((result.entry.name.as_str(), messages.join("\n")))
+            }
+            _ => None,
+        })
+        .collect();
+    failures.sort_unstable();
+
+    let mut unexpected_passes: Vec<(&str, &str)> = results
+        .iter()
+        .filter_map(|result| match &result.outcome {
+            TestOutcome::UnexpectedPass(reason) => {
+                Some((result.entry.name.as_str(), reason.as_str()))
+            }
+            _ => None,
+        })
+        .collect();
+    unexpected_passes.sort_unstable();
+
+    // Expected failures and unexpected passes count as passed, as libtest has no
+    // such outcomes.
+    let passed = results
+        .iter()
+        .filter(|result| {
+            matches!(
+                result.outcome,
+                TestOutcome::Passed
+                    | TestOutcome::ExpectedFailure(_)
+                    | TestOutcome::UnexpectedPass(_)
+            )
+        })
+        .count();
+    let ignored = results
+        .iter()
+        .filter(|result| matches!(result.outcome, TestOutcome::Ignored(_)))
+        .count();
+
+    if !unexpected_passes.is_empty() {
+        println!();
+        println!("unexpected passes, to be removed from the skip file:");
+        for (name, reason) in unexpected_passes.iter() {
+            println!("    {name} (expected failure: {reason})");
+        }
+    }
+
+    if !failures.is_empty() {
+        println!();
+        println!("failures:");
+        for (name, message) in failures.iter() {
+            println!();
+            println!("---- {name} stdout ----");
+            println!("{message}");
+        }
+        println!();
+        println!("failures:");
+        for (name, _) in failures.iter() {
+            println!("    {name}");
+        }
+    }
+
+    let status = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!();
+    println!(
+        "test result: {status}. {passed} passed; {} failed; {ignored} ignored; 0 measured; {filtered_out} filtered out; finished in {:.2}s",
+        failures.len(),
+        elapsed.as_secs_f64()
+    );
+    println!();
+
+    failures.is_empty()
+}
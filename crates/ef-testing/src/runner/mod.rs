@@ -0,0 +1,630 @@
+//! Runtime test runner. Discovers the blockchain tests in the fixtures and runs
+//! them directly, without generating a rust test per case.
+pub mod budget;
+pub mod libtest;
+pub mod report;
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::str::FromStr as _;
+use std::time::{Duration, Instant};
+
+use alloy_primitives::{Address, B256};
+use build_utils::{
+    content_reader::ContentReader,
+    converter::EfTests,
+    dir_reader::DirReader,
+    filter::{Expectation, Filter},
+    fork::Fork,
+    path::PathWrapper,
+    shard::Shard,
+};
+use ef_tests::models::{Account, Block, State};
+use rayon::prelude::*;
+use serde_json::Value;
+
+use crate::differential::Reference;
+use crate::models::case::BlockchainTestCase;
+use crate::models::fees::FeeTolerance;
+use crate::models::memory::{CaseMemory, MemoryUsage};
+use crate::models::metrics::CaseMetrics;
+
+/// Version of Kakarot selected by the features of the crate, used to apply the
+/// entries of the filter scoped to a version.
+pub use crate::evm_sequencer::constants::KAKAROT_VERSION;
+
+/// A blockchain test case found in the fixtures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestEntry {
+    /// Name of the test, matching the path of the generated test:
+    /// `{folder}::{fork}::{folder}::...::test_{case}`.
+    pub name: String,
+    /// Name of the case in the fixture file.
+    pub case_name: String,
+    /// Name of the directory containing the fixture file.
+    pub category: String,
+    pub fork: Fork,
+    pub path: PathBuf,
+    /// Reason for ignoring the test, if any.
+    pub ignored: Option<String>,
+    /// Reason why the test is expected to fail, if any.
+    pub xfail: Option<String>,
+}
+
+/// Outcome of a test run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestOutcome {
+    Passed,
+    /// The test failed, with one message per failed check.
+    Failed(Vec<String>),
+    Ignored(Option<String>),
+    /// The test is expected to fail and failed, with one message per failed check.
+    ExpectedFailure(Vec<String>),
+    /// The test is expected to fail for the given reason, but passed.
+    UnexpectedPass(String),
+}
+
+/// Result of a test run.
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub entry: TestEntry,
+    pub outcome: TestOutcome,
+    pub metrics: CaseMetrics,
+    pub duration: Duration,
+}
+
+/// Selection of the tests to run from their names, following the libtest semantics:
+/// a test is selected if it matches any of the filters (or if no filter is given)
+/// and none of the skip patterns.
+#[derive(Debug, Clone, Default)]
+pub struct NameFilter {
+    pub filters: Vec<String>,
+    pub skip: Vec<String>,
+    /// Match the filters and skip patterns exactly instead of as substrings.
+    pub exact: bool,
+}
+
+impl NameFilter {
+    pub fn matches(&self, name: &str) -> bool {
+        let is_match = |pattern: &String| {
+            if self.exact {
+                name == pattern
+            } else {
+                name.contains(pattern.as_str())
+            }
+        };
+        (self.filters.is_empty() || self.filters.iter().any(is_match))
+            && !self.skip.iter().any(is_match)
+    }
+}
+
+/// Handling of the tests ignored by the filter file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IgnoredMode {
+    /// Ignored tests are reported as such and not run.
+    #[default]
+    Skip,
+    /// Only the ignored tests are run.
+    Only,
+    /// Ignored tests are run along with the other tests.
+    Include,
+}
+
+/// Discovers the blockchain tests for the given forks in the directory.
+/// Tests skipped by the filter are marked as ignored, and the expected failures
+/// as xfail. Entries are sorted by name.
+pub fn discover(
+    root: &DirReader,
+    filter: &Filter,
+    forks: &[Fork],
+) -> Result<Vec<TestEntry>, eyre::Error> {
+    let mut entries = Vec::new();
+    for (folder_name, node) in root.sub_dirs() {
+        let folder = EfTests::format_into_identifier(folder_name);
+        for fork in forks {
+            let prefix = [folder.clone(), fork.module_name(), folder.clone()];
+            entries.append(&mut discover_folder(
+                node,
+                folder_name,
+                &prefix,
+                filter,
+                *fork,
+            )?);
+        }
+    }
+    entries.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+    Ok(entries)
+}
+
+fn discover_folder(
+    node: &DirReader,
+    parent_dir: &str,
+    prefix: &[String],
+    filter: &Filter,
+    fork: Fork,
+) -> Result<Vec<TestEntry>, eyre::Error> {
+    let mut entries = Vec::new();
+    for (dir_name, sub_node) in node.sub_dirs() {
+        let mut prefix = prefix.to_vec();
+        prefix.push(EfTests::format_into_identifier(dir_name));
+        entries.append(&mut discover_folder(
+            sub_node, dir_name, &prefix, filter, fork,
+        )?);
+    }
+
+    let files = node
+        .files()
+        .par_iter()
+        .map(|path| {
+            let content = path.read_file_to_string()?;
+            let cases: BTreeMap<String, Value> = serde_json::from_str(&content)?;
+            Ok(cases
+                .iter()
+                .filter(|(case_name, case)| fork.is_case_of(case_name, case))
+                .map(|(case_name, _)| {
+                    let (ignored, xfail) =
+                        match filter.expectation(path, Some(case_name.clone()), fork) {
+                            Expectation::Pass => (None, None),
+                            Expectation::Skip(reason) => (Some(reason), None),
+                            Expectation::Fail(reason) => (None, Some(reason)),
+                        };
+                    TestEntry {
+                        name: format!(
+                            "{}::test_{}",
+                            prefix.join("::"),
+                            EfTests::format_into_identifier(case_name)
+                        ),
+                        case_name: case_name.clone(),
+                        category: parent_dir.to_string(),
+                        fork,
+                        path: path.to_path_buf(),
+                        ignored,
+                        xfail,
+                    }
+                })
+                .collect::<Vec<_>>())
+        })
+        .collect::<Result<Vec<_>, eyre::Error>>()?;
+    entries.extend(files.into_iter().flatten());
+
+    Ok(entries)
+}
+
+/// Selects the entries to run. Returns the selected entries and the number of
+/// entries filtered out by name. Selected entries are cleared of their ignore
+/// reason if ignored tests should run.
+pub fn select(
+    entries: Vec<TestEntry>,
+    name_filter: &NameFilter,
+    ignored_mode: IgnoredMode,
+) -> (Vec<TestEntry>, usize) {
+    let total = entries.len();
+    let selected: Vec<TestEntry> = entries
+        .into_iter()
+        .filter(|entry| name_filter.matches(&entry.name))
+        .filter(|entry| ignored_mode != IgnoredMode::Only || entry.ignored.is_some())
+        .map(|mut entry| {
+            if ignored_mode != IgnoredMode::Skip {
+                entry.ignored = None;
+            }
+            entry
+        })
+        .collect();
+    let filtered_out = total - selected.len();
+    (selected, filtered_out)
+}
+
+/// Keeps the entries of the shard. Entries are assigned to a shard from the hash
+/// of their case, or, if the durations of a previous run are given (indexed by
+/// test name), so as to balance the expected duration of the shards.
+pub fn shard(
+    entries: Vec<TestEntry>,
+    shard: Shard,
+    durations: Option<&BTreeMap<String, f64>>,
+) -> Vec<TestEntry> {
+    match durations {
+        Some(durations) => {
+            let tests: Vec<(&str, Option<f64>)> = entries
+                .iter()
+                .map(|entry| (entry.name.as_str(), durations.get(&entry.name).copied()))
+                .collect();
+            let selection: BTreeSet<String> = shard
+                .balanced_selection(&tests)
+                .into_iter()
+                .map(ToString::to_string)
+                .collect();
+            entries
+                .into_iter()
+                .filter(|entry| selection.contains(&entry.name))
+                .collect()
+        }
+        None => entries
+            .into_iter()
+            .filter(|entry| {
+                shard.contains(&Shard::key(entry.fork, &entry.category, &entry.case_name))
+            })
+            .collect(),
+    }
+}
+
+/// Options of a test run.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Size of the thread pool, 0 for one thread per CPU.
+    pub threads: usize,
+    /// Report the expected failures which pass as failures.
+    pub xfail_strict: bool,
+    /// Reference the execution of each case is compared to, if any.
+    pub reference: Option<Reference>,
+    /// Directory the EIP-3155 traces of the transactions are written to, if any.
+    pub trace_dir: Option<PathBuf>,
+    /// Collect the opcodes and precompiles covered by each case, including the
+    /// ignored ones, whose coverage is collected from their code.
+    pub coverage: bool,
+    /// Directory the Cairo profile of Kakarot for each case is written to, if any.
+    pub profile_dir: Option<PathBuf>,
+    /// Memory, in bytes, beyond which a warning is printed for the cases. The
+    /// cases are run on a single thread when set.
+    pub memory_threshold: Option<u64>,
+    /// Tolerance allowed on each fee component when comparing the balances.
+    pub fee_tolerance: FeeTolerance,
+}
+
+thread_local! {
+    /// Location of the last panic of the thread, recorded by the panic hook
+    /// installed by `run`, which doesn't print the panics.
+    static PANIC_LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Runs the entries on a thread pool, calling `on_result` as soon as a test
+/// completes. Entries from the same fixture file are run on the same thread,
+/// reading the file once.
+pub fn run(
+    entries: Vec<TestEntry>,
+    options: &RunOptions,
+    on_result: impl Fn(&TestResult) + Sync,
+) -> Result<Vec<TestResult>, eyre::Error> {
+    let mut files: BTreeMap<PathBuf, Vec<TestEntry>> = BTreeMap::new();
+    for entry in entries {
+        files.entry(entry.path.clone()).or_default().push(entry);
+    }
+
+    // Panics are reported as test failures, along with their location.
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        let location = info.location().map(ToString::to_string);
+        PANIC_LOCATION.with(|panic_location| *panic_location.borrow_mut() = location);
+    }));
+
+    // The memory is the one of the whole process, so it can only be attributed to
+    // a case if the cases run one at a time.
+    let threads = if options.memory_threshold.is_some() {
+        1
+    } else {
+        options.threads
+    };
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()?;
+    let results = pool.install(|| {
+        files
+            .into_par_iter()
+            .flat_map_iter(|(path, entries)| {
+                let cases = PathWrapper::from(path)
+                    .read_file_to_string()
+                    .map_err(eyre::Error::from)
+                    .and_then(|content| {
+                        Ok(serde_json::from_str::<BTreeMap<String, Value>>(&content)?)
+                    });
+                entries
+                    .into_iter()
+                    .map(|entry| {
+                        let start = Instant::now();
+                        let (outcome, metrics) = match (&entry.ignored, &cases) {
+                            (Some(reason), Ok(cases)) if options.coverage => (
+                                TestOutcome::Ignored(Some(reason.clone())),
+                                ignored_case_metrics(&entry, cases.get(&entry.case_name)),
+                            ),
+                            (Some(reason), _) => (
+                                TestOutcome::Ignored(Some(reason.clone())),
+                                CaseMetrics::default(),
+                            ),
+                            (None, Err(err)) => (
+                                TestOutcome::Failed(vec![err.to_string()]),
+                                CaseMetrics::default(),
+                            ),
+                            (None, Ok(cases)) => match cases.get(&entry.case_name) {
+                                Some(case) => run_case(&entry, case, options),
+                                None => (
+                                    TestOutcome::Failed(vec![format!(
+                                        "case {} not found in {}",
+                                        entry.case_name,
+                                        entry.path.display()
+                                    )]),
+                                    CaseMetrics::default(),
+                                ),
+                            },
+                        };
+                        let outcome = apply_expectation(
+                            outcome,
+                            entry.xfail.as_deref(),
+                            options.xfail_strict,
+                        );
+                        let result = TestResult {
+                            entry,
+                            outcome,
+                            metrics,
+                            duration: start.elapsed(),
+                        };
+                        // Printed as soon as possible, as the run may be killed for
+                        // running out of memory.
+                        if let (Some(memory), Some(threshold)) =
+                            (result.metrics.memory.as_ref(), options.memory_threshold)
+                        {
+                            if let Some(warning) = memory.warning(&result.entry.name, threshold) {
+                                eprintln!("warning: {warning}");
+                            }
+                        }
+                        on_result(&result);
+                        result
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    });
+
+    panic::set_hook(default_hook);
+    Ok(results)
+}
+
+/// Converts the outcome of an expected failure: a failure is expected, and a pass
+/// is unexpected, which is a failure in strict mode.
+fn apply_expectation(outcome: TestOutcome, xfail: Option<&str>, xfail_strict: bool) -> TestOutcome {
+    let Some(reason) = xfail else {
+        return outcome;
+    };
+    match outcome {
+        TestOutcome::Failed(messages) => TestOutcome::ExpectedFailure(messages),
+        TestOutcome::Passed if xfail_strict => TestOutcome::Failed(vec![format!(
+            "test unexpectedly passed, expected failure: {reason}"
+        )]),
+        TestOutcome::Passed => TestOutcome::UnexpectedPass(reason.to_string()),
+        outcome => outcome,
+    }
+}
+
+/// Loads the blockchain test case of the entry from its content in the fixture.
+pub fn load_case(entry: &TestEntry, case: &Value) -> Result<BlockchainTestCase, eyre::Error> {
+    let blocks: Vec<Block> = serde_json::from_value(Value::Array(ContentReader::blocks(case)?))?;
+    let pre: State = serde_json::from_value(ContentReader::pre_state(case)?)?;
+    let post: Option<BTreeMap<Address, Account>> =
+        serde_json::from_value(ContentReader::post_state(case)?)?;
+    let expected_exceptions: Vec<Option<String>> =
+        serde_json::from_value(ContentReader::expected_exceptions(case)?)?;
+    let secret_key = ContentReader::secret_key(entry.path.clone().into(), case)?;
+    let secret_key = B256::from_str(secret_key.trim_matches('"'))?;
+
+    Ok(BlockchainTestCase::new(
+        entry.case_name.clone(),
+        entry.category.clone(),
+        blocks,
+        pre,
+        post,
+        secret_key,
+    )
+    .with_expected_exceptions(expected_exceptions)
+    .with_fork(entry.fork))
+}
+
+/// Runs the case of the entry, comparing its execution to the reference and
+/// tracing it if requested by the options. A case which cannot be loaded is
+/// ignored, as is done for the generated tests.
+fn run_case(entry: &TestEntry, case: &Value, options: &RunOptions) -> (TestOutcome, CaseMetrics) {
+    let test_case = match load_case(entry, case) {
+        Ok(test_case) => test_case
+            .with_reference(options.reference)
+            .with_trace_dir(options.trace_dir.clone())
+            .with_coverage(options.coverage)
+            .with_profile_dir(options.profile_dir.clone())
+            .with_fee_tolerance(options.fee_tolerance),
+        Err(err) => {
+            return (
+                TestOutcome::Ignored(Some(err.to_string())),
+                CaseMetrics::default(),
+            )
+        }
+    };
+
+    MemoryUsage::reset_peak();
+    let memory_start = MemoryUsage::current();
+    // The metrics outlive the run, keeping the ones collected before a panic.
+    let mut metrics = CaseMetrics::default();
+    let outcome = match panic::catch_unwind(AssertUnwindSafe(|| {
+        test_case.run_with_metrics(&mut metrics)
+    })) {
+        Ok(Ok(())) => TestOutcome::Passed,
+        Ok(Err(err)) => TestOutcome::Failed(err.messages()),
+        Err(payload) => TestOutcome::Failed(vec![panic_message(payload.as_ref())]),
+    };
+    metrics.memory = memory_start
+        .zip(MemoryUsage::current())
+        .map(|(start, end)| CaseMemory::new(start, end));
+    (outcome, metrics)
+}
+
+/// Returns the message of a panic caught while running a case, with the
+/// location recorded by the panic hook of the thread.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(ToString::to_string)
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "test panicked".to_string());
+    match PANIC_LOCATION.with(|location| location.borrow_mut().take()) {
+        Some(location) => format!("panicked at {location}: {message}"),
+        None => format!("panicked: {message}"),
+    }
+}
+
+/// Returns the metrics of an ignored case, holding the coverage of its code if
+/// the case can be loaded.
+fn ignored_case_metrics(entry: &TestEntry, case: Option<&Value>) -> CaseMetrics {
+    let coverage = case
+        .and_then(|case| load_case(entry, case).ok())
+        .and_then(|test_case| test_case.code_coverage().ok());
+    CaseMetrics {
+        coverage,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, ignored: bool) -> TestEntry {
+        TestEntry {
+            name: name.to_string(),
+            case_name: name.to_string(),
+            category: "stExample".to_string(),
+            fork: Fork::Cancun,
+            path: PathBuf::from("stExample/example.json"),
+            ignored: ignored.then(|| "skipped in config file".to_string()),
+            xfail: None,
+        }
+    }
+
+    #[test]
+    fn test_name_filter() {
+        // Given
+        let filter = NameFilter {
+            filters: vec!["add".to_string()],
+            skip: vec!["addmod".to_string()],
+            exact: false,
+        };
+        let exact = NameFilter {
+            filters: vec!["test_add".to_string()],
+            exact: true,
+            ..Default::default()
+        };
+
+        // When / Then
+        assert!(filter.matches("VmTests::cancun::VmTests::test_add_d0g0v0_Cancun"));
+        assert!(!filter.matches("VmTests::cancun::VmTests::test_addmod_d0g0v0_Cancun"));
+        assert!(!filter.matches("VmTests::cancun::VmTests::test_mul_d0g0v0_Cancun"));
+        assert!(exact.matches("test_add"));
+        assert!(!exact.matches("test_add_d0g0v0_Cancun"));
+        assert!(NameFilter::default().matches("anything"));
+    }
+
+    #[test]
+    fn test_select() {
+        // Given
+        let entries = vec![
+            entry("test_a", false),
+            entry("test_b", true),
+            entry("other", true),
+        ];
+        let filter = NameFilter {
+            filters: vec!["test_".to_string()],
+            ..Default::default()
+        };
+
+        // When
+        let (skip, skip_filtered) = select(entries.clone(), &filter, IgnoredMode::Skip);
+        let (only, only_filtered) = select(entries.clone(), &filter, IgnoredMode::Only);
+        let (include, _) = select(entries, &filter, IgnoredMode::Include);
+
+        // Then
+        assert_eq!(skip.len(), 2);
+        assert_eq!(skip_filtered, 1);
+        assert!(skip[1].ignored.is_some());
+        assert_eq!(only.len(), 1);
+        assert_eq!(only_filtered, 2);
+        assert_eq!(only[0].name, "test_b");
+        assert!(only[0].ignored.is_none());
+        assert!(include.iter().all(|entry| entry.ignored.is_none()));
+    }
+
+    #[test]
+    fn test_shard() {
+        // Given
+        let entries: Vec<TestEntry> = (0..20)
+            .map(|i| entry(&format!("test_{i}"), false))
+            .collect();
+        let durations: BTreeMap<String, f64> = entries
+            .iter()
+            .map(|entry| (entry.name.clone(), 1.))
+            .collect();
+
+        // When
+        let shards: Vec<Vec<TestEntry>> = (1..=3)
+            .map(|i| shard(entries.clone(), Shard::new(i, 3).unwrap(), None))
+            .collect();
+        let balanced: Vec<Vec<TestEntry>> = (1..=3)
+            .map(|i| shard(entries.clone(), Shard::new(i, 3).unwrap(), Some(&durations)))
+            .collect();
+
+        // Then
+        let mut names: Vec<String> = shards
+            .iter()
+            .flatten()
+            .map(|entry| entry.name.clone())
+            .collect();
+        names.sort();
+        let mut expected: Vec<String> = entries.iter().map(|entry| entry.name.clone()).collect();
+        expected.sort();
+        assert_eq!(names, expected);
+        assert_eq!(
+            balanced.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![7, 7, 6]
+        );
+    }
+
+    #[test]
+    fn test_panic_message() {
+        // Given
+        let payload: Box<dyn Any + Send> = Box::new("index out of bounds".to_string());
+        PANIC_LOCATION
+            .with(|location| *location.borrow_mut() = Some("src/models/case.rs:42:5".to_string()));
+
+        // When
+        let message = panic_message(payload.as_ref());
+        let without_location = panic_message(payload.as_ref());
+
+        // Then
+        assert_eq!(
+            message,
+            "panicked at src/models/case.rs:42:5: index out of bounds"
+        );
+        assert_eq!(without_location, "panicked: index out of bounds");
+    }
+
+    #[test]
+    fn test_apply_expectation() {
+        // Given
+        let failed = TestOutcome::Failed(vec!["nonce mismatch".to_string()]);
+        let reason = Some("kakarot bug");
+
+        // When / Then
+        assert_eq!(
+            apply_expectation(failed.clone(), None, true),
+            failed.clone()
+        );
+        assert_eq!(
+            apply_expectation(failed, reason, false),
+            TestOutcome::ExpectedFailure(vec!["nonce mismatch".to_string()])
+        );
+        assert_eq!(
+            apply_expectation(TestOutcome::Passed, reason, false),
+            TestOutcome::UnexpectedPass("kakarot bug".to_string())
+        );
+        assert!(matches!(
+            apply_expectation(TestOutcome::Passed, reason, true),
+            TestOutcome::Failed(_)
+        ));
+    }
+}
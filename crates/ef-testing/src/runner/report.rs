@@ -0,0 +1,423 @@
+//! Structured reports of a test run, written as JSON, JUnit XML or as a CSV of
+//! the resources used by each case.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use build_utils::fork::Fork;
+use serde::{Deserialize, Serialize};
+
+use super::{TestOutcome, TestResult};
+use crate::models::metrics::CaseMetrics;
+
+/// Status of a case in a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Passed,
+    Failed,
+    Ignored,
+    /// The test is expected to fail and failed.
+    #[serde(rename = "xfail")]
+    ExpectedFailure,
+    /// The test is expected to fail but passed.
+    #[serde(rename = "xpass")]
+    UnexpectedPass,
+}
+
+impl Status {
+    /// Returns the status as written in the JSON report.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Passed => "passed",
+            Self::Failed => "failed",
+            Self::Ignored => "ignored",
+            Self::ExpectedFailure => "xfail",
+            Self::UnexpectedPass => "xpass",
+        }
+    }
+}
+
+/// Report of a single case.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseReport {
+    /// Name of the test, as listed by the runner.
+    pub name: String,
+    /// Name of the case in the fixture file.
+    pub case_name: String,
+    /// Name of the directory containing the fixture file.
+    pub category: String,
+    pub fork: Fork,
+    pub path: PathBuf,
+    pub status: Status,
+    /// Failure messages for a failed case or an expected failure, the ignore reason
+    /// for an ignored case, or the expected failure reason for an unexpected pass.
+    pub messages: Vec<String>,
+    #[serde(flatten)]
+    pub metrics: CaseMetrics,
+    pub duration_secs: f64,
+}
+
+impl From<&TestResult> for CaseReport {
+    fn from(result: &TestResult) -> Self {
+        let (status, messages) = match &result.outcome {
+            TestOutcome::Passed => (Status::Passed, Vec::new()),
+            TestOutcome::Failed(messages) => (Status::Failed, messages.clone()),
+            TestOutcome::Ignored(reason) => (Status::Ignored, reason.iter().cloned().collect()),
+            TestOutcome::ExpectedFailure(messages) => (Status::ExpectedFailure, messages.clone()),
+            TestOutcome::UnexpectedPass(reason) => (Status::UnexpectedPass, vec![reason.clone()]),
+        };
+        Self {
+            name: result.entry.name.clone(),
+            case_name: result.entry.case_name.clone(),
+            category: result.entry.category.clone(),
+            fork: result.entry.fork,
+            path: result.entry.path.clone(),
+            status,
+            messages,
+            metrics: result.metrics.clone(),
+            duration_secs: result.duration.as_secs_f64(),
+        }
+    }
+}
+
+/// Report of a test run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub passed: usize,
+    pub failed: usize,
+    pub ignored: usize,
+    #[serde(default)]
+    pub expected_failures: usize,
+    #[serde(default)]
+    pub unexpected_passes: usize,
+    pub duration_secs: f64,
+    /// Reports of the cases, sorted by name.
+    pub cases: Vec<CaseReport>,
+}
+
+impl Report {
+    pub fn new(results: &[TestResult], elapsed: Duration) -> Self {
+        let mut cases: Vec<CaseReport> = results.iter().map(CaseReport::from).collect();
+        cases.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+        let count = |status| cases.iter().filter(|case| case.status == status).count();
+
+        Self {
+            passed: count(Status::Passed),
+            failed: count(Status::Failed),
+            ignored: count(Status::Ignored),
+            expected_failures: count(Status::ExpectedFailure),
+            unexpected_passes: count(Status::UnexpectedPass),
+            duration_secs: elapsed.as_secs_f64(),
+            cases,
+        }
+    }
+
+    pub fn load_json(path: &Path) -> Result<Self, eyre::Error> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<(), eyre::Error> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn write_junit(&self, path: &Path) -> Result<(), eyre::Error> {
+        fs::write(path, self.to_junit())?;
+        Ok(())
+    }
+
+    pub fn write_resources_csv(&self, path: &Path) -> Result<(), eyre::Error> {
+        fs::write(path, self.to_resources_csv())?;
+        Ok(())
+    }
+
+    /// Formats the gas and Cairo resources used by each case as CSV, with one
+    /// column per builtin. Cells of the cases which didn't execute are left empty.
+    pub fn to_resources_csv(&self) -> String {
+        let builtins: BTreeSet<&str> = self
+            .cases
+            .iter()
+            .filter_map(|case| case.metrics.resources.as_ref())
+            .flat_map(|resources| resources.builtins.keys().map(String::as_str))
+            .collect();
+
+        let mut header = vec![
+            "name",
+            "category",
+            "fork",
+            "status",
+            "duration_secs",
+            "expected_gas_used",
+            "gas_used",
+            "steps",
+            "memory_holes",
+            "l1_gas",
+            "da_gas",
+            "state_diff_size",
+        ];
+        header.extend(builtins.iter());
+        let mut csv = header.join(",") + "\n";
+
+        for case in self.cases.iter() {
+            let resources = case.metrics.resources.as_ref();
+            let mut row = vec![
+                csv_field(&case.name),
+                csv_field(&case.category),
+                case.fork.to_string(),
+                case.status.as_str().to_string(),
+                format!("{:.3}", case.duration_secs),
+                case.metrics
+                    .expected_gas_used
+                    .map(|gas| gas.to_string())
+                    .unwrap_or_default(),
+                case.metrics
+                    .gas_used
+                    .map(|gas| gas.to_string())
+                    .unwrap_or_default(),
+                resources
+                    .map(|resources| resources.steps.to_string())
+                    .unwrap_or_default(),
+                resources
+                    .map(|resources| resources.memory_holes.to_string())
+                    .unwrap_or_default(),
+                resources
+                    .map(|resources| resources.l1_gas.to_string())
+                    .unwrap_or_default(),
+                resources
+                    .map(|resources| resources.da_gas.to_string())
+                    .unwrap_or_default(),
+                resources
+                    .map(|resources| resources.state_diff_size.to_string())
+                    .unwrap_or_default(),
+            ];
+            row.extend(builtins.iter().map(|builtin| {
+                resources
+                    .map(|resources| {
+                        let count = resources.builtins.get(*builtin).copied();
+                        count.unwrap_or_default().to_string()
+                    })
+                    .unwrap_or_default()
+            }));
+            csv += &row.join(",");
+            csv += "\n";
+        }
+        csv
+    }
+
+    /// Formats the report as JUnit XML, with one test suite per category. Expected
+    /// failures and unexpected passes are reported as passed, with their messages
+    /// in the output of the test case.
+    pub fn to_junit(&self) -> String {
+        let mut suites: BTreeMap<&str, Vec<&CaseReport>> = BTreeMap::new();
+        for case in self.cases.iter() {
+            suites.entry(&case.category).or_default().push(case);
+        }
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuites name=\"ef-tests\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            self.cases.len(),
+            self.failed,
+            self.ignored,
+            self.duration_secs
+        );
+        for (category, cases) in suites {
+            let count = |status| cases.iter().filter(|case| case.status == status).count();
+            let time: f64 = cases.iter().map(|case| case.duration_secs).sum();
+            let _ = writeln!(
+                xml,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+                escape(category),
+                cases.len(),
+                count(Status::Failed),
+                count(Status::Ignored),
+                time
+            );
+            for case in cases {
+                let _ = write!(
+                    xml,
+                    "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                    escape(&case.name),
+                    escape(category),
+                    case.duration_secs
+                );
+                let message = case
+                    .messages
+                    .first()
+                    .map(String::as_str)
+                    .unwrap_or_default();
+                match case.status {
+                    Status::Passed => xml.push_str("/>\n"),
+                    Status::ExpectedFailure | Status::UnexpectedPass => {
+                        let prefix = if case.status == Status::ExpectedFailure {
+                            "expected failure"
+                        } else {
+                            "unexpected pass"
+                        };
+                        let _ = writeln!(
+                            xml,
+                            ">\n      <system-out>{}</system-out>\n    </testcase>",
+                            escape(&format!("{prefix}: {}", case.messages.join("\n")))
+                        );
+                    }
+                    Status::Failed => {
+                        let _ = writeln!(
+                            xml,
+                            ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                            escape(message),
+                            escape(&case.messages.join("\n"))
+                        );
+                    }
+                    Status::Ignored => {
+                        let _ = writeln!(
+                            xml,
+                            ">\n      <skipped message=\"{}\"/>\n    </testcase>",
+                            escape(message)
+                        );
+                    }
+                }
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+/// Quotes the CSV field if it contains a separator, a quote or a new line.
+fn csv_field(input: &str) -> String {
+    if input.contains([',', '"', '\n']) {
+        format!("\"{}\"", input.replace('"', "\"\""))
+    } else {
+        input.to_string()
+    }
+}
+
+/// Escapes the XML special characters of the input.
+fn escape(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::metrics::CairoResources;
+    use crate::runner::TestEntry;
+
+    fn result(name: &str, outcome: TestOutcome) -> TestResult {
+        TestResult {
+            entry: TestEntry {
+                name: name.to_string(),
+                case_name: name.to_string(),
+                category: "stExample".to_string(),
+                fork: Fork::Cancun,
+                path: PathBuf::from("stExample/example.json"),
+                ignored: None,
+                xfail: None,
+            },
+            outcome,
+            metrics: CaseMetrics {
+                expected_gas_used: Some(21_000),
+                gas_used: Some(21_000),
+                resources: None,
+                coverage: None,
+                memory: None,
+            },
+            duration: Duration::from_millis(1500),
+        }
+    }
+
+    #[test]
+    fn test_report_json_round_trip() {
+        // Given
+        let results = vec![
+            result(
+                "test_b",
+                TestOutcome::Failed(vec!["nonce mismatch".to_string()]),
+            ),
+            result("test_a", TestOutcome::Passed),
+        ];
+
+        // When
+        let report = Report::new(&results, Duration::from_secs(3));
+        let json = serde_json::to_string(&report).unwrap();
+        let deserialized: Report = serde_json::from_str(&json).unwrap();
+
+        // Then
+        assert_eq!(report.passed, 1);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.cases[0].name, "test_a");
+        assert_eq!(report.cases[1].messages, vec!["nonce mismatch".to_string()]);
+        assert_eq!(deserialized, report);
+    }
+
+    #[test]
+    fn test_report_to_resources_csv() {
+        // Given
+        let mut executed = result("test_a", TestOutcome::Passed);
+        executed.metrics.resources = Some(CairoResources {
+            steps: 1000,
+            memory_holes: 10,
+            builtins: BTreeMap::from([("range_check_builtin".to_string(), 42)]),
+            l1_gas: 0,
+            da_gas: 256,
+            state_diff_size: 3,
+        });
+        let results = vec![executed, result("test_b,c", TestOutcome::Ignored(None))];
+        let report = Report::new(&results, Duration::from_secs(3));
+
+        // When
+        let csv = report.to_resources_csv();
+
+        // Then
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "name,category,fork,status,duration_secs,expected_gas_used,gas_used,steps,memory_holes,l1_gas,da_gas,state_diff_size,range_check_builtin",
+                "test_a,stExample,Cancun,passed,1.500,21000,21000,1000,10,0,256,3,42",
+                "\"test_b,c\",stExample,Cancun,ignored,1.500,21000,21000,,,,,,",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_to_junit() {
+        // Given
+        let results = vec![
+            result("test_a", TestOutcome::Passed),
+            result(
+                "test_b",
+                TestOutcome::Failed(vec!["expected <1> & got \"2\"".to_string()]),
+            ),
+            result(
+                "test_c",
+                TestOutcome::Ignored(Some("skipped in config file".to_string())),
+            ),
+        ];
+        let report = Report::new(&results, Duration::from_secs(3));
+
+        // When
+        let junit = report.to_junit();
+
+        // Then
+        assert!(junit.contains(
+            "<testsuite name=\"stExample\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\"4.500\">"
+        ));
+        assert!(
+            junit.contains("<testcase name=\"test_a\" classname=\"stExample\" time=\"1.500\"/>")
+        );
+        assert!(junit.contains("<failure message=\"expected &lt;1&gt; &amp; got &quot;2&quot;\">"));
+        assert!(junit.contains("<skipped message=\"skipped in config file\"/>"));
+    }
+}
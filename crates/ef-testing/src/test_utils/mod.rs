@@ -1,3 +1,4 @@
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::Once;
@@ -6,8 +7,14 @@ use std::time::{Duration, Instant};
 
 use tracing_subscriber::{filter, FmtSubscriber};
 
+use crate::models::error::RunnerError;
+
 static INIT: Once = Once::new();
 
+/// Environment variable which, when set to a value other than `0`, makes the
+/// expected failures fail when they pass.
+pub const XFAIL_STRICT_ENV_VAR: &str = "EF_TESTS_XFAIL_STRICT";
+
 pub fn setup() {
     INIT.call_once(|| {
         // Set-up tracing filter
@@ -21,6 +28,32 @@ pub fn setup() {
     })
 }
 
+/// Returns true if the expected failures which pass should fail.
+pub fn is_xfail_strict() -> bool {
+    std::env::var(XFAIL_STRICT_ENV_VAR).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Runs a test which is expected to fail. An error or a panic is the expected
+/// outcome. A pass is reported, and panics in strict mode (see [`is_xfail_strict`])
+/// so that the test gets removed from the skip file.
+pub fn expect_failure<F>(test_name: &str, reason: &str, test_fn: F)
+where
+    F: FnOnce() -> Result<(), RunnerError>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(test_fn)) {
+        Ok(Ok(())) => {
+            tracing::warn!(
+                "Test {test_name} is expected to fail ({reason}) but passed, it should be removed from the skip file"
+            );
+            if is_xfail_strict() {
+                panic!("Test {test_name} unexpectedly passed, expected failure: {reason}");
+            }
+        }
+        Ok(Err(err)) => tracing::info!("Test {test_name} failed as expected ({reason}): {err}"),
+        Err(_) => tracing::info!("Test {test_name} panicked as expected ({reason})"),
+    }
+}
+
 pub struct TestMonitor {
     /// thresholds in seconds
     thresholds: Vec<u64>,
@@ -0,0 +1,257 @@
+//! Localization of the first divergence between the trace of Kakarot and a
+//! reference trace, e.g. the one of revm or a trace supplied with a fixture.
+use std::fmt::{Display, Formatter, Write as _};
+
+use revm_interpreter::opcode::SSTORE;
+
+use super::{Trace, TraceStep};
+
+/// Number of stack items printed for each step of a report.
+const STACK_ITEMS: usize = 4;
+
+/// Field of the first diverging step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivergenceKind {
+    /// The executed opcodes or program counters differ.
+    Opcode,
+    /// The call depths differ.
+    Depth,
+    /// The slot or value written by an SSTORE differ.
+    StorageWrite,
+    /// The stacks differ.
+    Stack,
+    /// The gas left before the opcode differs.
+    Gas,
+    /// The gas cost of the opcode differs.
+    GasCost,
+    /// One of the traces ended before the other.
+    Length,
+    /// The steps match, but not the output, gas used or status of the transaction.
+    Summary,
+}
+
+impl Display for DivergenceKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            Self::Opcode => "opcode",
+            Self::Depth => "depth",
+            Self::StorageWrite => "storage write",
+            Self::Stack => "stack",
+            Self::Gas => "gas",
+            Self::GasCost => "gas cost",
+            Self::Length => "trace length",
+            Self::Summary => "summary",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+/// First divergence between two traces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the first diverging step, the number of steps for a divergence
+    /// of the summaries.
+    pub step: usize,
+    pub kind: DivergenceKind,
+}
+
+impl Divergence {
+    /// Returns the first divergence between the actual and expected traces, if
+    /// any. The fields of a step are compared in order of significance: the
+    /// opcode, the depth, the stack (an SSTORE with a different slot or value
+    /// being reported as a storage write), the gas left and the gas cost.
+    pub fn find(actual: &Trace, expected: &Trace) -> Option<Self> {
+        for (step, (lhs, rhs)) in actual.steps.iter().zip(expected.steps.iter()).enumerate() {
+            if let Some(kind) = Self::compare_steps(lhs, rhs) {
+                return Some(Self { step, kind });
+            }
+        }
+
+        if actual.steps.len() != expected.steps.len() {
+            return Some(Self {
+                step: actual.steps.len().min(expected.steps.len()),
+                kind: DivergenceKind::Length,
+            });
+        }
+        (actual.summary != expected.summary).then_some(Self {
+            step: actual.steps.len(),
+            kind: DivergenceKind::Summary,
+        })
+    }
+
+    fn compare_steps(lhs: &TraceStep, rhs: &TraceStep) -> Option<DivergenceKind> {
+        if lhs.op != rhs.op || lhs.pc != rhs.pc {
+            return Some(DivergenceKind::Opcode);
+        }
+        if lhs.depth != rhs.depth {
+            return Some(DivergenceKind::Depth);
+        }
+        if lhs.stack != rhs.stack {
+            // The slot and the value are on top of the stack
+            let top =
+                |step: &TraceStep| step.stack.iter().rev().take(2).copied().collect::<Vec<_>>();
+            if lhs.op == SSTORE && top(lhs) != top(rhs) {
+                return Some(DivergenceKind::StorageWrite);
+            }
+            return Some(DivergenceKind::Stack);
+        }
+        if lhs.gas != rhs.gas {
+            return Some(DivergenceKind::Gas);
+        }
+        if lhs.gas_cost != rhs.gas_cost {
+            return Some(DivergenceKind::GasCost);
+        }
+        None
+    }
+
+    /// Returns a report of the divergence, with the given number of matching
+    /// steps preceding it followed by the diverging steps of both traces.
+    pub fn report(
+        &self,
+        actual: &Trace,
+        expected: &Trace,
+        context: usize,
+        actual_name: &str,
+        expected_name: &str,
+    ) -> String {
+        let mut report = format!("first divergence at step {}: {}\n", self.step, self.kind);
+        let start = self.step.saturating_sub(context);
+        for (index, step) in actual.steps.iter().enumerate().take(self.step).skip(start) {
+            let _ = writeln!(report, "  {}", format_step(index, step));
+        }
+
+        let width = actual_name.len().max(expected_name.len());
+        for (name, trace) in [(actual_name, actual), (expected_name, expected)] {
+            let line = match (self.kind, trace.steps.get(self.step)) {
+                (DivergenceKind::Summary, _) | (_, None) => format!(
+                    "end: output={} gas_used={} pass={}",
+                    trace.summary.output, trace.summary.gas_used, trace.summary.pass
+                ),
+                (_, Some(step)) => format_step(self.step, step),
+            };
+            let _ = writeln!(report, "- {name:<width$}: {line}");
+        }
+        report
+    }
+}
+
+fn format_step(index: usize, step: &TraceStep) -> String {
+    let name = if step.op_name.is_empty() {
+        TraceStep::op_name(step.op)
+    } else {
+        step.op_name.clone()
+    };
+    let stack: Vec<String> = step
+        .stack
+        .iter()
+        .rev()
+        .take(STACK_ITEMS)
+        .map(|item| format!("{item:#x}"))
+        .collect();
+    let ellipsis = if step.stack.len() > STACK_ITEMS {
+        ", .."
+    } else {
+        ""
+    };
+    format!(
+        "#{index} pc={} {name} gas={} cost={} depth={} stack(top first)=[{}{ellipsis}]",
+        step.pc,
+        step.gas,
+        step.gas_cost,
+        step.depth,
+        stack.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{U256, U64};
+
+    fn step(pc: u64, op: u8, gas: u64, stack: &[u64]) -> TraceStep {
+        TraceStep {
+            pc,
+            op,
+            gas: U64::from(gas),
+            gas_cost: U64::from(3),
+            stack: stack.iter().map(|item| U256::from(*item)).collect(),
+            depth: 1,
+            op_name: TraceStep::op_name(op),
+            ..Default::default()
+        }
+    }
+
+    fn trace(steps: Vec<TraceStep>) -> Trace {
+        Trace {
+            steps,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_divergence() {
+        // Given
+        let expected = trace(vec![
+            step(0, 0x60, 100, &[]),
+            step(2, 0x60, 97, &[1]),
+            step(4, SSTORE, 94, &[1, 2]),
+        ]);
+        let mut storage_write = expected.clone();
+        storage_write.steps[2].stack[0] = U256::from(3);
+        let mut gas = expected.clone();
+        gas.steps[1].gas = U64::from(96);
+        let mut truncated = expected.clone();
+        truncated.steps.pop();
+
+        // When
+        let same = Divergence::find(&expected, &expected);
+        let storage_write = Divergence::find(&storage_write, &expected);
+        let gas = Divergence::find(&gas, &expected);
+        let truncated = Divergence::find(&truncated, &expected);
+
+        // Then
+        assert_eq!(same, None);
+        assert_eq!(
+            storage_write,
+            Some(Divergence {
+                step: 2,
+                kind: DivergenceKind::StorageWrite
+            })
+        );
+        assert_eq!(
+            gas,
+            Some(Divergence {
+                step: 1,
+                kind: DivergenceKind::Gas
+            })
+        );
+        assert_eq!(
+            truncated,
+            Some(Divergence {
+                step: 2,
+                kind: DivergenceKind::Length
+            })
+        );
+    }
+
+    #[test]
+    fn test_divergence_report() {
+        // Given
+        let expected = trace(vec![step(0, 0x60, 100, &[]), step(2, 0x01, 97, &[1, 2])]);
+        let mut actual = expected.clone();
+        actual.steps[1].stack[1] = U256::from(3);
+        let divergence = Divergence::find(&actual, &expected).unwrap();
+
+        // When
+        let report = divergence.report(&actual, &expected, 5, "kakarot", "revm");
+
+        // Then
+        assert_eq!(
+            report,
+            "first divergence at step 1: stack\n  \
+             #0 pc=0 PUSH1 gas=100 cost=3 depth=1 stack(top first)=[]\n\
+             - kakarot: #1 pc=2 ADD gas=97 cost=3 depth=1 stack(top first)=[0x3, 0x1]\n\
+             - revm   : #1 pc=2 ADD gas=97 cost=3 depth=1 stack(top first)=[0x2, 0x1]\n"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Collection of the traces of the transactions executed by Kakarot.
+//!
+//! The steps of a trace are decoded from `opcode_executed` debug events emitted
+//! before the execution of each opcode, with the following data:
+//! `[pc, opcode, gas, gas_cost, depth, mem_size, refund, stack_len, ...stack]`,
+//! where each stack item is split into a low and a high part and the top of the
+//! stack is last. No Kakarot class emits these events yet, so the trace of a
+//! transaction only holds its summary.
+use alloy_primitives::{U256, U64};
+use blockifier::execution::call_info::CallInfo;
+use eyre::{eyre, Result};
+use starknet::core::types::Felt;
+use starknet::macros::selector;
+
+use super::{Trace, TraceStep, TraceSummary};
+use crate::models::result::EVMOutput;
+
+/// Returns the trace of the transaction from the debug events of its execution,
+/// if it was executed, and from its output.
+pub fn kakarot_trace(call_info: Option<&CallInfo>, output: &EVMOutput) -> Result<Trace> {
+    let mut steps = Vec::new();
+    for c in call_info.into_iter().flat_map(|call_info| call_info.iter()) {
+        for event in c.execution.events.iter().map(|e| &e.event) {
+            if event.keys.first().map(|k| k.0) != Some(selector!("opcode_executed")) {
+                continue;
+            }
+            steps.push(decode_step(&event.data.0)?);
+        }
+    }
+
+    Ok(Trace {
+        steps,
+        summary: TraceSummary {
+            output: output.return_data.clone().into(),
+            gas_used: U64::from(output.gas_used),
+            pass: output.success,
+            error: None,
+        },
+    })
+}
+
+/// Decodes the data of an `opcode_executed` event into a step.
+fn decode_step(data: &[Felt]) -> Result<TraceStep> {
+    let mut data = data.iter();
+    let mut next = |field: &str| {
+        data.next()
+            .copied()
+            .ok_or_else(|| eyre!("Missing {field} in opcode_executed event"))
+    };
+    let pc = felt_to_u64(next("pc")?)?;
+    let op: u8 = felt_to_u64(next("opcode")?)?
+        .try_into()
+        .map_err(|_| eyre!("Invalid opcode in opcode_executed event"))?;
+    let gas = felt_to_u64(next("gas")?)?;
+    let gas_cost = felt_to_u64(next("gas_cost")?)?;
+    let depth = felt_to_u64(next("depth")?)?;
+    let mem_size = felt_to_u64(next("mem_size")?)?;
+    let refund = felt_to_u64(next("refund")?)?;
+    let stack_len = felt_to_u64(next("stack_len")?)?;
+    let stack = (0..stack_len)
+        .map(|_| {
+            let low = U256::from_be_bytes(next("stack item")?.to_bytes_be());
+            let high = U256::from_be_bytes(next("stack item")?.to_bytes_be());
+            Ok(high << 128 | low)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(TraceStep {
+        pc,
+        op,
+        gas: U64::from(gas),
+        gas_cost: U64::from(gas_cost),
+        mem_size,
+        stack,
+        depth,
+        refund,
+        op_name: TraceStep::op_name(op),
+        error: None,
+    })
+}
+
+fn felt_to_u64(felt: Felt) -> Result<u64> {
+    felt.to_biguint()
+        .try_into()
+        .map_err(|_| eyre!("Error converting {felt} to u64"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_step() {
+        // Given
+        let data = [
+            Felt::from(2u8),       // pc
+            Felt::from(0x01u8),    // opcode
+            Felt::from(79_000u32), // gas
+            Felt::from(3u8),       // gas_cost
+            Felt::ONE,             // depth
+            Felt::ZERO,            // mem_size
+            Felt::ZERO,            // refund
+            Felt::from(2u8),       // stack_len
+            Felt::from(1u8),       // stack[0] low
+            Felt::ZERO,            // stack[0] high
+            Felt::from(2u8),       // stack[1] low
+            Felt::ONE,             // stack[1] high
+        ];
+
+        // When
+        let step = decode_step(&data).unwrap();
+
+        // Then
+        assert_eq!(
+            step,
+            TraceStep {
+                pc: 2,
+                op: 0x01,
+                gas: U64::from(79_000),
+                gas_cost: U64::from(3),
+                stack: vec![U256::from(1), U256::from(1) << 128 | U256::from(2)],
+                depth: 1,
+                op_name: "ADD".to_string(),
+                ..Default::default()
+            }
+        );
+        assert!(decode_step(&data[..9]).is_err());
+    }
+}
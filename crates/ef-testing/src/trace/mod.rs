@@ -0,0 +1,189 @@
+//! Opcode-level execution traces, in the EIP-3155 JSON-lines format
+//! (<https://eips.ethereum.org/EIPS/eip-3155>). Each line holds a step of the
+//! execution, and the last line the summary of the transaction, so that the
+//! traces of Kakarot can be diffed against the ones of geth or revm.
+pub mod divergence;
+pub mod kakarot;
+pub mod revm;
+
+use std::{fs, io::Write, path::Path};
+
+use alloy_primitives::{Bytes, U256, U64};
+use revm_interpreter::OpCode;
+use serde::{Deserialize, Serialize};
+
+/// Step of the execution, written before the opcode is executed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceStep {
+    pub pc: u64,
+    pub op: u8,
+    /// Gas left before the execution of the opcode.
+    pub gas: U64,
+    pub gas_cost: U64,
+    /// Size of the memory, in bytes.
+    #[serde(default)]
+    pub mem_size: u64,
+    /// Stack, with the top of the stack last.
+    pub stack: Vec<U256>,
+    pub depth: u64,
+    #[serde(default)]
+    pub refund: u64,
+    #[serde(default)]
+    pub op_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TraceStep {
+    /// Returns the name of the opcode, as written by geth.
+    pub fn op_name(op: u8) -> String {
+        OpCode::new(op)
+            .map(|op| op.as_str().to_string())
+            .unwrap_or_else(|| format!("opcode {op:#x} not defined"))
+    }
+}
+
+/// Summary of the execution of a transaction, written after the last step.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceSummary {
+    #[serde(default)]
+    pub output: Bytes,
+    pub gas_used: U64,
+    #[serde(default)]
+    pub pass: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Trace of the execution of a transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+    pub summary: TraceSummary,
+}
+
+impl Trace {
+    /// Parses the traces of the transactions held by the JSON lines, as written
+    /// by geth, revm or [`Trace::to_json_lines`]. Each summary line ends the trace
+    /// of a transaction, and the steps following the last summary, if any, are
+    /// returned as a trace with a default summary.
+    pub fn from_json_lines(lines: &str) -> Result<Vec<Self>, eyre::Error> {
+        let mut traces = Vec::new();
+        let mut steps = Vec::new();
+        for (index, line) in lines.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let value: serde_json::Value = serde_json::from_str(line)
+                .map_err(|err| eyre::eyre!("invalid trace line {}: {err}", index + 1))?;
+            if value.get("pc").is_some() {
+                steps.push(serde_json::from_value(value)?);
+            } else {
+                traces.push(Self {
+                    steps: std::mem::take(&mut steps),
+                    summary: serde_json::from_value(value)?,
+                });
+            }
+        }
+        if !steps.is_empty() {
+            traces.push(Self {
+                steps,
+                summary: TraceSummary::default(),
+            });
+        }
+        Ok(traces)
+    }
+
+    /// Reads the traces of the transactions held by the JSON lines file.
+    pub fn read_json_lines(path: &Path) -> Result<Vec<Self>, eyre::Error> {
+        Self::from_json_lines(&fs::read_to_string(path)?)
+    }
+
+    /// Returns the trace as JSON lines: one line per step, followed by the summary.
+    pub fn to_json_lines(&self) -> Result<String, eyre::Error> {
+        let mut lines = String::new();
+        for step in self.steps.iter() {
+            lines += &serde_json::to_string(step)?;
+            lines.push('\n');
+        }
+        lines += &serde_json::to_string(&self.summary)?;
+        lines.push('\n');
+        Ok(lines)
+    }
+
+    /// Writes the trace as JSON lines to the given path, creating the parent
+    /// directories if needed.
+    pub fn write_json_lines(&self, path: &Path) -> Result<(), eyre::Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::File::create(path)?;
+        file.write_all(self.to_json_lines()?.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::bytes;
+
+    #[test]
+    fn test_trace_to_json_lines() {
+        // Given
+        let trace = Trace {
+            steps: vec![TraceStep {
+                pc: 0,
+                op: 0x60,
+                gas: U64::from(79_000),
+                gas_cost: U64::from(3),
+                stack: vec![U256::from(1)],
+                depth: 1,
+                op_name: TraceStep::op_name(0x60),
+                ..Default::default()
+            }],
+            summary: TraceSummary {
+                output: bytes!("01"),
+                gas_used: U64::from(21_003),
+                pass: true,
+                error: None,
+            },
+        };
+
+        // When
+        let lines = trace.to_json_lines().unwrap();
+
+        // Then
+        assert_eq!(
+            lines,
+            "{\"pc\":0,\"op\":96,\"gas\":\"0x13498\",\"gasCost\":\"0x3\",\"memSize\":0,\"stack\":[\"0x1\"],\"depth\":1,\"refund\":0,\"opName\":\"PUSH1\"}\n\
+             {\"output\":\"0x01\",\"gasUsed\":\"0x520b\",\"pass\":true}\n"
+        );
+        assert_eq!(Trace::from_json_lines(&lines).unwrap(), vec![trace]);
+    }
+
+    #[test]
+    fn test_trace_from_geth_json_lines() {
+        // Given
+        let lines = r#"
+{"pc":0,"op":96,"gas":"0x2540be400","gasCost":"0x3","memSize":0,"stack":[],"depth":1,"refund":0,"opName":"PUSH1"}
+{"pc":2,"op":0,"gas":"0x2540be3fd","gasCost":"0x0","memSize":0,"stack":["0x40"],"depth":1,"refund":0,"opName":"STOP"}
+{"output":"","gasUsed":"0x3"}
+{"pc":0,"op":0,"gas":"0x5208","gasCost":"0x0","memSize":0,"stack":[],"depth":1,"refund":0,"opName":"STOP"}
+"#;
+
+        // When
+        let traces = Trace::from_json_lines(lines).unwrap();
+
+        // Then
+        assert_eq!(traces.len(), 2);
+        assert_eq!(traces[0].steps.len(), 2);
+        assert_eq!(traces[0].steps[1].stack, vec![U256::from(0x40)]);
+        assert_eq!(traces[0].summary.gas_used, U64::from(3));
+        assert_eq!(traces[1].steps.len(), 1);
+        assert_eq!(traces[1].summary, TraceSummary::default());
+    }
+}
@@ -0,0 +1,67 @@
+//! Collection of the traces of the transactions of a block executed by revm.
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    io::{self, Write},
+    rc::Rc,
+};
+
+use ::revm::{inspector_handle_register, inspectors::TracerEip3155, Evm};
+use alloy_primitives::Address;
+use ef_tests::models::Account;
+use reth_primitives::SealedBlock;
+
+use super::Trace;
+use crate::differential::revm::{block_env, pre_state_db, spec_id, tx_env};
+use crate::models::{error::RunnerError, Fork};
+
+/// Buffer shared with the tracer of revm, which requires an owned writer.
+#[derive(Debug, Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Executes the transactions of the block in revm from the pre-state, as done
+/// by [`crate::differential::revm::execute_block`], and returns the trace of
+/// each transaction.
+pub fn trace_block(
+    pre: &BTreeMap<Address, Account>,
+    block: &SealedBlock,
+    fork: Fork,
+    chain_id: u64,
+) -> Result<Vec<Trace>, RunnerError> {
+    let mut db = pre_state_db(pre)?;
+    let buffer = SharedBuffer::default();
+    {
+        let mut evm = Evm::builder()
+            .with_db(&mut db)
+            .with_external_context(TracerEip3155::new(Box::new(buffer.clone())))
+            .modify_cfg_env(|cfg| cfg.chain_id = chain_id)
+            .modify_block_env(|env| *env = block_env(block))
+            .with_spec_id(spec_id(fork))
+            .append_handler_register(inspector_handle_register)
+            .build();
+
+        for tx in block.body.transactions.iter() {
+            let sender = tx.recover_signer().ok_or_else(|| {
+                RunnerError::Other(vec!["revm: unable to recover the sender".to_string()].into())
+            })?;
+            *evm.tx_mut() = tx_env(&tx.transaction, sender);
+            evm.transact_commit().map_err(|err| {
+                RunnerError::Other(vec![format!("revm rejected the transaction: {err}")].into())
+            })?;
+        }
+    }
+
+    let lines = String::from_utf8(buffer.0.take())
+        .map_err(|err| RunnerError::Other(vec![format!("invalid revm trace: {err}")].into()))?;
+    Ok(Trace::from_json_lines(&lines)?)
+}
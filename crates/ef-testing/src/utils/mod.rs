@@ -1,6 +1,8 @@
 use std::collections::BTreeMap;
 
-use alloy_primitives::{Address, Bytes, U256};
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use alloy_rlp::{Encodable, Header};
+use alloy_trie::{HashBuilder, Nibbles};
 use ef_tests::models::{Account, State};
 
 pub(crate) fn update_post_state(
@@ -28,6 +30,61 @@ pub(crate) fn update_post_state(
     post_state
 }
 
+/// Computes the root of a Merkle Patricia Trie from unhashed keys and RLP encoded values.
+fn trie_root(leaves: impl IntoIterator<Item = (B256, Vec<u8>)>) -> B256 {
+    let mut leaves: Vec<_> = leaves
+        .into_iter()
+        .map(|(key, value)| (keccak256(key), value))
+        .collect();
+    leaves.sort_unstable_by_key(|(key, _)| *key);
+
+    let mut hash_builder = HashBuilder::default();
+    for (key, value) in leaves {
+        hash_builder.add_leaf(Nibbles::unpack(key), &value);
+    }
+    hash_builder.root()
+}
+
+/// Computes the storage root of an account. Zero values are not part of the trie.
+pub(crate) fn storage_root(storage: &BTreeMap<U256, U256>) -> B256 {
+    trie_root(
+        storage
+            .iter()
+            .filter(|(_, value)| !value.is_zero())
+            .map(|(key, value)| (B256::from(*key), alloy_rlp::encode(value))),
+    )
+}
+
+/// Computes the state root of the given accounts.
+pub(crate) fn state_root(accounts: &BTreeMap<Address, Account>) -> Result<B256, eyre::Error> {
+    let leaves = accounts
+        .iter()
+        .map(|(address, account)| {
+            let nonce: u64 = account.nonce.try_into()?;
+            let storage_root = storage_root(&account.storage);
+            let code_hash = keccak256(&account.code);
+
+            let payload_length = nonce.length()
+                + account.balance.length()
+                + storage_root.length()
+                + code_hash.length();
+            let mut rlp_account = Vec::with_capacity(payload_length + 3);
+            Header {
+                list: true,
+                payload_length,
+            }
+            .encode(&mut rlp_account);
+            nonce.encode(&mut rlp_account);
+            account.balance.encode(&mut rlp_account);
+            storage_root.encode(&mut rlp_account);
+            code_hash.encode(&mut rlp_account);
+
+            Ok((address.into_word(), rlp_account))
+        })
+        .collect::<Result<Vec<_>, eyre::Error>>()?;
+    Ok(trie_root(leaves))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -59,4 +116,40 @@ mod tests {
         // Assert the updated state
         assert_eq!(updated_state, expected_post_state);
     }
+
+    #[test]
+    fn test_state_root_empty() {
+        let state_root = state_root(&BTreeMap::new()).unwrap();
+        assert_eq!(state_root, alloy_trie::EMPTY_ROOT_HASH);
+    }
+
+    #[test]
+    fn test_state_root_of_fixture_post_state() {
+        // Given
+        let content = std::fs::read_to_string(
+            "ethereum-tests/BlockchainTests/GeneralStateTests/VmTests/vmArithmeticTest/add.json",
+        )
+        .unwrap();
+        let cases: BTreeMap<String, serde_json::Value> = serde_json::from_str(&content).unwrap();
+
+        for (case_name, case) in cases {
+            let post_state: BTreeMap<Address, Account> =
+                serde_json::from_value(case["postState"].clone()).unwrap();
+            let last_block = case["blocks"].as_array().unwrap().last().unwrap();
+            let expected: B256 =
+                serde_json::from_value(last_block["blockHeader"]["stateRoot"].clone()).unwrap();
+
+            // When
+            let root = state_root(&post_state).unwrap();
+
+            // Then
+            assert_eq!(root, expected, "state root mismatch for {case_name}");
+        }
+    }
+
+    #[test]
+    fn test_storage_root_ignores_zero_values() {
+        let storage = BTreeMap::from([(U256::from(1), U256::ZERO)]);
+        assert_eq!(storage_root(&storage), alloy_trie::EMPTY_ROOT_HASH);
+    }
 }
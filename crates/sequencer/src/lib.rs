@@ -2,6 +2,7 @@ pub mod commit;
 pub mod constants;
 pub mod execution;
 pub mod native;
+pub mod profiling;
 pub mod sequencer;
 pub mod serde;
 pub mod state;
@@ -0,0 +1,265 @@
+//! Profiling of the Cairo steps of a class executed by the sequencer.
+//!
+//! Blockifier runs the Cairo VM without tracing. The call to the profiled class
+//! made by a transaction is therefore replayed, from the state preceding the
+//! transaction, by a Cairo VM with tracing enabled. The call stacks are rebuilt
+//! from the frame pointers of the trace and the functions are named after the
+//! identifiers of the program, so that the profile can be written as folded
+//! stacks and rendered by flamegraph tooling.
+//!
+//! Only Cairo 0 classes, whose programs hold their identifiers, are supported.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use blockifier::context::TransactionContext;
+use blockifier::execution::contract_class::CompiledClassV0;
+use blockifier::execution::deprecated_entry_point_execution::{
+    prepare_call_arguments, resolve_entry_point_pc, run_entry_point,
+};
+use blockifier::execution::deprecated_syscalls::hint_processor::DeprecatedSyscallHintProcessor;
+use blockifier::execution::entry_point::{CallEntryPoint, EntryPointExecutionContext};
+use blockifier::state::state_api::State;
+use cairo_vm::types::layout_name::LayoutName;
+use cairo_vm::types::program::Program;
+use cairo_vm::vm::runners::cairo_runner::CairoRunner;
+use cairo_vm::vm::trace::trace_entry::RelocatedTraceEntry;
+use starknet_api::core::ClassHash;
+
+/// Address of the program segment once relocated.
+const PROGRAM_BASE: usize = 1;
+
+/// Name of the frames whose function isn't found in the program.
+const UNKNOWN_FUNCTION: &str = "<unknown>";
+
+/// Functions of a Cairo 0 program, indexed by the pc of their first instruction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionMap(BTreeMap<usize, String>);
+
+impl FunctionMap {
+    pub fn new(program: &Program) -> Self {
+        Self(
+            program
+                .iter_identifiers()
+                .filter(|(_, identifier)| identifier.type_.as_deref() == Some("function"))
+                .filter_map(|(name, identifier)| Some((identifier.pc?, name.to_string())))
+                .collect(),
+        )
+    }
+
+    /// Returns the name of the function containing the instruction at the pc.
+    pub fn function_at(&self, pc: usize) -> &str {
+        self.0
+            .range(..=pc)
+            .next_back()
+            .map_or(UNKNOWN_FUNCTION, |(_, name)| name.as_str())
+    }
+}
+
+/// Number of Cairo steps executed by each call stack, the stacks being formatted
+/// as folded stacks: the names of the functions separated by semicolons, from
+/// the entry point to the executing function.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    stacks: BTreeMap<String, usize>,
+}
+
+impl Profile {
+    /// Records the steps of the relocated trace of a call. A frame is entered
+    /// when the frame pointer grows, and left when it goes back to the one of a
+    /// calling frame.
+    pub fn record(&mut self, functions: &FunctionMap, trace: &[RelocatedTraceEntry]) {
+        let mut frames: Vec<(usize, &str)> = Vec::new();
+        let mut stack = String::new();
+        for entry in trace {
+            let mut changed = false;
+            while frames.last().is_some_and(|(fp, _)| *fp > entry.fp) {
+                frames.pop();
+                changed = true;
+            }
+            if frames.last().map(|(fp, _)| *fp) != Some(entry.fp) {
+                let pc = entry.pc.saturating_sub(PROGRAM_BASE);
+                frames.push((entry.fp, functions.function_at(pc)));
+                changed = true;
+            }
+            if changed {
+                stack = frames
+                    .iter()
+                    .map(|(_, function)| *function)
+                    .collect::<Vec<_>>()
+                    .join(";");
+            }
+            *self.stacks.entry(stack.clone()).or_default() += 1;
+        }
+    }
+
+    /// Adds the steps of the other profile.
+    pub fn merge(&mut self, other: &Self) {
+        for (stack, steps) in other.stacks.iter() {
+            *self.stacks.entry(stack.clone()).or_default() += steps;
+        }
+    }
+
+    /// Returns the total number of recorded steps.
+    pub fn steps(&self) -> usize {
+        self.stacks.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+
+    /// Formats the profile as folded stacks, one `<stack> <steps>` line per
+    /// call stack.
+    pub fn to_folded(&self) -> String {
+        self.stacks
+            .iter()
+            .map(|(stack, steps)| format!("{stack} {steps}\n"))
+            .collect()
+    }
+
+    /// Writes the profile as folded stacks, creating the parent directories.
+    pub fn write_folded(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_folded())
+    }
+}
+
+/// Profiler of the calls to a class.
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    pub(crate) class_hash: ClassHash,
+    /// Functions of the program of the class, loaded on its first call.
+    pub(crate) functions: Option<FunctionMap>,
+    pub(crate) profile: Profile,
+}
+
+impl Profiler {
+    pub const fn new(class_hash: ClassHash) -> Self {
+        Self {
+            class_hash,
+            functions: None,
+            profile: Profile {
+                stacks: BTreeMap::new(),
+            },
+        }
+    }
+
+    pub const fn class_hash(&self) -> ClassHash {
+        self.class_hash
+    }
+
+    pub const fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Takes the profile recorded so far, leaving an empty profile.
+    pub fn take_profile(&mut self) -> Profile {
+        std::mem::take(&mut self.profile)
+    }
+}
+
+/// Replays the call to the Cairo 0 class with a traced Cairo VM, returning the
+/// relocated trace of the call. The inner calls are executed by blockifier and
+/// aren't traced.
+pub(crate) fn trace_call(
+    state: &mut dyn State,
+    tx_context: Arc<TransactionContext>,
+    call: &CallEntryPoint,
+    class_hash: ClassHash,
+    class: &CompiledClassV0,
+) -> eyre::Result<Vec<RelocatedTraceEntry>> {
+    let mut context = EntryPointExecutionContext::new_invoke(tx_context, false);
+    let entry_point_pc = resolve_entry_point_pc(call, class)?;
+
+    let proof_mode = false;
+    let trace_enabled = true;
+    let mut runner = CairoRunner::new(
+        &class.program,
+        LayoutName::starknet,
+        proof_mode,
+        trace_enabled,
+    )?;
+    runner.initialize_builtins(false)?;
+    runner.initialize_segments(None);
+
+    let initial_syscall_ptr = runner.vm.add_memory_segment();
+    let mut syscall_handler = DeprecatedSyscallHintProcessor::new(
+        state,
+        &mut context,
+        initial_syscall_ptr,
+        call.storage_address,
+        call.caller_address,
+        class_hash,
+    );
+    let (_, args) = prepare_call_arguments(
+        call,
+        &mut runner,
+        initial_syscall_ptr,
+        &mut syscall_handler.read_only_segments,
+    )?;
+    run_entry_point(&mut runner, &mut syscall_handler, entry_point_pc, args)?;
+
+    runner.relocate(false)?;
+    Ok(runner.relocated_trace.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pc: usize, fp: usize) -> RelocatedTraceEntry {
+        RelocatedTraceEntry { pc, ap: fp, fp }
+    }
+
+    #[test]
+    fn test_record() {
+        // Given
+        let functions = FunctionMap(BTreeMap::from([
+            (0, "main".to_string()),
+            (10, "foo".to_string()),
+            (20, "bar".to_string()),
+        ]));
+        // main calls foo, which calls bar, then main calls bar.
+        let trace = [
+            entry(PROGRAM_BASE, 100),
+            entry(PROGRAM_BASE + 1, 100),
+            entry(PROGRAM_BASE + 10, 110),
+            entry(PROGRAM_BASE + 20, 120),
+            entry(PROGRAM_BASE + 21, 120),
+            entry(PROGRAM_BASE + 11, 110),
+            entry(PROGRAM_BASE + 2, 100),
+            entry(PROGRAM_BASE + 20, 105),
+            entry(PROGRAM_BASE + 3, 100),
+        ];
+        let mut profile = Profile::default();
+
+        // When
+        profile.record(&functions, &trace);
+
+        // Then
+        assert_eq!(profile.steps(), 9);
+        assert_eq!(
+            profile.to_folded(),
+            "main 4\nmain;bar 1\nmain;foo 2\nmain;foo;bar 2\n"
+        );
+    }
+
+    #[test]
+    fn test_function_at() {
+        // Given
+        let functions = FunctionMap(BTreeMap::from([
+            (5, "foo".to_string()),
+            (10, "bar".to_string()),
+        ]));
+
+        // When / Then
+        assert_eq!(functions.function_at(0), UNKNOWN_FUNCTION);
+        assert_eq!(functions.function_at(5), "foo");
+        assert_eq!(functions.function_at(9), "foo");
+        assert_eq!(functions.function_at(42), "bar");
+    }
+}
@@ -1,6 +1,13 @@
-use crate::{commit::Committer, execution::Execution};
+use std::sync::Arc;
+
+use crate::{
+    commit::Committer,
+    execution::Execution,
+    profiling::{trace_call, FunctionMap, Profiler},
+};
 use blockifier::{
     context::BlockContext,
+    execution::contract_class::RunnableCompiledClass,
     state::{
         cached_state::CachedState,
         state_api::{State, StateReader},
@@ -11,8 +18,9 @@ use blockifier::{
         transactions::ExecutableTransaction,
     },
 };
-use starknet_api::core::ContractAddress;
+use starknet_api::core::{ClassHash, ContractAddress};
 use starknet_api::executable_transaction::AccountTransaction;
+use tracing::warn;
 
 /// Sequencer is the main struct of the sequencer crate.
 #[derive(Clone)]
@@ -21,6 +29,8 @@ pub struct Sequencer<S, A> {
     pub(crate) block_context: BlockContext,
     pub(crate) state: S,
     pub(crate) address: A,
+    /// Profiler of the calls to a class, if profiling is enabled.
+    pub(crate) profiler: Option<Profiler>,
 }
 
 impl<S, A> Sequencer<S, A> {
@@ -32,6 +42,7 @@ impl<S, A> Sequencer<S, A> {
             block_context,
             state,
             address,
+            profiler: None,
         }
     }
 
@@ -39,6 +50,13 @@ impl<S, A> Sequencer<S, A> {
         &self.block_context
     }
 
+    /// Sets the context of the next executed block and the address of the
+    /// sequencer producing it, keeping the state.
+    pub fn set_block_context(&mut self, block_context: BlockContext, address: A) {
+        self.block_context = block_context;
+        self.address = address;
+    }
+
     /// Returns a reference to the state.
     pub const fn state(&self) -> &S {
         &self.state
@@ -53,6 +71,75 @@ impl<S, A> Sequencer<S, A> {
     pub const fn address(&self) -> &A {
         &self.address
     }
+
+    /// Profiles the Cairo steps of the calls to the Cairo 0 class made by the
+    /// executed transactions. See [`crate::profiling`].
+    pub fn enable_profiling(&mut self, class_hash: ClassHash) {
+        self.profiler = Some(Profiler::new(class_hash));
+    }
+
+    /// Returns the profiler, if profiling is enabled.
+    pub const fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Returns a mutable reference to the profiler, if profiling is enabled.
+    pub fn profiler_mut(&mut self) -> Option<&mut Profiler> {
+        self.profiler.as_mut()
+    }
+}
+
+impl<S, A> Sequencer<S, A>
+where
+    for<'any> &'any mut S: State + StateReader + Committer<S>,
+{
+    /// Profiles the first call to the profiled class made by the transaction.
+    /// The transaction is dry run to find the call, which is then replayed with
+    /// a traced Cairo VM from the state preceding the transaction. Both runs are
+    /// discarded.
+    fn profile(&mut self, transaction: &Transaction) -> eyre::Result<()> {
+        let Some(profiler) = self.profiler.as_mut() else {
+            return Ok(());
+        };
+        let Transaction::Account(account_tx) = transaction else {
+            return Ok(());
+        };
+
+        let execution_information = {
+            let mut dry_run_state = CachedState::new(&mut self.state);
+            transaction.execute(&mut dry_run_state, &self.block_context, false, true)?
+        };
+        let Some(call) = execution_information
+            .execute_call_info
+            .iter()
+            .flat_map(|call_info| call_info.iter())
+            .find(|call_info| call_info.call.class_hash == Some(profiler.class_hash))
+            .map(|call_info| call_info.call.clone())
+        else {
+            return Ok(());
+        };
+
+        let mut replay_state = CachedState::new(&mut self.state);
+        let RunnableCompiledClass::V0(class) =
+            replay_state.get_compiled_class(profiler.class_hash)?
+        else {
+            eyre::bail!("only Cairo 0 classes can be profiled");
+        };
+        let tx_context = Arc::new(self.block_context.to_tx_context(account_tx));
+        let trace = trace_call(
+            &mut replay_state,
+            tx_context,
+            &call,
+            profiler.class_hash,
+            &class,
+        )?;
+
+        let functions = profiler
+            .functions
+            .get_or_insert_with(|| FunctionMap::new(&class.program));
+        profiler.profile.record(functions, &trace);
+        Ok(())
+    }
 }
 
 /// Using a trait bound for the state allows for better
@@ -84,6 +171,10 @@ where
             Transaction::L1Handler(_) => ContractAddress::from(0u8),
         };
 
+        if let Err(err) = self.profile(&transaction) {
+            warn!("failed to profile transaction: {err}");
+        }
+
         let mut cached_state = CachedState::new(&mut self.state);
         let charge_fee = false;
         let validate = true;
@@ -304,4 +395,42 @@ mod tests {
 
     sequencer_test!(CairoVersion::V0, test_sequencer_cairo_0);
     sequencer_test!(CairoVersion::V1, test_sequencer_cairo_1);
+
+    #[test]
+    fn test_profile_cairo_0() {
+        // Given
+        let mut state = State::default();
+        let mutable = &mut state;
+        declare_and_deploy_contract(
+            "src/test_data/cairo_0/compiled_classes/counter.json",
+            *TEST_CONTRACT,
+            ClassHash(Felt::ONE),
+            mutable,
+            CairoVersion::V0,
+        );
+        declare_and_deploy_contract(
+            "src/test_data/cairo_0/compiled_classes/account.json",
+            *TEST_ACCOUNT,
+            ClassHash(Felt::TWO),
+            mutable,
+            CairoVersion::V0,
+        );
+        fund(*TEST_ACCOUNT.0.key(), mutable);
+        let mut sequencer = Sequencer::new(block_context(), state, 0);
+        sequencer.enable_profiling(ClassHash(Felt::ONE));
+
+        // When
+        sequencer.execute(test_transaction()).unwrap();
+
+        // Then
+        let profile = sequencer.profiler().unwrap().profile();
+        assert!(profile.to_folded().lines().any(|line| line.starts_with(
+            "__wrappers__.inc;__main__.inc;__main__.counter.write;starkware.starknet.common.syscalls.storage_write "
+        )));
+        let expected = Felt::ONE;
+        let actual = (&mut sequencer.state)
+            .get_storage_at(*TEST_CONTRACT, get_storage_var_address("counter", &[]))
+            .unwrap();
+        assert_eq!(expected, actual);
+    }
 }
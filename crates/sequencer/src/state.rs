@@ -5,7 +5,7 @@ use blockifier::state::errors::StateError;
 use blockifier::state::state_api::{
     State as BlockifierState, StateReader as BlockifierStateReader, StateResult,
 };
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use serde::{Deserialize, Serialize};
 use starknet::core::types::Felt;
 use starknet_api::core::CompiledClassHash;
@@ -17,12 +17,16 @@ pub type ContractStorageKey = (ContractAddress, StorageKey);
 /// Generic state structure for the sequencer.
 /// The use of `HashMap` implementation from hashbrown allows for a better performance.
 /// See [Performance](https://github.com/rust-lang/hashbrown?tab=readme-ov-file#performance)
+/// The storage keys of each contract are indexed, and the index is rebuilt from
+/// the storage when the state is deserialized.
 #[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(from = "SerializableState", into = "SerializableState")]
 pub struct State {
     classes: HashMap<ClassHash, RunnableCompiledClass>,
     compiled_class_hashes: HashMap<ClassHash, CompiledClassHash>,
     contracts: HashMap<ContractAddress, ClassHash>,
     storage: HashMap<ContractStorageKey, Felt>,
+    storage_keys: HashMap<ContractAddress, HashSet<StorageKey>>,
     nonces: HashMap<ContractAddress, Nonce>,
 }
 
@@ -40,11 +44,17 @@ impl From<State> for SerializableState {
 
 impl From<SerializableState> for State {
     fn from(serializable_state: SerializableState) -> Self {
+        let mut storage_keys: HashMap<ContractAddress, HashSet<StorageKey>> = HashMap::new();
+        for (address, key) in serializable_state.storage.keys() {
+            storage_keys.entry(*address).or_default().insert(*key);
+        }
+
         Self {
             classes: serializable_state.classes,
             compiled_class_hashes: serializable_state.compiled_classes_hash,
             contracts: serializable_state.contracts,
             storage: serializable_state.storage,
+            storage_keys,
             nonces: serializable_state.nonces,
         }
     }
@@ -55,6 +65,23 @@ impl State {
     pub fn set_nonce(&mut self, contract_address: ContractAddress, nonce: Nonce) {
         self.nonces.insert(contract_address, nonce);
     }
+
+    /// Returns an iterator over the deployed contracts and their class hash.
+    pub fn contracts(&self) -> impl Iterator<Item = (&ContractAddress, &ClassHash)> {
+        self.contracts.iter()
+    }
+
+    /// Returns an iterator over the storage entries of the contract.
+    pub fn storage_of(
+        &self,
+        contract_address: ContractAddress,
+    ) -> impl Iterator<Item = (&StorageKey, &Felt)> {
+        self.storage_keys
+            .get(&contract_address)
+            .into_iter()
+            .flatten()
+            .filter_map(move |key| Some((key, self.storage.get(&(contract_address, *key))?)))
+    }
 }
 
 impl Committer<State> for &mut State {}
@@ -69,6 +96,10 @@ impl BlockifierState for &mut State {
         value: Felt,
     ) -> StateResult<()> {
         self.storage.insert((contract_address, key), value);
+        self.storage_keys
+            .entry(contract_address)
+            .or_default()
+            .insert(key);
         Ok(())
     }
 
@@ -188,7 +219,9 @@ impl BlockifierStateReader for &mut State {
 mod tests {
     use blockifier::execution::contract_class::CompiledClassV0;
 
-    use crate::constants::test_constants::{ONE_PATRICIA, TEST_CONTRACT};
+    use crate::constants::test_constants::{
+        ONE_PATRICIA, TEST_ACCOUNT, TEST_CONTRACT, TEST_STORAGE_KEY,
+    };
 
     use super::*;
 
@@ -240,6 +273,56 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_contracts() {
+        // Given
+        let mut state = &mut State::default();
+
+        // When
+        state
+            .set_class_hash_at(*TEST_CONTRACT, ClassHash(Felt::ONE))
+            .unwrap();
+
+        // Then
+        let contracts: Vec<_> = state.contracts().collect();
+        assert_eq!(contracts, vec![(&*TEST_CONTRACT, &ClassHash(Felt::ONE))]);
+    }
+
+    #[test]
+    fn test_storage_of() {
+        // Given
+        let mut state = &mut State::default();
+
+        // When
+        state
+            .set_storage_at(*TEST_CONTRACT, *TEST_STORAGE_KEY, Felt::ONE)
+            .unwrap();
+        state
+            .set_storage_at(*TEST_ACCOUNT, *TEST_STORAGE_KEY, Felt::TWO)
+            .unwrap();
+
+        // Then
+        let storage: Vec<_> = state.storage_of(*TEST_CONTRACT).collect();
+        assert_eq!(storage, vec![(&*TEST_STORAGE_KEY, &Felt::ONE)]);
+    }
+
+    #[test]
+    fn test_storage_of_serializable_state() {
+        // Given
+        let mut state = &mut State::default();
+        state
+            .set_storage_at(*TEST_CONTRACT, *TEST_STORAGE_KEY, Felt::ONE)
+            .unwrap();
+
+        // When
+        let serializable_state: SerializableState = state.clone().into();
+        let state: State = serializable_state.into();
+
+        // Then
+        let storage: Vec<_> = state.storage_of(*TEST_CONTRACT).collect();
+        assert_eq!(storage, vec![(&*TEST_STORAGE_KEY, &Felt::ONE)]);
+    }
+
     #[test]
     fn test_contract_class() {
         // Given
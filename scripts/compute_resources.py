@@ -22,12 +22,15 @@ def get_resource_usage(path: str = "./test.out"):
     # Remove ANSI escape sequences
     cleaned_output = re.sub(r"\x1b\[[0-9;]*[a-zA-Z]", "", result)
     matches = re.findall(
-        r"ef_testing::models::result: (.*) passed: .?ResourcesMapping\((.*)\)",
+        r"ef_testing::models::result: (.*) passed: (\{.*\})",
         cleaned_output,
     )
-    tests_resources = [
-        {**json.loads(resources), "test": test_name} for test_name, resources in matches
-    ]
+    tests_resources = []
+    for test_name, resources in matches:
+        resources = json.loads(resources)
+        # Flatten the builtins so that each one gets its own column
+        builtins = resources.pop("builtins", {})
+        tests_resources.append({**resources, **builtins, "test": test_name})
     return tests_resources
 
 